@@ -0,0 +1,1451 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! The Lua API, described once.
+//!
+//! Every method and field the wrapper exposes to Lua is declared here with
+//! its parameters, return types and documentation. The `UserData` impls
+//! register through a [`Registrar`], which refuses names that are not declared
+//! and fails if a declared one is left out, and `mcrstw gen-types` renders the
+//! same data as the LuaLS stubs in `tools/lua-types/mcrw.lua`. A test keeps the
+//! committed stubs identical to [`lua_stubs`].
+
+use std::fmt::Write as _;
+use std::marker::PhantomData;
+
+use mlua::{
+    FromLuaMulti, IntoLua, IntoLuaMulti, Lua, MaybeSend, UserDataFields, UserDataMethods,
+    UserDataRef,
+};
+
+/// A parameter, field or return value: name (with a trailing `?` when
+/// optional), LuaLS type, and a one-line description.
+pub struct Param {
+    pub name: &'static str,
+    pub ty: &'static str,
+    pub doc: &'static str,
+}
+
+const fn p(name: &'static str, ty: &'static str, doc: &'static str) -> Param {
+    Param { name, ty, doc }
+}
+
+// A return value has no name.
+const fn ret(ty: &'static str, doc: &'static str) -> Param {
+    Param { name: "", ty, doc }
+}
+
+pub struct Method {
+    pub name: &'static str,
+    pub doc: &'static str,
+    /// Registered with `add_async_method`: yields the calling coroutine.
+    pub is_async: bool,
+    pub generics: &'static [&'static str],
+    pub params: &'static [Param],
+    pub returns: &'static [Param],
+}
+
+impl Method {
+    const fn new(name: &'static str, doc: &'static str) -> Self {
+        Method {
+            name,
+            doc,
+            is_async: false,
+            generics: &[],
+            params: &[],
+            returns: &[],
+        }
+    }
+
+    const fn asynchronous(self) -> Self {
+        Method {
+            is_async: true,
+            ..self
+        }
+    }
+
+    const fn generics(self, generics: &'static [&'static str]) -> Self {
+        Method { generics, ..self }
+    }
+
+    const fn params(self, params: &'static [Param]) -> Self {
+        Method { params, ..self }
+    }
+
+    const fn returns(self, returns: &'static [Param]) -> Self {
+        Method { returns, ..self }
+    }
+}
+
+/// How a class is declared in the stubs.
+pub enum Decl {
+    /// A data shape only (`---@class` and fields).
+    Shape,
+    /// `local <name> = {}`, holding the methods.
+    Local(&'static str),
+    /// A global, like `Server`.
+    Global(&'static str),
+}
+
+pub struct Class {
+    pub name: &'static str,
+    pub doc: &'static str,
+    pub decl: Decl,
+    pub fields: &'static [Param],
+    pub methods: &'static [Method],
+}
+
+impl Class {
+    pub fn method(&self, name: &str) -> Option<&'static Method> {
+        // `self` is one of the statics below.
+        let methods: &'static [Method] = self.methods;
+        methods.iter().find(|m| m.name == name)
+    }
+}
+
+pub enum Item {
+    Section(&'static str),
+    Alias {
+        name: &'static str,
+        ty: &'static str,
+        doc: &'static str,
+    },
+    Class(&'static Class),
+}
+
+const fn alias(name: &'static str, ty: &'static str, doc: &'static str) -> Item {
+    Item::Alias { name, ty, doc }
+}
+
+// ---------------------------------------------------------------------------
+// Registration
+// ---------------------------------------------------------------------------
+
+enum Kind {
+    Methods,
+    Fields,
+}
+
+/// Registers a `UserData` type's methods or fields against its [`Class`].
+/// Panics (on the type's first use in a Lua state) when the Rust side and
+/// the declaration disagree; the tests below create every type, so this
+/// cannot reach a release.
+pub struct Registrar<'a, T, R> {
+    class: &'static Class,
+    reg: &'a mut R,
+    kind: Kind,
+    done: Vec<&'static str>,
+    _type: PhantomData<T>,
+}
+
+impl<'a, T, R> Registrar<'a, T, R> {
+    pub fn methods(class: &'static Class, reg: &'a mut R) -> Self {
+        Registrar {
+            class,
+            reg,
+            kind: Kind::Methods,
+            done: Vec::new(),
+            _type: PhantomData,
+        }
+    }
+
+    pub fn fields(class: &'static Class, reg: &'a mut R) -> Self {
+        Registrar {
+            class,
+            reg,
+            kind: Kind::Fields,
+            done: Vec::new(),
+            _type: PhantomData,
+        }
+    }
+
+    fn declared(&self) -> impl Iterator<Item = &'static str> {
+        let class = self.class;
+        let names: Vec<&'static str> = match self.kind {
+            Kind::Methods => class.methods.iter().map(|m| m.name).collect(),
+            Kind::Fields => class.fields.iter().map(|f| f.name).collect(),
+        };
+        names.into_iter()
+    }
+
+    fn claim(&mut self, name: &str) -> &'static str {
+        let Some(declared) = self.declared().find(|d| *d == name) else {
+            panic!(
+                "api_spec: {} registers '{name}', which is not declared",
+                self.class.name
+            );
+        };
+        assert!(
+            !self.done.contains(&declared),
+            "api_spec: {} registers '{name}' twice",
+            self.class.name
+        );
+        self.done.push(declared);
+        declared
+    }
+
+    /// Check that everything declared was registered.
+    pub fn finish(self) {
+        let missing: Vec<&str> = self.declared().filter(|d| !self.done.contains(d)).collect();
+        assert!(
+            missing.is_empty(),
+            "api_spec: {} declares {} but never registers them",
+            self.class.name,
+            missing.join(", ")
+        );
+    }
+
+    fn check_async(&self, name: &str, is_async: bool) {
+        let declared = self.class.method(name).is_some_and(|m| m.is_async);
+        assert_eq!(
+            declared,
+            is_async,
+            "api_spec: {}:{name} is registered {}async but declared otherwise",
+            self.class.name,
+            if is_async { "" } else { "non-" }
+        );
+    }
+}
+
+impl<T: 'static, M: UserDataMethods<T>> Registrar<'_, T, M> {
+    pub fn method<F, A, R>(&mut self, name: &str, method: F)
+    where
+        F: Fn(&Lua, &T, A) -> mlua::Result<R> + MaybeSend + 'static,
+        A: FromLuaMulti,
+        R: IntoLuaMulti,
+    {
+        let name = self.claim(name);
+        self.check_async(name, false);
+        self.reg.add_method(name, method);
+    }
+
+    pub fn async_method<F, A, MR, R>(&mut self, name: &str, method: F)
+    where
+        F: Fn(Lua, UserDataRef<T>, A) -> MR + MaybeSend + 'static,
+        A: FromLuaMulti,
+        MR: Future<Output = mlua::Result<R>> + MaybeSend + 'static,
+        R: IntoLuaMulti,
+    {
+        let name = self.claim(name);
+        self.check_async(name, true);
+        self.reg.add_async_method(name, method);
+    }
+}
+
+impl<T, F: UserDataFields<T>> Registrar<'_, T, F> {
+    pub fn field_get<G, R>(&mut self, name: &str, get: G)
+    where
+        G: Fn(&Lua, &T) -> mlua::Result<R> + MaybeSend + 'static,
+        R: IntoLua,
+    {
+        let name = self.claim(name);
+        self.reg.add_field_method_get(name, get);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Stub generation
+// ---------------------------------------------------------------------------
+
+const HEADER: &str = "\
+---@meta
+--
+-- MCRW (Minecraft Rust Wrapper) — Lua API type definitions.
+--
+-- This file documents the API that MCRW exposes to plugins so that the
+-- Lua Language Server (sumneko `lua-language-server`, shipped with the
+-- VS Code \"Lua\" extension) can provide autocomplete, hover docs, and
+-- type checking. It is NOT loaded or executed at runtime — the `---@meta`
+-- tag tells the language server to treat it as definitions only.
+--
+-- To use it in a plugin workspace, point a `.luarc.json` at this directory
+-- (see the README in this folder, or docs/plugin-development.md §2.5).
+--
+-- GENERATED by `mcrstw gen-types` from src/api_spec.rs; do not edit by hand.
+-- Change the declarations there and run
+--     cargo run -- gen-types tools/lua-types/mcrw.lua
+";
+
+fn write_doc(out: &mut String, doc: &str) {
+    if doc.is_empty() {
+        return;
+    }
+    for line in doc.lines() {
+        if line.is_empty() {
+            out.push_str("---\n");
+        } else {
+            let _ = writeln!(out, "--- {line}");
+        }
+    }
+}
+
+fn write_tagged(out: &mut String, tag: &str, parts: &[&str]) {
+    let line = parts
+        .iter()
+        .filter(|s| !s.is_empty())
+        .copied()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let _ = writeln!(out, "---@{tag} {line}");
+}
+
+fn write_class(out: &mut String, class: &Class) {
+    write_doc(out, class.doc);
+    write_tagged(out, "class", &[class.name]);
+    for f in class.fields {
+        write_tagged(out, "field", &[f.name, f.ty, f.doc]);
+    }
+    let local = match class.decl {
+        Decl::Shape => {
+            out.push('\n');
+            return;
+        }
+        Decl::Local(local) => {
+            let _ = writeln!(out, "local {local} = {{}}\n");
+            local
+        }
+        Decl::Global(global) => {
+            let _ = writeln!(out, "{global} = {{}}\n");
+            global
+        }
+    };
+    for m in class.methods {
+        write_doc(out, m.doc);
+        for g in m.generics {
+            write_tagged(out, "generic", &[g]);
+        }
+        for a in m.params {
+            write_tagged(out, "param", &[a.name, a.ty, a.doc]);
+        }
+        for r in m.returns {
+            write_tagged(out, "return", &[r.ty, r.doc]);
+        }
+        let args: Vec<&str> = m
+            .params
+            .iter()
+            .map(|a| a.name.trim_end_matches('?'))
+            .collect();
+        let _ = writeln!(
+            out,
+            "function {local}:{}({}) end\n",
+            m.name,
+            args.join(", ")
+        );
+    }
+}
+
+/// The contents of `tools/lua-types/mcrw.lua`.
+pub fn lua_stubs() -> String {
+    let mut out = String::from(HEADER);
+    for item in API {
+        out.push('\n');
+        match item {
+            Item::Section(title) => {
+                let rule = "-".repeat(80);
+                let _ = writeln!(out, "{rule}\n-- {title}\n{rule}");
+            }
+            Item::Alias { name, ty, doc } => {
+                write_doc(&mut out, doc);
+                write_tagged(&mut out, "alias", &[name, ty]);
+            }
+            Item::Class(class) => {
+                write_class(&mut out, class);
+                // write_class ends with a blank line; items are separated by one.
+                out.pop();
+            }
+        }
+    }
+    out
+}
+
+// ---------------------------------------------------------------------------
+// Declarations
+// ---------------------------------------------------------------------------
+
+pub static PLAYER: Class = Class {
+    name: "mcrw.Player",
+    doc: "A player handle, returned by `wrapper:players()` / `wrapper:player()` and\n\
+          passed to join/leave callbacks. The fields are read from the cached registry\n\
+          state; `pos()`/`dimension()` fetch live data on demand.",
+    decl: Decl::Local("Player"),
+    fields: &[
+        p("name", "string", "The player name."),
+        p(
+            "uuid",
+            "string|nil",
+            "UUID, once seen in the auth log; nil otherwise.",
+        ),
+        p("ip", "string|nil", "Last login IP, if known."),
+        p(
+            "online",
+            "boolean",
+            "Whether the player is currently online.",
+        ),
+        p(
+            "first_join",
+            "integer|nil",
+            "Unix seconds of the first-ever join (persisted across restarts).",
+        ),
+        p(
+            "last_seen",
+            "integer",
+            "Unix seconds of the last seen join/leave.",
+        ),
+        p(
+            "join_time",
+            "integer|nil",
+            "Unix seconds of the current session's join; nil when offline.",
+        ),
+    ],
+    methods: &[
+        Method::new(
+            "pos",
+            "Live coordinates for this player. Yields until the lookup resolves. Returns\n\
+             nil if the player is offline, or (on the stdio path) if it times out. Uses\n\
+             RCON when connected (reliable), else issues a `data get` and correlates the\n\
+             echoed response by name.",
+        )
+        .asynchronous()
+        .returns(&[ret("mcrw.Pos|nil", "")]),
+        Method::new(
+            "dimension",
+            "Live dimension for this player (e.g. \"minecraft:overworld\"), or nil if\n\
+             offline/timed-out. Same mechanism as `Player:pos()`.",
+        )
+        .asynchronous()
+        .returns(&[ret("string|nil", "")]),
+    ],
+};
+
+pub static TIMER: Class = Class {
+    name: "mcrw.Timer",
+    doc: "A pending timer, returned by `wrapper:after` and `wrapper:every`.",
+    decl: Decl::Local("Timer"),
+    fields: &[],
+    methods: &[
+        Method::new(
+            "cancel",
+            "Stop the timer. Returns `false` if it already fired (`after`), was cancelled,\n\
+             or was dropped by a reload of its plugin.",
+        )
+        .returns(&[ret("boolean", "")]),
+        Method::new("active", "`true` while the timer is still scheduled.")
+            .returns(&[ret("boolean", "")]),
+    ],
+};
+
+pub static STORE: Class = Class {
+    name: "mcrw.Store",
+    doc: "A persistent key-value store handle, bound to one namespace and returned by\n\
+          `wrapper:store([namespace])`. Each namespace is a flat `string -> value` map\n\
+          (a key like `\"homes.bed\"` is one opaque key, not a nested path). Values may be\n\
+          any JSON-serialisable Lua value (strings, numbers, booleans, and tables).\n\
+          Data is persisted to `.mcrw/store.json` and survives `!reload` and restarts.",
+    decl: Decl::Local("Store"),
+    fields: &[],
+    methods: &[
+        Method::new(
+            "get",
+            "Read `key` from this namespace. Returns the stored value (tables come back as\n\
+             tables), or nil if the key is absent.",
+        )
+        .params(&[p("key", "string", "")])
+        .returns(&[ret("any|nil", "")]),
+        Method::new(
+            "set",
+            "Write `value` under `key`. Passing `nil` deletes the key (so `set(k, nil)`\n\
+             and `delete(k)` are equivalent). The write is auto-saved within ~5s; call\n\
+             `flush()` to persist immediately.",
+        )
+        .params(&[
+            p("key", "string", ""),
+            p(
+                "value",
+                "any",
+                "Any JSON-serialisable value, or nil to delete.",
+            ),
+        ]),
+        Method::new(
+            "delete",
+            "Delete `key` from this namespace (no-op if absent).",
+        )
+        .params(&[p("key", "string", "")]),
+        Method::new(
+            "keys",
+            "List the keys currently present in this namespace (unordered).",
+        )
+        .returns(&[ret("string[]", "")]),
+        Method::new(
+            "flush",
+            "Force an immediate durable write of pending changes, bypassing the ~5s\n\
+             debounce. Use after a critical update (e.g. an economy transfer) that must not\n\
+             be lost on a crash. Writes are atomic (temp file + rename).",
+        ),
+    ],
+};
+
+pub static WRAPPER: Class = Class {
+    name: "mcrw.Wrapper",
+    doc: "",
+    decl: Decl::Local("Wrapper"),
+    fields: &[],
+    methods: &[
+        Method::new(
+            "register",
+            "Register a stdout regex trigger. Every line the Minecraft server prints is\n\
+             matched against `pattern` (Rust `regex` syntax: no backreferences, no\n\
+             lookaround). On a match, `callback` is invoked with the line and its capture\n\
+             groups, and may return a list of commands to run.\n\
+             \n\
+             With a `mcrw.LogFilter` table instead, the line's log prefix must match the\n\
+             filter, the regex is matched against the message only, and the callback gets\n\
+             the parsed `mcrw.LogRecord` in place of the line. Filter triggers see whole\n\
+             records: continuation lines such as stack trace frames are folded into the\n\
+             record before them (see `[log]` in mcrw.toml) instead of firing on their own.\n\
+             \n\
+             Note: Lua string escapes apply first, so a literal backslash in the regex\n\
+             must be written `\\\\` in the Lua string.",
+        )
+        .params(&[
+            p(
+                "pattern",
+                "string|mcrw.LogFilter",
+                "Rust regex, or a filter table. Raises if the regex fails to compile.",
+            ),
+            p("callback", "mcrw.TriggerCallback|mcrw.FilterCallback", ""),
+        ]),
+        Method::new(
+            "register_cron",
+            "Register a recurring cron job. `expr` is a 6-field cron expression\n\
+             (`sec min hour day-of-month month day-of-week`) evaluated in the local\n\
+             timezone; the `@yearly`/`@monthly`/`@weekly`/`@daily`/`@hourly` aliases are\n\
+             also accepted. The callback may return commands to run on each fire.\n\
+             \n\
+             Overlap is NOT prevented: if a previous run has not finished when the next\n\
+             tick fires, both run concurrently. Guard with a Lua flag if needed.",
+        )
+        .params(&[
+            p(
+                "expr",
+                "string",
+                "6-field cron expression. Raises if invalid or has no future fire time.",
+            ),
+            p("callback", "mcrw.CronCallback", ""),
+        ]),
+        Method::new(
+            "after",
+            "Run `callback` once, `ms` milliseconds from now. Like a cron callback it may\n\
+             return commands. The timer belongs to this plugin and is dropped when it is\n\
+             reloaded or unloaded.",
+        )
+        .params(&[
+            p("ms", "integer", ""),
+            p("callback", "fun(): mcrw.Commands?", ""),
+        ])
+        .returns(&[ret("mcrw.Timer", "")]),
+        Method::new(
+            "every",
+            "Run `callback` every `ms` milliseconds (at least 1), first after one period,\n\
+             until cancelled or the plugin is reloaded. Periods missed while the wrapper\n\
+             was busy are skipped, not made up.",
+        )
+        .params(&[
+            p("ms", "integer", ""),
+            p("callback", "fun(): mcrw.Commands?", ""),
+        ])
+        .returns(&[ret("mcrw.Timer", "")]),
+        Method::new(
+            "sleep",
+            "Yield the running callback for `ms` milliseconds. Other callbacks keep running\n\
+             meanwhile; later callbacks for the same line wait for this one.",
+        )
+        .asynchronous()
+        .params(&[p("ms", "integer", "")]),
+        Method::new(
+            "wait_for",
+            "Wait for the next server line matching `pattern`, optionally sending a command\n\
+             first; the matcher is in place before the command goes out. Returns the line and\n\
+             its captures, like a trigger callback receives them, or nil on timeout.",
+        )
+        .asynchronous()
+        .params(&[
+            p("pattern", "string", "Rust regex."),
+            p("opts?", "mcrw.WaitForOpts", ""),
+        ])
+        .returns(&[ret("string?", ""), ret("string ...", "")]),
+        Method::new(
+            "register_start",
+            "Register a callback for when the server finishes starting up (the \"Done\"\n\
+             line, configurable via trigger_config.toml). May return commands.",
+        )
+        .params(&[p("callback", "mcrw.StartCallback", "")]),
+        Method::new(
+            "register_on_stop",
+            "Register a callback for a clean server shutdown (exit code 0).",
+        )
+        .params(&[p("callback", "mcrw.LifecycleCallback", "")]),
+        Method::new(
+            "register_on_crash",
+            "Register a callback for a server crash (non-zero exit code).",
+        )
+        .params(&[p("callback", "mcrw.LifecycleCallback", "")]),
+        Method::new(
+            "register_on_exception",
+            "Register a callback for Java exceptions in the server log. Called with each\n\
+             assembled `mcrw.LogRecord` whose lines contain an exception and at least one\n\
+             `at ...` frame; `record.exception` holds the class, message and full trace.\n\
+             May return commands.",
+        )
+        .params(&[p("callback", "mcrw.ExceptionCallback", "")]),
+        Method::new(
+            "register_on_unload",
+            "Register a callback run before this plugin's registrations are cleared.\n\
+             `reason` is \"reload\", \"unload\" or \"shutdown\". Each call is limited to\n\
+             `[reload] unload_timeout_ms` (default 2000 ms).",
+        )
+        .params(&[p(
+            "callback",
+            "fun(reason: \"reload\"|\"unload\"|\"shutdown\")",
+            "",
+        )]),
+        Method::new(
+            "register_on_load_complete",
+            "Register a callback run once every plugin of the current load pass has\n\
+             initialized (startup, `!reload`, or this plugin's own `!reload`/`!load`).\n\
+             `reason` is \"load\", or \"rollback\" when a failed reload put this instance\n\
+             back after its unload callbacks ran.",
+        )
+        .params(&[p("callback", "fun(reason: \"load\"|\"rollback\")", "")]),
+        Method::new(
+            "register_on_join",
+            "Register a callback fired when a player joins the game. The callback receives\n\
+             the player handle and may return commands.",
+        )
+        .params(&[p("callback", "mcrw.PlayerCallback", "")]),
+        Method::new(
+            "register_on_leave",
+            "Register a callback fired when a player leaves the game.",
+        )
+        .params(&[p("callback", "mcrw.PlayerCallback", "")]),
+        Method::new(
+            "players",
+            "Return handles for all currently-online players. The registry is populated by\n\
+             parsing the server's join/leave/login log lines (patterns are configurable in\n\
+             mcrw.toml's `[players]` section).",
+        )
+        .returns(&[ret("mcrw.Player[]", "")]),
+        Method::new(
+            "player",
+            "Return a handle for `name`, or nil if the player has never been seen. A handle\n\
+             for a known-but-offline player still exposes its persisted fields.",
+        )
+        .params(&[p("name", "string", "")])
+        .returns(&[ret("mcrw.Player|nil", "")]),
+        Method::new(
+            "is_rcon",
+            "Whether a live RCON connection currently backs the active-query path\n\
+             (`Player:pos()`/`Player:dimension()`). RCON is auto-detected from\n\
+             `server.properties` (overridable via mcrw.toml's `[rcon]`); when unavailable\n\
+             the wrapper falls back to stdio parsing.",
+        )
+        .returns(&[ret("boolean", "")]),
+        Method::new(
+            "server_info",
+            "What the wrapper knows about the server: version, flavor, level name, ports,\n\
+             max players, online mode and startup time. Combines `server.properties` with\n\
+             the server's startup lines; fields not known yet (e.g. before the server is\n\
+             up) are nil. Returns a fresh snapshot on each call.",
+        )
+        .returns(&[ret("mcrw.ServerInfo", "")]),
+        Method::new(
+            "rcon_command",
+            "Run an arbitrary command over RCON and return its output text. Unlike\n\
+             `wrapper:command` (fire-and-forget to stdin), this captures the response.\n\
+             Yields until the response arrives. RAISES a Lua error if RCON is not enabled,\n\
+             not connected, or the call exceeds `[rcon].timeout_ms` (default 5000) — guard\n\
+             with `wrapper:is_rcon()` or wrap in `pcall`.\n\
+             \n\
+             ```lua\n\
+             if wrapper:is_rcon() then\n  \
+               local players = wrapper:rcon_command(\"list\")\n\
+             end\n\
+             ```",
+        )
+        .asynchronous()
+        .params(&[p(
+            "cmd",
+            "string",
+            "The server command to run (no leading slash).",
+        )])
+        .returns(&[ret("string", "The command's output text.")]),
+        Method::new(
+            "query",
+            "Run a command and return its reply: over RCON when connected, otherwise on the\n\
+             console, taking the next server line that matches `response_pattern`. Returns the\n\
+             reply and the pattern's captures, or nil on timeout or when the reply does not\n\
+             match. Queries with the same pattern take turns on the console path.",
+        )
+        .asynchronous()
+        .params(&[
+            p(
+                "cmd",
+                "string",
+                "The server command to run (no leading slash).",
+            ),
+            p("opts", "mcrw.QueryOpts", ""),
+        ])
+        .returns(&[ret("string?", ""), ret("string ...", "")]),
+        Method::new(
+            "store",
+            "Open a persistent key-value store handle. With no argument you get this\n\
+             plugin's PRIVATE namespace (isolated from every other plugin). Pass a name to\n\
+             open a SHARED namespace that any plugin can reach by the same name — use this\n\
+             for cross-plugin data (e.g. an \"economy\" balance read by both a bank and a\n\
+             shop plugin). Stored data survives `!reload` and restarts.\n\
+             \n\
+             ```lua\n\
+             local db = wrapper:store()            -- private to this plugin\n\
+             db:set(\"homes.bed\", { x = 1, y = 2, z = 3 })\n\
+             local home = db:get(\"homes.bed\")\n\
+             \n\
+             local econ = wrapper:store(\"economy\") -- shared across plugins\n\
+             econ:set(\"Steve.balance\", 100)\n\
+             econ:flush()                          -- persist now (don't risk the ~5s debounce)\n\
+             ```",
+        )
+        .params(&[p(
+            "namespace?",
+            "string",
+            "Shared namespace name; omit for this plugin's private store.",
+        )])
+        .returns(&[ret("mcrw.Store", "")]),
+        Method::new(
+            "command",
+            "Push a single command to the server immediately, without waiting for the\n\
+             current callback to return. Use this to emit commands from outside a\n\
+             trigger return value (e.g. between awaited steps). Yields if the command\n\
+             queue is full (backpressure); resumes when there is room.",
+        )
+        .asynchronous()
+        .params(&[p("cmd", "string", "One command, no trailing newline.")]),
+        Method::new(
+            "is_op",
+            "Check whether `name` is listed in the server's `ops.json`\n\
+             (case-insensitive). `ops.json` is re-read on every call. Missing or\n\
+             malformed files degrade to `false` (least-privilege default).",
+        )
+        .params(&[p("name", "string", "")])
+        .returns(&[ret("boolean", "")]),
+        Method::new(
+            "load_config",
+            "Load this plugin's `config.json`, creating it from `defaults` on first run.\n\
+             An existing file is deep-merged over `defaults`; keys it lacks are written\n\
+             back, user values are never changed. The return value has the same shape as\n\
+             `defaults`. With `schema`, the merged config is validated and every\n\
+             violation is raised with its JSON path (e.g. `$.rewards.amount`).",
+        )
+        .generics(&["T"])
+        .params(&[
+            p("defaults", "T", "The default config table."),
+            p("schema?", "table<string, mcrw.ConfigSpec>", ""),
+        ])
+        .returns(&[ret("T", "")]),
+        Method::new(
+            "register_on_config_change",
+            "Register a callback for edits to this plugin's `config.json`. Called with\n\
+             the new and previous config after the edit passed `load_config`'s merge and\n\
+             schema; invalid edits are logged and ignored. Requires a prior `load_config`.",
+        )
+        .params(&[p("callback", "fun(new: table, old: table)", "")]),
+        Method::new("meta", "Return this plugin's parsed `meta.toml`.")
+            .returns(&[ret("mcrw.Meta", "")]),
+        Method::new(
+            "has_feature",
+            "`true` if this wrapper provides the `wrapper` method `name`\n\
+             (e.g. \"rcon_command\"). Use it to degrade gracefully on older wrappers.",
+        )
+        .params(&[p("name", "string", "")])
+        .returns(&[ret("boolean", "")]),
+        Method::new(
+            "has_permission",
+            "`true` if this plugin may use the capability `cap` (\"http\", \"python\",\n\
+             \"rcon\", \"command\", \"fs\", \"metrics\", \"store:shared:<name>\"). Always `true`\n\
+             unless the operator enables `[permissions] enforce` in mcrw.toml.",
+        )
+        .params(&[p("cap", "string", "")])
+        .returns(&[ret("boolean", "")]),
+        Method::new(
+            "export",
+            "Publish `api` for plugins that list this one in `dependencies`. Calling it\n\
+             again replaces the earlier export; exports are cleared on `!reload`.",
+        )
+        .params(&[p("api", "table", "")]),
+        Method::new(
+            "import",
+            "Return the table exported by `name` (directory or meta.toml name). `name`\n\
+             MUST be listed in this plugin's `dependencies`; errors otherwise, or if the\n\
+             dependency never called `wrapper:export`.",
+        )
+        .params(&[p("name", "string", "")])
+        .returns(&[ret("table", "")]),
+        Method::new(
+            "register_on_quarantine",
+            "Register a callback for when another plugin is quarantined because its\n\
+             callbacks keep failing (see `[quarantine]` in mcrw.toml). Called with the\n\
+             plugin's directory and a reason such as \"10 consecutive failures\".",
+        )
+        .params(&[p("callback", "fun(plugin: string, reason: string)", "")]),
+        Method::new(
+            "log",
+            "Print a line to the wrapper console, prefixed with `[<plugin name>]`.",
+        )
+        .params(&[p("msg", "string", "")]),
+        Method::new(
+            "run_python",
+            "(Experimental) Run a Python script located inside this plugin's directory.\n\
+             `script` is resolved relative to the plugin directory and is containment-\n\
+             checked (paths escaping via `..` or symlinks are rejected). The script's\n\
+             last non-empty stdout line MUST be valid JSON (or stdout empty); it is\n\
+             decoded into `result.stdout`. Yields until the process exits or times out.",
+        )
+        .asynchronous()
+        .params(&[
+            p(
+                "script",
+                "string",
+                "Path to the .py file, relative to the plugin dir.",
+            ),
+            p(
+                "args?",
+                "string[]",
+                "Command-line arguments passed to the script.",
+            ),
+            p("opts?", "mcrw.PythonOpts", ""),
+        ])
+        .returns(&[ret("mcrw.PythonResult", "")]),
+        Method::new(
+            "http_request",
+            "Perform a one-shot HTTP request. Yields the current coroutine until the\n\
+             full response has been received, then returns it. Transport failures (DNS,\n\
+             connection, timeout) raise a Lua error — wrap in `pcall` to handle them; a\n\
+             non-2xx status is NOT an error and returns normally with `ok = false`.\n\
+             \n\
+             ```lua\n\
+             local resp = wrapper:http_request{\n  \
+               url = \"https://api.example.com/x\",\n  \
+               method = \"POST\",\n  \
+               json = { key = \"value\" },\n\
+             }\n\
+             if resp.ok then\n  \
+               local data = wrapper:json_decode(resp.body)\n\
+             end\n\
+             ```\n\
+             \n\
+             (Streaming responses — `wrapper:http_stream` — are a reserved, not-yet-\n\
+             implemented capability.)",
+        )
+        .asynchronous()
+        .params(&[p("opts", "mcrw.HttpOpts", "")])
+        .returns(&[ret("mcrw.HttpResponse", "")]),
+        Method::new(
+            "json_encode",
+            "Encode a Lua value as a JSON string. Lua 5.4 has no built-in JSON library,\n\
+             so use this to build request bodies. Raises on values that cannot be\n\
+             represented as JSON.",
+        )
+        .params(&[p("value", "any", "")])
+        .returns(&[ret("string", "")]),
+        Method::new(
+            "json_decode",
+            "Decode a JSON string into a Lua value. Raises on invalid JSON.",
+        )
+        .params(&[p("str", "string", "")])
+        .returns(&[ret("any", "")]),
+        Method::new(
+            "stats",
+            "Callback metrics since startup or the last reset, slowest total first; the\n\
+             same numbers as the `!profile` console command. Returns this plugin's\n\
+             entries unless `opts.plugin` names another (`\"*\"` for all plugins), which\n\
+             needs the \"metrics\" permission under `[permissions] enforce`.",
+        )
+        .params(&[p("opts?", "mcrw.StatsOpts", "")])
+        .returns(&[ret("mcrw.Stat[]", "")]),
+    ],
+};
+
+pub static SERVER: Class = Class {
+    name: "mcrw.Server",
+    doc: "The global entry point, available in every plugin's `init.lua`.",
+    decl: Decl::Global("Server"),
+    fields: &[],
+    methods: &[Method::new(
+        "get_context",
+        "Obtain this plugin's `wrapper` handle. Call once at the top of `init.lua`,\n\
+         passing the vararg `...` (which Lua sets to the module path the wrapper used\n\
+         to `require` the plugin, e.g. \"lua_plugins.myplugin.\"). The trailing\n\
+         directory segment identifies the plugin in the registry.\n\
+         \n\
+         ```lua\n\
+         local wrapper = Server:get_context(...)\n\
+         ```",
+    )
+    .params(&[p("module_path", "string", "Pass `...`.")])
+    .returns(&[ret("mcrw.Wrapper", "")])],
+};
+
+static CONFIG_SPEC_TABLE: Class = Class {
+    name: "mcrw.ConfigSpecTable",
+    doc: "",
+    decl: Decl::Shape,
+    fields: &[
+        p("type", "mcrw.ConfigType", ""),
+        p("enum?", "any[]", "Allowed values."),
+        p(
+            "min?",
+            "number",
+            "Lower bound (value, or length of a string/array).",
+        ),
+        p(
+            "max?",
+            "number",
+            "Upper bound (value, or length of a string/array).",
+        ),
+        p(
+            "fields?",
+            "table<string, mcrw.ConfigSpec>",
+            "Nested fields of a \"table\".",
+        ),
+        p(
+            "items?",
+            "mcrw.ConfigSpec",
+            "Spec of every element of an \"array\".",
+        ),
+        p("optional?", "boolean", "The key may be absent."),
+    ],
+    methods: &[],
+};
+
+static META: Class = Class {
+    name: "mcrw.Meta",
+    doc: "Parsed contents of the plugin's `meta.toml`, as returned by `wrapper:meta()`.",
+    decl: Decl::Shape,
+    fields: &[
+        p("name", "string", "Plugin display name."),
+        p("version", "string", "Plugin version string."),
+        p("description", "string", "Optional; \"\" when absent."),
+        p("authors", "string[]", "Optional; {} when absent."),
+        p(
+            "dependencies",
+            "string[]",
+            "Optional; {} when absent. Loaded before this plugin.",
+        ),
+        p(
+            "mcrw_version",
+            "string",
+            "Optional; \"\" when absent. SemVer requirement on the wrapper version.",
+        ),
+        p(
+            "permissions",
+            "string[]",
+            "Optional; {} when absent. Capabilities needed under [permissions] enforce.",
+        ),
+        p(
+            "isolation",
+            "\"shared\"|\"isolated\"",
+            "Optional; \"shared\" when absent. \"isolated\" runs in its own Lua state.",
+        ),
+        p(
+            "libs",
+            "table<string,string>",
+            "Optional; {} when absent. lua_libs/ library name → SemVer requirement.",
+        ),
+    ],
+    methods: &[],
+};
+
+static PYTHON_OPTS: Class = Class {
+    name: "mcrw.PythonOpts",
+    doc: "Options for `wrapper:run_python`.",
+    decl: Decl::Shape,
+    fields: &[
+        p("stdin?", "string", "Data piped to the script's stdin."),
+        p(
+            "timeout_ms?",
+            "integer",
+            "Per-call timeout; defaults to mcrw.toml's python.default_timeout_ms (30000).",
+        ),
+        p(
+            "env?",
+            "table<string,string>",
+            "Extra environment variables for the child process.",
+        ),
+    ],
+    methods: &[],
+};
+
+static PYTHON_RESULT: Class = Class {
+    name: "mcrw.PythonResult",
+    doc: "Result of `wrapper:run_python`.",
+    decl: Decl::Shape,
+    fields: &[
+        p(
+            "stdout",
+            "any",
+            "The script's last non-empty stdout line, JSON-decoded. `nil` if stdout was empty.",
+        ),
+        p("stderr", "string", "The script's full stderr output."),
+        p(
+            "code",
+            "integer",
+            "The process exit code. `-1` if the process was killed (e.g. timeout).",
+        ),
+    ],
+    methods: &[],
+};
+
+static HTTP_OPTS: Class = Class {
+    name: "mcrw.HttpOpts",
+    doc: "Options for `wrapper:http_request`.",
+    decl: Decl::Shape,
+    fields: &[
+        p("url", "string", "The request URL. Required."),
+        p(
+            "method?",
+            "string",
+            "HTTP method; defaults to \"GET\". Case-insensitive.",
+        ),
+        p("headers?", "table<string,string>", "Request headers."),
+        p(
+            "body?",
+            "string",
+            "Raw request body. Mutually exclusive with `json`.",
+        ),
+        p(
+            "json?",
+            "any",
+            "A value to JSON-encode as the body; also sets Content-Type: application/json unless \
+             already set. Mutually exclusive with `body`.",
+        ),
+        p(
+            "timeout_ms?",
+            "integer",
+            "Per-request timeout; defaults to mcrw.toml's http.default_timeout_ms (30000).",
+        ),
+    ],
+    methods: &[],
+};
+
+static HTTP_RESPONSE: Class = Class {
+    name: "mcrw.HttpResponse",
+    doc: "Result of `wrapper:http_request`.",
+    decl: Decl::Shape,
+    fields: &[
+        p("status", "integer", "The HTTP status code."),
+        p("ok", "boolean", "True if `status` is in the 200–299 range."),
+        p(
+            "headers",
+            "table<string,string>",
+            "Response headers, with lowercased names.",
+        ),
+        p(
+            "body",
+            "string",
+            "The full response body as a string. Use `wrapper:json_decode` to parse JSON.",
+        ),
+    ],
+    methods: &[],
+};
+
+static POS: Class = Class {
+    name: "mcrw.Pos",
+    doc: "A live player position, as returned by `Player:pos()`.",
+    decl: Decl::Shape,
+    fields: &[
+        p("x", "number", ""),
+        p("y", "number", ""),
+        p("z", "number", ""),
+    ],
+    methods: &[],
+};
+
+static SERVER_INFO: Class = Class {
+    name: "mcrw.ServerInfo",
+    doc: "Returned by `wrapper:server_info()`.",
+    decl: Decl::Shape,
+    fields: &[
+        p("version?", "string", "Minecraft version, e.g. \"1.20.4\"."),
+        p(
+            "flavor",
+            "string",
+            "\"vanilla\", \"paper\", \"fabric\", \"forge\", \"velocity\", or \"auto\" until detected.",
+        ),
+        p("level_name?", "string", ""),
+        p("port?", "integer", "The game port the server listens on."),
+        p(
+            "rcon_port?",
+            "integer",
+            "Set while RCON is enabled in server.properties.",
+        ),
+        p(
+            "query_port?",
+            "integer",
+            "Set while the query protocol is enabled in server.properties.",
+        ),
+        p("max_players?", "integer", ""),
+        p("online_mode?", "boolean", ""),
+        p(
+            "startup_secs?",
+            "number",
+            "Startup time from the server's \"Done (3.021s)!\" line.",
+        ),
+    ],
+    methods: &[],
+};
+
+static LOG_FILTER: Class = Class {
+    name: "mcrw.LogFilter",
+    doc: "A `wrapper:register` filter. Unset fields match any line.",
+    decl: Decl::Shape,
+    fields: &[
+        p(
+            "level?",
+            "string",
+            "Log level, e.g. \"WARN\" (case-insensitive).",
+        ),
+        p(
+            "thread?",
+            "string",
+            "Thread name, e.g. \"Server thread\". Paper lines have none.",
+        ),
+        p(
+            "logger?",
+            "string",
+            "Logger name (Fabric and Forge lines only).",
+        ),
+        p(
+            "message?",
+            "string",
+            "Rust regex matched against the message after the prefix.",
+        ),
+    ],
+    methods: &[],
+};
+
+static LOG_RECORD: Class = Class {
+    name: "mcrw.LogRecord",
+    doc: "A server log line split into its prefix fields and message.",
+    decl: Decl::Shape,
+    fields: &[
+        p(
+            "time?",
+            "string",
+            "Timestamp as printed, e.g. \"12:00:01\".",
+        ),
+        p("thread?", "string", ""),
+        p("level?", "string", ""),
+        p("logger?", "string", ""),
+        p(
+            "message",
+            "string",
+            "The line after the prefix; the whole line if it has none.",
+        ),
+        p("raw", "string", "The full line."),
+        p(
+            "continuation?",
+            "string[]",
+            "Lines folded into this record (stack frames etc.), unparsed.",
+        ),
+        p(
+            "exception?",
+            "mcrw.ExceptionInfo",
+            "Set when the record contains a Java exception.",
+        ),
+    ],
+    methods: &[],
+};
+
+static EXCEPTION_INFO: Class = Class {
+    name: "mcrw.ExceptionInfo",
+    doc: "A Java exception found in a log record.",
+    decl: Decl::Shape,
+    fields: &[
+        p(
+            "class",
+            "string",
+            "Fully qualified class, e.g. \"java.lang.IllegalStateException\".",
+        ),
+        p(
+            "message?",
+            "string",
+            "The text after the class; nil if there is none.",
+        ),
+        p(
+            "trace",
+            "string",
+            "The exception line and every line after it, newline-separated.",
+        ),
+    ],
+    methods: &[],
+};
+
+static WAIT_FOR_OPTS: Class = Class {
+    name: "mcrw.WaitForOpts",
+    doc: "Options for `wrapper:wait_for`.",
+    decl: Decl::Shape,
+    fields: &[
+        p(
+            "timeout_ms?",
+            "integer",
+            "Give up and return nil after this long. Default 5000.",
+        ),
+        p(
+            "after_command?",
+            "string",
+            "Command to send once the matcher is registered; needs \"command\".",
+        ),
+    ],
+    methods: &[],
+};
+
+static QUERY_OPTS: Class = Class {
+    name: "mcrw.QueryOpts",
+    doc: "Options for `wrapper:query`.",
+    decl: Decl::Shape,
+    fields: &[
+        p(
+            "response_pattern",
+            "string",
+            "Rust regex the reply must match, searched anywhere in the line or RCON reply.",
+        ),
+        p(
+            "timeout_ms?",
+            "integer",
+            "Give up and return nil after this long. Default 5000.",
+        ),
+    ],
+    methods: &[],
+};
+
+static STATS_OPTS: Class = Class {
+    name: "mcrw.StatsOpts",
+    doc: "Options for `wrapper:stats`.",
+    decl: Decl::Shape,
+    fields: &[
+        p(
+            "plugin?",
+            "string",
+            "Plugin directory to report; `\"*\"` for all. Defaults to this plugin.",
+        ),
+        p(
+            "reset?",
+            "boolean",
+            "Clear the reported entries after reading them.",
+        ),
+    ],
+    methods: &[],
+};
+
+static STAT: Class = Class {
+    name: "mcrw.Stat",
+    doc: "One callback's metrics, as returned by `wrapper:stats`.",
+    decl: Decl::Shape,
+    fields: &[
+        p("plugin", "string", "Plugin directory."),
+        p(
+            "kind",
+            "\"trigger\"|\"cron\"|\"timer\"|\"join\"|\"leave\"|\"lifecycle\"|\"stop\"|\"crash\"|\"unload\"",
+            "",
+        ),
+        p(
+            "key",
+            "string",
+            "Trigger pattern, cron expression, `after` / `every <ms>ms` for timers, lifecycle \
+             event or unload reason; empty otherwise.",
+        ),
+        p("calls", "integer", ""),
+        p(
+            "errors",
+            "integer",
+            "Calls that raised an error (or, for unload hooks, timed out).",
+        ),
+        p(
+            "commands",
+            "integer",
+            "Server commands the callback returned.",
+        ),
+        p(
+            "total_ms",
+            "number",
+            "Total wall time, including time spent yielded.",
+        ),
+        p("p50_ms", "number", "Median over the last 512 calls."),
+        p(
+            "p99_ms",
+            "number",
+            "99th percentile over the last 512 calls.",
+        ),
+    ],
+    methods: &[],
+};
+
+/// Everything in the stubs, in file order.
+pub static API: &[Item] = &[
+    Item::Section("Callback signatures"),
+    alias(
+        "mcrw.Commands",
+        "string[]|nil",
+        "A list of server commands to forward to the Minecraft server's stdin, in\n\
+         order. Returning `nil` (or nothing) forwards no commands. Each string is one\n\
+         command WITHOUT a trailing newline; the wrapper appends it and replaces any\n\
+         interior CR/LF with a space.",
+    ),
+    alias(
+        "mcrw.TriggerCallback",
+        "fun(line: string, ...: string): mcrw.Commands",
+        "Regex trigger callback. `line` is the full matched stdout line; the\n\
+         remaining varargs are the regex capture groups in order (a group that did\n\
+         not participate is the empty string).",
+    ),
+    alias(
+        "mcrw.FilterCallback",
+        "fun(record: mcrw.LogRecord, ...: string): mcrw.Commands",
+        "Callback of a trigger registered with a `mcrw.LogFilter`. The varargs are the\n\
+         capture groups of `message` in the record's message.",
+    ),
+    alias(
+        "mcrw.ExceptionCallback",
+        "fun(record: mcrw.LogRecord): mcrw.Commands",
+        "`wrapper:register_on_exception` callback. `record.exception` is always set.",
+    ),
+    alias(
+        "mcrw.CronCallback",
+        "fun(fire_time: string): mcrw.Commands",
+        "Cron callback. `fire_time` is the scheduled fire time as an RFC 3339 /\n\
+         ISO 8601 string in the local timezone, e.g. \"2026-05-21T03:00:00+08:00\".",
+    ),
+    alias(
+        "mcrw.StartCallback",
+        "fun(): mcrw.Commands",
+        "Server-ready (start) callback. May return commands to run once the server\n\
+         has finished starting up.",
+    ),
+    alias(
+        "mcrw.LifecycleCallback",
+        "fun()",
+        "Server-stop / server-crash callback. Return value is ignored: the server\n\
+         process has already exited, so no commands can be delivered.",
+    ),
+    alias(
+        "mcrw.PlayerCallback",
+        "fun(player: mcrw.Player): mcrw.Commands",
+        "Join/leave callback. Receives the affected player handle; may return a list\n\
+         of commands to run.",
+    ),
+    Item::Section("Data shapes"),
+    alias(
+        "mcrw.ConfigType",
+        "\"string\"|\"number\"|\"integer\"|\"boolean\"|\"table\"|\"array\"|\"any\"",
+        "A `wrapper:load_config` schema entry: a type name, or a table of checks.",
+    ),
+    alias(
+        "mcrw.ConfigSpec",
+        "mcrw.ConfigType|mcrw.ConfigSpecTable",
+        "",
+    ),
+    Item::Class(&CONFIG_SPEC_TABLE),
+    Item::Class(&META),
+    Item::Class(&PYTHON_OPTS),
+    Item::Class(&PYTHON_RESULT),
+    Item::Class(&HTTP_OPTS),
+    Item::Class(&HTTP_RESPONSE),
+    Item::Class(&POS),
+    Item::Class(&SERVER_INFO),
+    Item::Class(&LOG_FILTER),
+    Item::Class(&LOG_RECORD),
+    Item::Class(&EXCEPTION_INFO),
+    Item::Class(&WAIT_FOR_OPTS),
+    Item::Class(&QUERY_OPTS),
+    Item::Class(&STATS_OPTS),
+    Item::Class(&STAT),
+    Item::Section("Handles"),
+    Item::Class(&PLAYER),
+    Item::Class(&STORE),
+    Item::Class(&TIMER),
+    Item::Section("The `wrapper` handle (per-plugin), returned by `Server:get_context`."),
+    Item::Class(&WRAPPER),
+    Item::Section("The global `Server` object."),
+    Item::Class(&SERVER),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    use crate::lua_ctx::{PlayerHandle, PluginApi, ServerApi};
+    use crate::store::StoreHandle;
+    use crate::timers::TimerHandle;
+
+    // Creating a proxy runs the type's registration, which panics on any
+    // mismatch with its declaration.
+    #[test]
+    fn registered_api_matches_declarations() {
+        let lua = Lua::new();
+        lua.create_proxy::<PluginApi>().unwrap();
+        lua.create_proxy::<PlayerHandle>().unwrap();
+        lua.create_proxy::<StoreHandle>().unwrap();
+        lua.create_proxy::<TimerHandle>().unwrap();
+        lua.create_proxy::<ServerApi>().unwrap();
+    }
+
+    #[test]
+    fn committed_stubs_are_up_to_date() {
+        let committed = include_str!("../tools/lua-types/mcrw.lua");
+        assert!(
+            committed == lua_stubs(),
+            "tools/lua-types/mcrw.lua is out of date; run `cargo run -- gen-types tools/lua-types/mcrw.lua`"
+        );
+    }
+
+    #[test]
+    fn names_are_unique() {
+        for item in API {
+            let Item::Class(class) = item else { continue };
+            let mut seen = HashSet::new();
+            for name in class
+                .fields
+                .iter()
+                .map(|f| f.name)
+                .chain(class.methods.iter().map(|m| m.name))
+            {
+                assert!(seen.insert(name), "{}: '{name}' declared twice", class.name);
+            }
+        }
+    }
+
+    #[test]
+    fn stubs_render_methods() {
+        let stubs = lua_stubs();
+        assert!(stubs.starts_with("---@meta\n"));
+        assert!(stubs.contains(
+            "---@param namespace? string Shared namespace name; omit for this plugin's private store.\n\
+             ---@return mcrw.Store\n\
+             function Wrapper:store(namespace) end\n"
+        ));
+        assert!(stubs.contains("---@class mcrw.Server\nServer = {}\n"));
+        assert!(stubs.ends_with("end\n"));
+    }
+}
@@ -0,0 +1,194 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Circuit breaker for plugins whose callbacks keep failing.
+//!
+//! Every dispatched trigger, cron, join/leave and lifecycle callback reports
+//! its outcome here. A plugin that fails `[quarantine] consecutive_failures`
+//! times in a row, or `failures_per_minute` times within 60 seconds, is
+//! quarantined: the dispatcher skips its callbacks until `!plugin resume` or
+//! a reload of the plugin clears the state.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::lua_ctx::QuarantineConfig;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct Health {
+    consecutive: u32,
+    // Failure times within the last WINDOW, oldest first.
+    recent: VecDeque<Instant>,
+    quarantined: bool,
+}
+
+#[derive(Default)]
+pub struct CircuitBreaker {
+    plugins: Mutex<HashMap<String, Health>>,
+}
+
+impl CircuitBreaker {
+    /// Record one callback outcome of `plugin`. Returns the reason when this
+    /// failure is the one that quarantines it.
+    pub fn record(
+        &self,
+        plugin: &str,
+        ok: bool,
+        limits: &QuarantineConfig,
+        now: Instant,
+    ) -> Option<String> {
+        let mut g = self.plugins.lock().unwrap();
+        let h = g.entry(plugin.to_string()).or_default();
+        if ok {
+            h.consecutive = 0;
+            return None;
+        }
+        h.consecutive += 1;
+        h.recent.push_back(now);
+        while h
+            .recent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= WINDOW)
+        {
+            h.recent.pop_front();
+        }
+        if h.quarantined {
+            return None;
+        }
+        let reason =
+            if limits.consecutive_failures > 0 && h.consecutive >= limits.consecutive_failures {
+                format!("{} consecutive failures", h.consecutive)
+            } else if limits.failures_per_minute > 0
+                && h.recent.len() >= limits.failures_per_minute as usize
+            {
+                format!("{} failures within a minute", h.recent.len())
+            } else {
+                return None;
+            };
+        h.quarantined = true;
+        Some(reason)
+    }
+
+    pub fn is_quarantined(&self, plugin: &str) -> bool {
+        self.plugins
+            .lock()
+            .unwrap()
+            .get(plugin)
+            .is_some_and(|h| h.quarantined)
+    }
+
+    /// Lift the quarantine of `plugin` with fresh counters. Returns false if it
+    /// was not quarantined.
+    pub fn resume(&self, plugin: &str) -> bool {
+        self.plugins
+            .lock()
+            .unwrap()
+            .remove(plugin)
+            .is_some_and(|h| h.quarantined)
+    }
+
+    /// Forget the state of `plugin` (every plugin for `None`), e.g. because its
+    /// code was reloaded.
+    pub fn forget(&self, plugin: Option<&str>) {
+        let mut g = self.plugins.lock().unwrap();
+        match plugin {
+            Some(p) => {
+                g.remove(p);
+            }
+            None => g.clear(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(consecutive_failures: u32, failures_per_minute: u32) -> QuarantineConfig {
+        QuarantineConfig {
+            consecutive_failures,
+            failures_per_minute,
+        }
+    }
+
+    #[test]
+    fn trips_on_consecutive_failures_once() {
+        let b = CircuitBreaker::default();
+        let l = limits(3, 0);
+        let now = Instant::now();
+        assert_eq!(b.record("a", false, &l, now), None);
+        assert_eq!(b.record("a", false, &l, now), None);
+        // A success in between starts the count over.
+        assert_eq!(b.record("a", true, &l, now), None);
+        assert_eq!(b.record("a", false, &l, now), None);
+        assert_eq!(b.record("a", false, &l, now), None);
+        assert_eq!(
+            b.record("a", false, &l, now).as_deref(),
+            Some("3 consecutive failures")
+        );
+        assert!(b.is_quarantined("a"));
+        assert_eq!(b.record("a", false, &l, now), None);
+        assert!(!b.is_quarantined("b"));
+    }
+
+    #[test]
+    fn trips_on_failure_rate_within_the_window() {
+        let b = CircuitBreaker::default();
+        let l = limits(0, 3);
+        let t0 = Instant::now();
+        for i in 0..2 {
+            assert_eq!(b.record("a", false, &l, t0 + Duration::from_secs(i)), None);
+            assert_eq!(b.record("a", true, &l, t0 + Duration::from_secs(i)), None);
+        }
+        // The first two failures have left the window by now.
+        assert_eq!(b.record("a", false, &l, t0 + Duration::from_secs(61)), None);
+        assert_eq!(b.record("a", false, &l, t0 + Duration::from_secs(62)), None);
+        assert_eq!(
+            b.record("a", false, &l, t0 + Duration::from_secs(63))
+                .as_deref(),
+            Some("3 failures within a minute")
+        );
+    }
+
+    #[test]
+    fn zero_limits_never_trip() {
+        let b = CircuitBreaker::default();
+        let now = Instant::now();
+        for _ in 0..1000 {
+            assert_eq!(b.record("a", false, &limits(0, 0), now), None);
+        }
+    }
+
+    #[test]
+    fn resume_and_forget_clear_state() {
+        let b = CircuitBreaker::default();
+        let l = limits(1, 0);
+        let now = Instant::now();
+        assert!(!b.resume("a"));
+        assert!(b.record("a", false, &l, now).is_some());
+        assert!(b.resume("a"));
+        assert!(!b.is_quarantined("a"));
+        assert!(b.record("a", false, &l, now).is_some());
+        assert!(b.record("b", false, &l, now).is_some());
+        b.forget(Some("a"));
+        assert!(!b.is_quarantined("a") && b.is_quarantined("b"));
+        b.forget(None);
+        assert!(!b.is_quarantined("b"));
+    }
+}
@@ -0,0 +1,219 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Server software flavors, set by `[server] flavor` in mcrw.toml.
+//!
+//! A flavor picks the bundled defaults for what differs between server
+//! software: the log prefix layout (unless `[log] format` is set), the
+//! player patterns (players.rs) and the built-in `start` lifecycle pattern.
+//! `auto` starts from the vanilla player patterns and a `start` pattern that
+//! fits every flavor, and settles on a flavor from the startup banner
+//! ([`FlavorDetector`]). The explicit `*_pattern` overrides, `[log] format`
+//! and trigger_config.toml always win over the presets.
+
+use std::fmt;
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::log_line::{LogFormat, LogRecord};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    #[default]
+    Auto,
+    Vanilla,
+    /// Also Spigot, Purpur, Pufferfish and Folia.
+    Paper,
+    /// Also Quilt.
+    Fabric,
+    /// Also NeoForge.
+    Forge,
+    Velocity,
+}
+
+impl Flavor {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Flavor::Auto => "auto",
+            Flavor::Vanilla => "vanilla",
+            Flavor::Paper => "paper",
+            Flavor::Fabric => "fabric",
+            Flavor::Forge => "forge",
+            Flavor::Velocity => "velocity",
+        }
+    }
+
+    /// The prefix layout this flavor prints; used while `[log] format` is
+    /// `auto`.
+    pub fn log_format(self) -> LogFormat {
+        match self {
+            Flavor::Auto => LogFormat::Auto,
+            Flavor::Vanilla => LogFormat::Vanilla,
+            Flavor::Paper | Flavor::Velocity => LogFormat::Paper,
+            Flavor::Fabric => LogFormat::Fabric,
+            Flavor::Forge => LogFormat::Forge,
+        }
+    }
+
+    /// The built-in `start` lifecycle pattern, matched against the whole line.
+    pub fn start_pattern(self) -> &'static str {
+        match self {
+            // Velocity prints a bare "Done (1.23s)!".
+            Flavor::Auto => r#"Done \([0-9.]+s\)!(?: For help|$)"#,
+            Flavor::Velocity => r#"Done \([0-9.]+s\)!$"#,
+            _ => r#"Done \([0-9.]+s\)! For help"#,
+        }
+    }
+}
+
+impl fmt::Display for Flavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// Startup banner lines that name the software, matched against the message.
+static BANNERS: LazyLock<Vec<(Regex, Flavor)>> = LazyLock::new(|| {
+    [
+        (r"^Booting up Velocity ", Flavor::Velocity),
+        (
+            r"(?:Loading|running) (?:Paper|Purpur|Pufferfish|Folia|Spigot|CraftBukkit)\b",
+            Flavor::Paper,
+        ),
+        (
+            r"^Loading Minecraft \S+ with (?:Fabric|Quilt) Loader",
+            Flavor::Fabric,
+        ),
+        (
+            r"^ModLauncher running|^(?:Neo)?Forge mod loading|^MinecraftForge v",
+            Flavor::Forge,
+        ),
+    ]
+    .into_iter()
+    .map(|(p, f)| {
+        (
+            Regex::new(p).expect("built-in banner pattern must compile"),
+            f,
+        )
+    })
+    .collect()
+});
+
+// A vanilla server names no software; by the time it is done starting, or a
+// player joins, it has had every chance to.
+static STARTED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^Done \([0-9.]+s\)!|^\w{3,16} joined the game$")
+        .expect("built-in start pattern must compile")
+});
+
+/// Settles `auto` on a flavor from the startup lines. A configured flavor is
+/// settled from the start.
+pub struct FlavorDetector {
+    flavor: Flavor,
+}
+
+impl FlavorDetector {
+    pub fn new(configured: Flavor) -> Self {
+        Self { flavor: configured }
+    }
+
+    /// The configured or detected flavor; `Auto` until it is known.
+    pub fn flavor(&self) -> Flavor {
+        self.flavor
+    }
+
+    /// Look at one line; returns the flavor on the line that settles it.
+    pub fn observe(&mut self, rec: &LogRecord) -> Option<Flavor> {
+        if self.flavor != Flavor::Auto {
+            return None;
+        }
+        let found = BANNERS
+            .iter()
+            .find(|(re, _)| re.is_match(&rec.message))
+            .map(|(_, f)| *f)
+            .or_else(|| STARTED.is_match(&rec.message).then_some(Flavor::Vanilla))?;
+        self.flavor = found;
+        Some(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_line::LogParser;
+
+    fn detect(lines: &[&str]) -> Option<Flavor> {
+        let p = LogParser::default();
+        let mut d = FlavorDetector::new(Flavor::Auto);
+        let found = lines.iter().find_map(|l| d.observe(&p.parse(l)));
+        assert_eq!(d.flavor(), found.unwrap_or(Flavor::Auto));
+        found
+    }
+
+    #[test]
+    fn detects_flavor_from_banner() {
+        assert_eq!(
+            detect(&[
+                "[12:00:00 INFO]: [bootstrap] Loading Paper 1.20.4-496-ver/1.20.4@7ac24a1 for Minecraft 1.20.4"
+            ]),
+            Some(Flavor::Paper)
+        );
+        assert_eq!(
+            detect(&[
+                "[12:00:00] [main/INFO] (FabricLoader/GameProvider) Loading Minecraft 1.20.1 with Fabric Loader 0.14.21"
+            ]),
+            Some(Flavor::Fabric)
+        );
+        assert_eq!(
+            detect(&[
+                "[12:00:00] [main/INFO] [cp.mo.mo.Launcher/MODLAUNCHER]: ModLauncher running: args [--launchTarget]"
+            ]),
+            Some(Flavor::Forge)
+        );
+        assert_eq!(
+            detect(&["[12:00:00 INFO]: Booting up Velocity 3.3.0-SNAPSHOT..."]),
+            Some(Flavor::Velocity)
+        );
+        // Nothing but vanilla lines: settled as vanilla once the server is up.
+        let vanilla = [
+            "[12:00:00] [Server thread/INFO]: Starting minecraft server version 1.20.4",
+            "[12:00:03] [Server thread/INFO]: Done (3.021s)! For help, type \"help\"",
+        ];
+        assert_eq!(detect(&vanilla[..1]), None);
+        assert_eq!(detect(&vanilla), Some(Flavor::Vanilla));
+    }
+
+    #[test]
+    fn configured_flavor_is_kept() {
+        let mut d = FlavorDetector::new(Flavor::Forge);
+        let rec = LogParser::default().parse("[12:00:00 INFO]: Booting up Velocity 3.3.0");
+        assert_eq!(d.observe(&rec), None);
+        assert_eq!(d.flavor(), Flavor::Forge);
+    }
+
+    #[test]
+    fn start_patterns_match_their_flavor() {
+        let matches = |f: Flavor, line: &str| Regex::new(f.start_pattern()).unwrap().is_match(line);
+        let vanilla = "[12:00:03] [Server thread/INFO]: Done (3.021s)! For help, type \"help\"";
+        let velocity = "[12:00:03 INFO]: Done (1.23s)!";
+        assert!(matches(Flavor::Vanilla, vanilla) && !matches(Flavor::Vanilla, velocity));
+        assert!(matches(Flavor::Velocity, velocity) && !matches(Flavor::Velocity, vanilla));
+        assert!(matches(Flavor::Auto, vanilla) && matches(Flavor::Auto, velocity));
+    }
+}
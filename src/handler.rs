@@ -13,23 +13,40 @@
 
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
-use mlua::{Function, Lua, Variadic};
+use mlua::{
+    Function, IntoLuaMulti, Lua, LuaSerdeExt, MultiValue, SerializeOptions, Value, Variadic,
+};
 use rustyline::{DefaultEditor, error::ReadlineError};
 use tokio::{
-    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncWriteExt, BufReader},
     sync::mpsc,
 };
 
 use crate::{teprintln, tprintln};
 
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
 
+use crate::breaker::CircuitBreaker;
+use crate::flavor::FlavorDetector;
+use crate::log_line::{self, LogAssembler, LogParser, LogRecord};
 use crate::lua_ctx::{
-    self, ChildTracker, ControlMsg, CrashTriggerList, CronJobList, LifecycleEvents, PlayerCallbackList,
-    PlayerHandle, PluginRegistry, StopTriggerList, TriggerList,
+    self, ChildTracker, ControlMsg, CrashTriggerList, CronJobList, EXCEPTION_EVENT, HookList,
+    LifecycleEvents, PlayerCallbackList, PlayerHandle, PluginConfigs, PluginExports,
+    PluginRegistry, PluginVms, QuarantineConfig, SharedMcrwConfig, StopTriggerList, Trigger,
+    TriggerList,
 };
+use crate::metrics::{self, Kind, MetricsRegistry};
 use crate::players::{PlayerEvent, PlayerRegistry};
+use crate::plugin_state::{
+    PLUGIN_STATE_PATH, PluginState, load_plugin_state, save_plugin_state, toggled_plugin_state,
+};
+use crate::rcon;
+use crate::server_info::SharedServerInfo;
 use crate::store::StoreRegistry;
+use crate::timers::TimerList;
+use crate::waiters::{self, LineWaiters};
 
 pub fn spawn_cmd_sender(mut rx: mpsc::Receiver<String>, mut mc_stdin: tokio::process::ChildStdin) {
     // Forwards channel-supplied commands to the Minecraft server's stdin, one
@@ -57,6 +74,199 @@ pub fn spawn_cmd_sender(mut rx: mpsc::Receiver<String>, mut mc_stdin: tokio::pro
     });
 }
 
+// Longest server line kept; the rest of a longer line is cut off.
+const MAX_LINE_BYTES: usize = 64 * 1024;
+
+// Read one line of at most `max` bytes, without its line ending, decoding
+// invalid UTF-8 as U+FFFD. A cut line ends on a character boundary. Returns
+// the line and how many bytes were cut off, or `None` at end of stream.
+async fn read_server_line<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> std::io::Result<Option<(String, usize)>> {
+    buf.clear();
+    let mut skipped = 0;
+    let mut read_any = false;
+    loop {
+        let chunk = reader.fill_buf().await?;
+        if chunk.is_empty() {
+            if !read_any {
+                return Ok(None);
+            }
+            break;
+        }
+        read_any = true;
+        let (line, used, done) = match chunk.iter().position(|&b| b == b'\n') {
+            Some(i) => (&chunk[..i], i + 1, true),
+            None => (chunk, chunk.len(), false),
+        };
+        let take = line.len().min(max.saturating_sub(buf.len()));
+        buf.extend_from_slice(&line[..take]);
+        skipped += line.len() - take;
+        reader.consume(used);
+        if done {
+            break;
+        }
+    }
+    if skipped > 0 {
+        skipped += cut_to_char_boundary(buf);
+    }
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+    Ok(Some((String::from_utf8_lossy(buf).into_owned(), skipped)))
+}
+
+// Drop a multi-byte character the cut went through, so it is not decoded as
+// U+FFFD. Returns how many bytes were dropped.
+fn cut_to_char_boundary(buf: &mut Vec<u8>) -> usize {
+    let start = buf.len().saturating_sub(3);
+    let Some(lead) = (start..buf.len()).rev().find(|&i| buf[i] & 0xC0 != 0x80) else {
+        return 0;
+    };
+    let width = match buf[lead] {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => 1,
+    };
+    let partial = buf.len() - lead;
+    if partial >= width {
+        return 0;
+    }
+    buf.truncate(lead);
+    partial
+}
+
+// Drains the server's stdout on its own task, so the pipe never fills while
+// the main loop is busy (awaiting unload hooks during a reload, say) and the
+// server never blocks writing its log. Each line is echoed to the console
+// here, as printed, then queued for dispatch. The queue is unbounded: every
+// line must still reach the player registry and wait_for/query waiters, and
+// the main loop's stalls are bounded by unload_timeout_ms.
+pub fn spawn_stdout_reader(
+    mc_stdout: tokio::process::ChildStdout,
+) -> mpsc::UnboundedReceiver<String> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(mc_stdout);
+        let mut buf = Vec::new();
+        loop {
+            let (line, skipped) =
+                match read_server_line(&mut reader, &mut buf, MAX_LINE_BYTES).await {
+                    Ok(Some(l)) => l,
+                    Ok(None) => break,
+                    Err(e) => {
+                        teprintln!("[MCRW] read line failed: {}", e);
+                        break;
+                    }
+                };
+            tprintln!("[MC] {}", line);
+            if skipped > 0 {
+                teprintln!(
+                    "[MCRW] [WARNING] Server line over {MAX_LINE_BYTES} bytes; cut the last {skipped} bytes."
+                );
+            }
+            // A send error means the main loop is gone; keep draining so the
+            // server can exit.
+            let _ = tx.send(line);
+        }
+    });
+    rx
+}
+
+// Parse a wrapper built-in console command. `None` means the line is not a
+// built-in and goes to the server unchanged; `Some(Err(usage))` is a built-in
+// with bad arguments, reported to the operator instead of being forwarded.
+pub fn parse_console_command(line: &str) -> Option<Result<ControlMsg, String>> {
+    let mut words = line.split_whitespace();
+    let command = words.next()?;
+    let args: Vec<&str> = words.collect();
+    let usage = |u: &str| Some(Err(format!("usage: {u}")));
+    match (command, args.as_slice()) {
+        ("!reload", []) => Some(Ok(ControlMsg::Reload)),
+        ("!reload", [plugin]) => Some(Ok(ControlMsg::ReloadPlugin(plugin.to_string()))),
+        ("!reload", _) => usage("!reload [plugin]"),
+        ("!unload", [plugin]) => Some(Ok(ControlMsg::UnloadPlugin(plugin.to_string()))),
+        ("!unload", _) => usage("!unload <plugin>"),
+        ("!load", [dir]) => Some(Ok(ControlMsg::LoadPlugin(dir.to_string()))),
+        ("!load", _) => usage("!load <dir>"),
+        ("!plugin", [verb @ ("enable" | "disable"), plugin]) => {
+            Some(Ok(ControlMsg::SetPluginEnabled {
+                plugin: plugin.to_string(),
+                enabled: *verb == "enable",
+            }))
+        }
+        ("!plugin", ["resume", plugin]) => Some(Ok(ControlMsg::ResumePlugin(plugin.to_string()))),
+        ("!plugin", _) => usage("!plugin enable|disable|resume <dir>"),
+        ("!profile", []) => Some(Ok(ControlMsg::Profile {
+            plugin: None,
+            reset: false,
+        })),
+        ("!profile", ["reset"]) => Some(Ok(ControlMsg::Profile {
+            plugin: None,
+            reset: true,
+        })),
+        ("!profile", ["reset", plugin]) => Some(Ok(ControlMsg::Profile {
+            plugin: Some(plugin.to_string()),
+            reset: true,
+        })),
+        ("!profile", [plugin]) => Some(Ok(ControlMsg::Profile {
+            plugin: Some(plugin.to_string()),
+            reset: false,
+        })),
+        ("!profile", _) => usage("!profile [reset] [plugin]"),
+        ("!status", []) => Some(Ok(ControlMsg::Status)),
+        ("!status", _) => usage("!status"),
+        _ => None,
+    }
+}
+
+// The .mcrw/plugins.toml state `!plugin enable|disable <dir>` asks for, or
+// None when there is nothing to do. It is reloaded with, and only written
+// once that reload commits.
+fn plan_plugin_toggle(plugin: &str, enabled: bool) -> Option<PluginState> {
+    let verb = if enabled { "enabled" } else { "disabled" };
+    if !enabled && !Path::new("lua_plugins").join(plugin).is_dir() {
+        teprintln!("[MCRW] [ERROR] no plugin directory 'lua_plugins/{plugin}'");
+        return None;
+    }
+    match toggled_plugin_state(Path::new(PLUGIN_STATE_PATH), plugin, enabled) {
+        Ok(Some(state)) => {
+            tprintln!("[MCRW] Plugin '{plugin}' {verb}; reloading.");
+            Some(state)
+        }
+        Ok(None) => {
+            tprintln!("[MCRW] Plugin '{plugin}' is already {verb}.");
+            None
+        }
+        Err(e) => {
+            let action = if enabled { "enable" } else { "disable" };
+            teprintln!("[MCRW] [ERROR] cannot {action} '{plugin}': {e}");
+            None
+        }
+    }
+}
+
+// `!profile [reset] [plugin]`: print the callback metrics table, or clear it.
+fn show_profile(metrics: &MetricsRegistry, plugin: Option<&str>, reset: bool) {
+    let scope = plugin.map(|p| format!(" for '{p}'")).unwrap_or_default();
+    if reset {
+        let n = metrics.reset(plugin);
+        tprintln!("[MCRW] Cleared {n} profile entries{scope}.");
+        return;
+    }
+    let stats = metrics.snapshot(plugin);
+    if stats.is_empty() {
+        tprintln!("[MCRW] No callbacks recorded{scope}.");
+        return;
+    }
+    for line in metrics::format_profile(&stats) {
+        tprintln!("[MCRW] {line}");
+    }
+}
+
 pub fn spawn_terminal_receiver(tx: mpsc::Sender<String>, ctl_tx: mpsc::Sender<ControlMsg>) {
     // this routine reads lines from the wrapper terminal and either
     //   - intercepts wrapper built-in commands (e.g. `!reload`) into the control channel, or
@@ -66,13 +276,18 @@ pub fn spawn_terminal_receiver(tx: mpsc::Sender<String>, ctl_tx: mpsc::Sender<Co
         let mut line = String::new();
 
         while reader.read_line(&mut line).await.unwrap_or(0) > 0 {
-            let trimmed = line.trim();
-            if trimmed == "!reload" {
-                if ctl_tx.send(ControlMsg::Reload).await.is_err() {
-                    break;
+            match parse_console_command(line.trim()) {
+                Some(Ok(msg)) => {
+                    if ctl_tx.send(msg).await.is_err() {
+                        break;
+                    }
+                }
+                Some(Err(usage)) => teprintln!("[MCRW] {usage}"),
+                None => {
+                    if tx.send(line.clone()).await.is_err() {
+                        break;
+                    }
                 }
-            } else if tx.send(line.clone()).await.is_err() {
-                break;
             }
             line.clear();
         }
@@ -130,12 +345,18 @@ pub fn spawn_console_editor(
                     // In-memory history only; not persisted across restarts.
                     let _ = editor.add_history_entry(trimmed);
 
-                    if trimmed == "!reload" {
-                        if ctl_tx.blocking_send(ControlMsg::Reload).is_err() {
-                            break; // receiver gone → wrapper shutting down
+                    match parse_console_command(trimmed) {
+                        Some(Ok(msg)) => {
+                            if ctl_tx.blocking_send(msg).is_err() {
+                                break; // receiver gone → wrapper shutting down
+                            }
+                        }
+                        Some(Err(usage)) => teprintln!("[MCRW] {usage}"),
+                        None => {
+                            if tx.blocking_send(trimmed.to_string()).is_err() {
+                                break;
+                            }
                         }
-                    } else if tx.blocking_send(trimmed.to_string()).is_err() {
-                        break;
                     }
                 }
                 Err(ReadlineError::Interrupted) => {
@@ -166,6 +387,120 @@ pub fn spawn_console_editor(
     });
 }
 
+// What a dispatch task needs besides the callbacks themselves: where to
+// account each call, and how to report a plugin that trips the breaker.
+pub struct Dispatch {
+    pub metrics: Arc<MetricsRegistry>,
+    pub breaker: Arc<CircuitBreaker>,
+    pub limits: QuarantineConfig,
+    pub ctl_tx: mpsc::Sender<ControlMsg>,
+}
+
+impl Dispatch {
+    // Call one plugin callback and return the commands it produced. Skipped
+    // while the plugin is quarantined; a failure is logged with the plugin
+    // and `key` (trigger pattern, cron expression, event) it came from.
+    async fn call(
+        &self,
+        f: &Function,
+        args: impl IntoLuaMulti,
+        plugin: &str,
+        kind: Kind,
+        key: &str,
+    ) -> Vec<String> {
+        if self.breaker.is_quarantined(plugin) {
+            return Vec::new();
+        }
+        let started = Instant::now();
+        let result = f.call_async::<Option<Vec<String>>>(args).await;
+        self.settle(plugin, kind, key, started, result)
+            .await
+            .unwrap_or_default()
+    }
+
+    // Like `call`, for the lifecycle callbacks whose return value means
+    // nothing (load complete, quarantine, config change).
+    pub async fn notify(&self, f: &Function, args: impl IntoLuaMulti, plugin: &str, key: &str) {
+        if self.breaker.is_quarantined(plugin) {
+            return;
+        }
+        let started = Instant::now();
+        let result = f.call_async::<()>(args).await.map(|()| None);
+        self.settle(plugin, Kind::Lifecycle, key, started, result)
+            .await;
+    }
+
+    // Account a finished call, log its failure and report a trip.
+    async fn settle(
+        &self,
+        plugin: &str,
+        kind: Kind,
+        key: &str,
+        started: Instant,
+        result: mlua::Result<Option<Vec<String>>>,
+    ) -> Option<Vec<String>> {
+        self.metrics
+            .record_call(plugin, kind, key, started, &result);
+        let tripped = self
+            .breaker
+            .record(plugin, result.is_ok(), &self.limits, Instant::now());
+        let commands = match result {
+            Ok(cmds) => cmds,
+            Err(e) => {
+                let site = if key.is_empty() {
+                    plugin.to_string()
+                } else {
+                    format!("{plugin} / {key}")
+                };
+                teprintln!(
+                    "[MCRW] [ERROR] {} callback failed ({site}): {e}",
+                    kind.as_str()
+                );
+                None
+            }
+        };
+        if let Some(reason) = tripped {
+            let _ = self
+                .ctl_tx
+                .send(ControlMsg::Quarantined {
+                    plugin: plugin.to_string(),
+                    reason,
+                })
+                .await;
+        }
+        commands
+    }
+}
+
+// Callback arguments if `t` fires for this line: the line and its captures,
+// or for a filter trigger the record and the captures in its message. Built
+// in the plugin's own Lua state, which may be isolated.
+fn trigger_args(vm: &Lua, t: &Trigger, record: &LogRecord) -> Option<mlua::Result<MultiValue>> {
+    let Some(filter) = &t.filter else {
+        let args = waiters::captures(&t.regex, &record.raw)?;
+        return Some(Variadic::from_iter(args).into_lua_multi(vm));
+    };
+    if !filter.matches(record) {
+        return None;
+    }
+    let mut args = waiters::captures(&t.regex, &record.message)?;
+    args.remove(0);
+    Some(
+        record_table(vm, record)
+            .and_then(|rec| (rec, Variadic::from_iter(args)).into_lua_multi(vm)),
+    )
+}
+
+// A record as handed to filter triggers and exception callbacks. Missing
+// fields are left out rather than set to a null sentinel, so
+// `record.thread == nil` works.
+fn record_table(vm: &Lua, record: &LogRecord) -> mlua::Result<Value> {
+    vm.to_value_with(
+        record,
+        SerializeOptions::new().serialize_none_to_null(false),
+    )
+}
+
 // The main loop is the single owner that threads every piece of shared wrapper
 // state (trigger lists, registries, cron jobs, channels) into the select! loop.
 // Bundling them into a context struct would only move the same fields elsewhere,
@@ -181,38 +516,149 @@ pub async fn run_main_loop(
     lifecycle_events: LifecycleEvents,
     children: ChildTracker,
     cron_jobs: CronJobList,
+    timers: TimerList,
+    line_waiters: LineWaiters,
+    server_info: SharedServerInfo,
     player_registry: Arc<PlayerRegistry>,
     join_triggers: PlayerCallbackList,
     leave_triggers: PlayerCallbackList,
+    unload_hooks: HookList,
+    load_complete_hooks: HookList,
+    exports: PluginExports,
+    configs: PluginConfigs,
+    vms: PluginVms,
+    force_incompatible: bool,
+    mcrw_config: SharedMcrwConfig,
+    metrics: Arc<MetricsRegistry>,
+    breaker: Arc<CircuitBreaker>,
+    ctl_tx: mpsc::Sender<ControlMsg>,
     mut ctl_rx: mpsc::Receiver<ControlMsg>,
     lua: &Lua,
 ) {
-    let mut reader = BufReader::new(mc_stdout).lines();
+    let mut server_lines = spawn_stdout_reader(mc_stdout);
 
     let tx_main: mpsc::Sender<String> = tx.clone();
+    // Settles `[server] flavor = "auto"` from the startup banner.
+    let mut flavor = FlavorDetector::new(mcrw_config.lock().unwrap().server.flavor);
+    // Rebuilt when mcrw.toml's [log] section changes or the flavor is detected.
+    let mut log_parser = LogParser::from_config(&mcrw_config.lock().unwrap().log, flavor.flavor());
+    let mut assembler = LogAssembler::from_config(&mcrw_config.lock().unwrap().log);
+    // Handed to each dispatch task, with the current [quarantine] limits.
+    let dispatch = |cfg: &SharedMcrwConfig| Dispatch {
+        metrics: metrics.clone(),
+        breaker: breaker.clone(),
+        limits: cfg.lock().unwrap().quarantine.clone(),
+        ctl_tx: ctl_tx.clone(),
+    };
+    // Filter triggers and register_on_exception callbacks see whole records
+    // from the assembler instead of lines. A record's callbacks run on their
+    // own task, triggers first, each group in registration order.
+    let dispatch_record = |record: LogRecord| {
+        let mut pending: Vec<(Function, MultiValue, String, Kind, String)> = Vec::new();
+        match triggers.lock() {
+            Ok(g) => {
+                for t in g.iter().filter(|t| t.filter.is_some()) {
+                    let vm = lua_ctx::plugin_lua(lua, &vms, &t.plugin);
+                    let Some(args) = trigger_args(&vm, t, &record) else {
+                        continue;
+                    };
+                    match args
+                        .and_then(|args| Ok((vm.registry_value::<Function>(&t.callback)?, args)))
+                    {
+                        Ok((f, args)) => {
+                            pending.push((f, args, t.plugin.clone(), Kind::Trigger, t.key()))
+                        }
+                        Err(e) => teprintln!("[MCRW] [ERROR] trigger registry lookup: {e}"),
+                    }
+                }
+            }
+            Err(e) => teprintln!("[MCRW] [ERROR] trigger lock poisoned: {e}"),
+        }
+        if record.exception.is_some() {
+            match lifecycle_events.lock() {
+                Ok(g) => {
+                    for cb in g.get(EXCEPTION_EVENT).map_or(&[][..], |s| &s.callbacks) {
+                        let vm = lua_ctx::plugin_lua(lua, &vms, &cb.plugin);
+                        let f = vm.registry_value::<Function>(&cb.callback);
+                        match f
+                            .and_then(|f| Ok((f, record_table(&vm, &record)?.into_lua_multi(&vm)?)))
+                        {
+                            Ok((f, args)) => pending.push((
+                                f,
+                                args,
+                                cb.plugin.clone(),
+                                Kind::Lifecycle,
+                                EXCEPTION_EVENT.to_string(),
+                            )),
+                            Err(e) => teprintln!("[MCRW] [ERROR] exception registry lookup: {e}"),
+                        }
+                    }
+                }
+                Err(e) => teprintln!("[MCRW] [ERROR] lifecycle lock poisoned: {e}"),
+            }
+        }
+        if pending.is_empty() {
+            return;
+        }
+        let tx_line = tx_main.clone();
+        let dispatch = dispatch(&mcrw_config);
+        tokio::spawn(async move {
+            let mut commands_to_exec: Vec<String> = Vec::new();
+            for (f, args, plugin, kind, key) in pending {
+                commands_to_exec.extend(dispatch.call(&f, args, &plugin, kind, &key).await);
+            }
+            for cmd in commands_to_exec {
+                match tx_line.send(format!("{}\n", cmd)).await {
+                    Ok(_) => tprintln!("[MCRW -> Server]: {}", cmd),
+                    Err(_) => tprintln!("[MCRW] Fail to send cmd: {}", cmd),
+                };
+            }
+        });
+    };
     loop {
+        let record_due = assembler.deadline();
         tokio::select! {
-            line_result = reader.next_line() => {
-                let line = match line_result {
-                    Ok(Some(line)) => line,
-                    Ok(None) => break,
-                    Err(e) => {
-                        teprintln!("[MCRW] read line failed: {}", e);
-                        break;
+            line = server_lines.recv() => {
+                let Some(line) = line else { break };
+                // Paper colors its console; everything below matches the
+                // line without the escape codes.
+                let line = log_line::strip_ansi(&line).into_owned();
+                let mut record = log_parser.parse(&line);
+                if let Some(detected) = flavor.observe(&record) {
+                    tprintln!("[MCRW] Detected server flavor: {detected}");
+                    server_info.lock().unwrap().flavor = detected;
+                    let cfg = mcrw_config.lock().unwrap().clone();
+                    player_registry.set_flavor(detected, &cfg.players);
+                    log_parser = LogParser::from_config(&cfg.log, detected);
+                    record = log_parser.parse(&line);
+                }
+                {
+                    let mut info = server_info.lock().unwrap();
+                    if info.observe(&record) {
+                        info.fill_from_properties(&rcon::read_server_properties());
                     }
-                };
-                tprintln!("[MC] {}", line);
+                }
+                // Resolve wrapper:wait_for waiters before any callback is
+                // dispatched, so a coroutine waiting on this line resumes ahead
+                // of the triggers (including the filter triggers of the record
+                // this line completes).
+                waiters::observe_line(&line_waiters, &line);
+
+                if let Some(done) = assembler.push(record.clone(), tokio::time::Instant::now()) {
+                    dispatch_record(done);
+                }
 
-                // Feed every line to the player registry first: it updates cached
-                // records and resolves any in-flight pos()/dimension() waiters, and
-                // returns join/leave events for us to dispatch to Lua callbacks.
-                let player_pending: Vec<(Function, PlayerHandle)> = {
-                    let events = player_registry.observe_line(&line);
+                // Feed every line to the player registry before the regex
+                // triggers: it updates cached records and resolves any in-flight
+                // pos()/dimension() waiters, and returns join/leave events for us
+                // to dispatch to Lua callbacks.
+                let player_pending: Vec<(Function, PlayerHandle, String, Kind)> = {
+                    let events = player_registry.observe(&record);
                     let mut v = Vec::new();
                     for ev in &events {
-                        let (list, name) = match ev {
-                            PlayerEvent::Joined(n) => (&join_triggers, n),
-                            PlayerEvent::Left(n) => (&leave_triggers, n),
+                        let (list, name, kind) = match ev {
+                            PlayerEvent::Joined(n) => (&join_triggers, n, Kind::Join),
+                            PlayerEvent::Left(n) => (&leave_triggers, n, Kind::Leave),
                         };
                         let g = match list.lock() {
                             Ok(g) => g,
@@ -222,10 +668,14 @@ pub async fn run_main_loop(
                             }
                         };
                         for cb in g.iter() {
-                            match lua.registry_value::<Function>(cb) {
+                            match lua_ctx::plugin_lua(lua, &vms, &cb.plugin)
+                                .registry_value::<Function>(&cb.callback)
+                            {
                                 Ok(f) => v.push((
                                     f,
                                     PlayerHandle::new(player_registry.clone(), name.clone()),
+                                    cb.plugin.clone(),
+                                    kind,
                                 )),
                                 Err(e) => {
                                     teprintln!("[MCRW] [ERROR] player registry lookup: {e}")
@@ -243,7 +693,7 @@ pub async fn run_main_loop(
                 // (e.g. one waiting on a Python subprocess) is in flight. Callbacks for
                 // the SAME line still run sequentially in registration order inside the
                 // task; only DIFFERENT lines' dispatches run concurrently.
-                let pending: Vec<(Function, Vec<String>)> = {
+                let pending: Vec<(Function, MultiValue, String, String)> = {
                     let g = match triggers.lock() {
                         Ok(g) => g,
                         Err(e) => {
@@ -252,23 +702,18 @@ pub async fn run_main_loop(
                         }
                     };
                     let mut v = Vec::new();
-                    for t in g.iter() {
-                        if let Some(caps) = t.regex.captures(&line) {
-                            let mut args = Vec::with_capacity(caps.len());
-                            args.push(line.clone());
-                            for i in 1..caps.len() {
-                                args.push(caps.get(i).map_or("", |m| m.as_str()).to_string());
-                            }
-                            match lua.registry_value::<Function>(&t.callback) {
-                                Ok(f) => v.push((f, args)),
-                                Err(e) => teprintln!("[MCRW] [ERROR] trigger registry lookup: {e}"),
-                            }
+                    for t in g.iter().filter(|t| t.filter.is_none()) {
+                        let vm = lua_ctx::plugin_lua(lua, &vms, &t.plugin);
+                        let Some(args) = trigger_args(&vm, t, &record) else { continue };
+                        match args.and_then(|args| Ok((vm.registry_value::<Function>(&t.callback)?, args))) {
+                            Ok((f, args)) => v.push((f, args, t.plugin.clone(), t.key())),
+                            Err(e) => teprintln!("[MCRW] [ERROR] trigger registry lookup: {e}"),
                         }
                     }
                     v
                 };
 
-                let lifecycle_pending: Vec<Function> = {
+                let lifecycle_pending: Vec<(Function, String, String)> = {
                     let mut events = match lifecycle_events.lock() {
                         Ok(g) => g,
                         Err(e) => {
@@ -277,7 +722,7 @@ pub async fn run_main_loop(
                         }
                     };
                     let mut funcs = Vec::new();
-                    for (_name, state) in events.iter_mut() {
+                    for (name, state) in events.iter_mut() {
                         let mut should_fire = false;
                         for p in state.patterns.iter_mut() {
                             if p.fired {
@@ -291,9 +736,11 @@ pub async fn run_main_loop(
                             }
                         }
                         if should_fire {
-                            for cb_key in state.callbacks.iter() {
-                                match lua.registry_value::<Function>(cb_key) {
-                                    Ok(f) => funcs.push(f),
+                            for cb in state.callbacks.iter() {
+                                match lua_ctx::plugin_lua(lua, &vms, &cb.plugin)
+                                    .registry_value::<Function>(&cb.callback)
+                                {
+                                    Ok(f) => funcs.push((f, cb.plugin.clone(), name.clone())),
                                     Err(e) => teprintln!(
                                         "[MCRW] [ERROR] lifecycle registry lookup: {e}"
                                     ),
@@ -308,40 +755,20 @@ pub async fn run_main_loop(
                 {
                     let tx_line = tx_main.clone();
                     let line_for_lc = line;
+                    let dispatch = dispatch(&mcrw_config);
                     tokio::spawn(async move {
                         let mut commands_to_exec: Vec<String> = Vec::new();
-                        for (f, handle) in player_pending {
-                            match f.call_async::<Option<Vec<String>>>(handle).await {
-                                Ok(Some(cmds)) => commands_to_exec.extend(cmds),
-                                Ok(None) => {}
-                                Err(e) => {
-                                    teprintln!("[MCRW] [ERROR] player callback failed: {e}")
-                                }
-                            }
+                        for (f, handle, plugin, kind) in player_pending {
+                            commands_to_exec.extend(dispatch.call(&f, handle, &plugin, kind, "").await);
                         }
-                        for (f, args) in pending {
-                            match f
-                                .call_async::<Option<Vec<String>>>(Variadic::from_iter(args))
-                                .await
-                            {
-                                Ok(Some(cmds)) => commands_to_exec.extend(cmds),
-                                Ok(None) => {}
-                                Err(e) => {
-                                    teprintln!("[MCRW] [ERROR] trigger callback failed: {e}")
-                                }
-                            }
+                        for (f, args, plugin, pattern) in pending {
+                            commands_to_exec
+                                .extend(dispatch.call(&f, args, &plugin, Kind::Trigger, &pattern).await);
                         }
-                        for f in lifecycle_pending {
-                            match f
-                                .call_async::<Option<Vec<String>>>(line_for_lc.clone())
-                                .await
-                            {
-                                Ok(Some(cmds)) => commands_to_exec.extend(cmds),
-                                Ok(None) => {}
-                                Err(e) => {
-                                    teprintln!("[MCRW] [ERROR] lifecycle callback failed: {e}")
-                                }
-                            }
+                        for (f, plugin, event) in lifecycle_pending {
+                            let line = line_for_lc.clone();
+                            commands_to_exec
+                                .extend(dispatch.call(&f, line, &plugin, Kind::Lifecycle, &event).await);
                         }
                         for cmd in commands_to_exec {
                             match tx_line.send(format!("{}\n", cmd)).await {
@@ -353,10 +780,169 @@ pub async fn run_main_loop(
                 }
             }
             ctl = ctl_rx.recv() => {
-                match ctl {
-                    Some(ControlMsg::Reload) => {
-                        if let Err(e) = lua_ctx::reload_plugins(
+                let Some(ctl) = ctl else { continue };
+                let reload_cfg = mcrw_config.lock().unwrap().reload.clone();
+                let timeout_ms = reload_cfg.unload_timeout_ms;
+                let full_reload = |state: &PluginState| {
+                    lua_ctx::reload_plugins(
+                        lua,
+                        &triggers,
+                        &stop_triggers,
+                        &crash_triggers,
+                        &plugins,
+                        &lifecycle_events,
+                        &children,
+                        &cron_jobs,
+                        &timers,
+                        &join_triggers,
+                        &leave_triggers,
+                        &unload_hooks,
+                        &load_complete_hooks,
+                        &exports,
+                        &configs,
+                        &vms,
+                        state,
+                        force_incompatible,
+                        &reload_cfg.critical,
+                        flavor.flavor(),
+                    )
+                };
+                let reload_one = |plugin: &str| {
+                    lua_ctx::reload_plugin(
+                        lua,
+                        plugin,
+                        &triggers,
+                        &stop_triggers,
+                        &crash_triggers,
+                        &plugins,
+                        &lifecycle_events,
+                        &children,
+                        &cron_jobs,
+                        &timers,
+                        &join_triggers,
+                        &leave_triggers,
+                        &unload_hooks,
+                        &load_complete_hooks,
+                        &exports,
+                        &configs,
+                        &vms,
+                        force_incompatible,
+                    )
+                };
+                // Unload hooks run before each operation; load-complete hooks
+                // after it, for every plugin (full reload) or just the one that
+                // was (re)loaded: "load" when it committed, "rollback" when the
+                // instances whose unload hooks ran were put back.
+                let (what, result) = match ctl {
+                    ControlMsg::Reload => {
+                        lua_ctx::run_unload_hooks(lua, &vms, &unload_hooks, &metrics, |_| true, "reload", timeout_ms)
+                            .await;
+                        let result = full_reload(&load_plugin_state(Path::new(PLUGIN_STATE_PATH)));
+                        if result.is_ok() {
+                            breaker.forget(None);
+                            line_waiters.clear();
+                        }
+                        let reason = if result.is_ok() { "load" } else { "rollback" };
+                        lua_ctx::spawn_load_complete_hooks(
+                            lua,
+                            &vms,
+                            &load_complete_hooks,
+                            dispatch(&mcrw_config),
+                            |_| true,
+                            reason,
+                        );
+                        ("reload".to_string(), result)
+                    }
+                    ControlMsg::SetPluginEnabled { plugin, enabled } => {
+                        let result = if let Some(state) = plan_plugin_toggle(&plugin, enabled) {
+                            // A plugin being disabled does not come back.
+                            let gone = |p: &str| !enabled && p == plugin;
+                            lua_ctx::run_unload_hooks(
+                                lua,
+                                &vms,
+                                &unload_hooks,
+                                &metrics,
+                                gone,
+                                "unload",
+                                timeout_ms,
+                            )
+                            .await;
+                            lua_ctx::run_unload_hooks(
+                                lua,
+                                &vms,
+                                &unload_hooks,
+                                &metrics,
+                                |p| !gone(p),
+                                "reload",
+                                timeout_ms,
+                            )
+                            .await;
+                            let result = full_reload(&state).map_err(|e| {
+                                mlua::Error::external(format!("{e}; {PLUGIN_STATE_PATH} left unchanged"))
+                            });
+                            if result.is_ok() {
+                                if let Err(e) = save_plugin_state(Path::new(PLUGIN_STATE_PATH), &state) {
+                                    teprintln!("[MCRW] [ERROR] {e}; the change lasts until the next restart");
+                                }
+                                breaker.forget(None);
+                                line_waiters.clear();
+                            }
+                            let reason = if result.is_ok() { "load" } else { "rollback" };
+                            lua_ctx::spawn_load_complete_hooks(
+                                lua,
+                                &vms,
+                                &load_complete_hooks,
+                                dispatch(&mcrw_config),
+                                |_| true,
+                                reason,
+                            );
+                            result
+                        } else {
+                            Ok(())
+                        };
+                        ("reload".to_string(), result)
+                    }
+                    ControlMsg::ReloadPlugin(plugin) => {
+                        lua_ctx::run_unload_hooks(
+                            lua,
+                            &vms,
+                            &unload_hooks,
+                            &metrics,
+                            |p| p == plugin,
+                            "reload",
+                            timeout_ms,
+                        )
+                        .await;
+                        let result = reload_one(&plugin);
+                        if result.is_ok() {
+                            breaker.forget(Some(&plugin));
+                            line_waiters.cancel_where(|w| w.plugin == plugin);
+                        }
+                        let reason = if result.is_ok() { "load" } else { "rollback" };
+                        lua_ctx::spawn_load_complete_hooks(
                             lua,
+                            &vms,
+                            &load_complete_hooks,
+                            dispatch(&mcrw_config),
+                            |p| p == plugin,
+                            reason,
+                        );
+                        (format!("reload {plugin}"), result)
+                    }
+                    ControlMsg::UnloadPlugin(plugin) => {
+                        lua_ctx::run_unload_hooks(
+                            lua,
+                            &vms,
+                            &unload_hooks,
+                            &metrics,
+                            |p| p == plugin,
+                            "unload",
+                            timeout_ms,
+                        )
+                        .await;
+                        let result = lua_ctx::unload_plugin(
+                            lua,
+                            &plugin,
                             &triggers,
                             &stop_triggers,
                             &crash_triggers,
@@ -364,13 +950,127 @@ pub async fn run_main_loop(
                             &lifecycle_events,
                             &children,
                             &cron_jobs,
+                            &timers,
                             &join_triggers,
                             &leave_triggers,
-                        ) {
-                            teprintln!("[MCRW] [ERROR] reload failed: {}", e);
+                            &unload_hooks,
+                            &load_complete_hooks,
+                            &exports,
+                            &configs,
+                            &vms,
+                        );
+                        breaker.forget(Some(&plugin));
+                        line_waiters.cancel_where(|w| w.plugin == plugin);
+                        (format!("unload {plugin}"), result)
+                    }
+                    ControlMsg::PluginChanged(plugin) => {
+                        let loaded = plugins.lock().unwrap().contains_key(&plugin);
+                        let result = if loaded {
+                            tprintln!("[MCRW] lua_plugins/{plugin} changed; reloading it.");
+                            lua_ctx::run_unload_hooks(
+                                lua,
+                                &vms,
+                                &unload_hooks,
+                                &metrics,
+                                |p| p == plugin,
+                                "reload",
+                                timeout_ms,
+                            )
+                            .await;
+                            reload_one(&plugin)
+                        } else if Path::new("lua_plugins").join(&plugin).join("init.lua").exists()
+                            && !load_plugin_state(Path::new(PLUGIN_STATE_PATH)).is_disabled(&plugin)
+                        {
+                            tprintln!("[MCRW] lua_plugins/{plugin} appeared; loading it.");
+                            lua_ctx::load_plugin(lua, &plugin, &plugins, &vms, force_incompatible)
+                        } else {
+                            Ok(())
+                        };
+                        if result.is_ok() {
+                            breaker.forget(Some(&plugin));
+                            line_waiters.cancel_where(|w| w.plugin == plugin);
+                        }
+                        // A failed reload put the old instance back; a failed
+                        // first load has nothing to tell.
+                        if result.is_ok() || loaded {
+                            let reason = if result.is_ok() { "load" } else { "rollback" };
+                            lua_ctx::spawn_load_complete_hooks(
+                                lua,
+                                &vms,
+                                &load_complete_hooks,
+                                dispatch(&mcrw_config),
+                                |p| p == plugin,
+                                reason,
+                            );
+                        }
+                        (format!("reload {plugin}"), result)
+                    }
+                    ControlMsg::ConfigChanged(plugin) => {
+                        let result =
+                            lua_ctx::reload_plugin_config(lua, &vms, &configs, dispatch(&mcrw_config), &plugin);
+                        (format!("config reload {plugin}"), result)
+                    }
+                    ControlMsg::McrwConfigChanged => {
+                        lua_ctx::reload_mcrw_config(
+                            Path::new("mcrw.toml"),
+                            &mcrw_config,
+                            &player_registry,
+                        );
+                        log_parser = LogParser::from_config(&mcrw_config.lock().unwrap().log, flavor.flavor());
+                        assembler.set_limits(&mcrw_config.lock().unwrap().log);
+                        ("mcrw.toml reload".to_string(), Ok(()))
+                    }
+                    ControlMsg::Quarantined { plugin, reason } => {
+                        teprintln!(
+                            "[MCRW] [WARNING] Plugin '{plugin}' quarantined after {reason}; its callbacks are suspended until `!plugin resume {plugin}` or `!reload {plugin}`."
+                        );
+                        lua_ctx::spawn_quarantine_event(
+                            lua,
+                            &vms,
+                            &lifecycle_events,
+                            dispatch(&mcrw_config),
+                            &plugin,
+                            &reason,
+                        );
+                        ("quarantine".to_string(), Ok(()))
+                    }
+                    ControlMsg::ResumePlugin(plugin) => {
+                        if breaker.resume(&plugin) {
+                            tprintln!("[MCRW] Plugin '{plugin}' resumed.");
+                        } else {
+                            tprintln!("[MCRW] Plugin '{plugin}' is not quarantined.");
                         }
+                        (format!("resume {plugin}"), Ok(()))
+                    }
+                    ControlMsg::Profile { plugin, reset } => {
+                        show_profile(&metrics, plugin.as_deref(), reset);
+                        ("profile".to_string(), Ok(()))
                     }
-                    None => {}
+                    ControlMsg::Status => {
+                        let online = player_registry.online_names().len();
+                        for line in server_info.lock().unwrap().status_lines(online) {
+                            tprintln!("[MCRW] {line}");
+                        }
+                        ("status".to_string(), Ok(()))
+                    }
+                    ControlMsg::LoadPlugin(plugin) => {
+                        let result =
+                            lua_ctx::load_plugin(lua, &plugin, &plugins, &vms, force_incompatible);
+                        if result.is_ok() {
+                            lua_ctx::spawn_load_complete_hooks(
+                                lua,
+                                &vms,
+                                &load_complete_hooks,
+                                dispatch(&mcrw_config),
+                                |p| p == plugin,
+                                "load",
+                            );
+                        }
+                        (format!("load {plugin}"), result)
+                    }
+                };
+                if let Err(e) = result {
+                    teprintln!("[MCRW] [ERROR] {} failed: {}", what, e);
                 }
             }
             // Cron arm. The future computes the next-fire deadline and sleeps
@@ -390,22 +1090,58 @@ pub async fn run_main_loop(
                     None => std::future::pending::<()>().await,
                 }
             } => {
-                let due = lua_ctx::drain_due_cron_jobs(lua, &cron_jobs, chrono::Local::now());
+                let due = lua_ctx::drain_due_cron_jobs(lua, &vms, &cron_jobs, chrono::Local::now());
                 if !due.is_empty() {
                     let tx_line = tx_main.clone();
+                    let dispatch = dispatch(&mcrw_config);
                     tokio::spawn(async move {
                         let mut commands_to_exec: Vec<String> = Vec::new();
                         for (f, fire_time, plugin, expr) in due {
-                            match f
-                                .call_async::<Option<Vec<String>>>(fire_time)
-                                .await
-                            {
-                                Ok(Some(cmds)) => commands_to_exec.extend(cmds),
-                                Ok(None) => {}
-                                Err(e) => teprintln!(
-                                    "[MCRW] [ERROR] cron callback failed ({plugin} / {expr}): {e}"
-                                ),
-                            }
+                            commands_to_exec
+                                .extend(dispatch.call(&f, fire_time, &plugin, Kind::Cron, &expr).await);
+                        }
+                        for cmd in commands_to_exec {
+                            match tx_line.send(format!("{}\n", cmd)).await {
+                                Ok(_) => tprintln!("[MCRW -> Server]: {}", cmd),
+                                Err(_) => tprintln!("[MCRW] Fail to send cmd: {}", cmd),
+                            };
+                        }
+                    });
+                }
+            }
+            // A pending record with no continuation line for [log] max_delay_ms
+            // is complete.
+            _ = async {
+                match record_due {
+                    Some(due) => tokio::time::sleep_until(due).await,
+                    None => std::future::pending::<()>().await,
+                }
+            } => {
+                if let Some(done) = assembler.flush() {
+                    dispatch_record(done);
+                }
+            }
+            // Timer arm (wrapper:after / wrapper:every). Sleeps until the
+            // earliest deadline, or until a plugin adds a timer that may be
+            // earlier; a wake with nothing due just loops.
+            _ = async {
+                match timers.next_due() {
+                    Some(due) => {
+                        let _ = tokio::time::timeout_at(due, timers.changed()).await;
+                    }
+                    None => timers.changed().await,
+                }
+            } => {
+                let due = timers.drain_due(tokio::time::Instant::now(), |plugin, key| {
+                    lua_ctx::plugin_lua(lua, &vms, plugin).registry_value::<Function>(key)
+                });
+                if !due.is_empty() {
+                    let tx_line = tx_main.clone();
+                    let dispatch = dispatch(&mcrw_config);
+                    tokio::spawn(async move {
+                        let mut commands_to_exec: Vec<String> = Vec::new();
+                        for (f, plugin, key) in due {
+                            commands_to_exec.extend(dispatch.call(&f, (), &plugin, Kind::Timer, &key).await);
                         }
                         for cmd in commands_to_exec {
                             match tx_line.send(format!("{}\n", cmd)).await {
@@ -418,15 +1154,22 @@ pub async fn run_main_loop(
             }
         }
     }
+    // stdout closed: the last record gets no more lines.
+    if let Some(done) = assembler.flush() {
+        dispatch_record(done);
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn check_shutdown(
     lua: &Lua,
+    vms: &PluginVms,
     mut child: tokio::process::Child,
     stop_triggers: StopTriggerList,
     crash_triggers: CrashTriggerList,
     player_registry: Arc<PlayerRegistry>,
     store: Arc<StoreRegistry>,
+    metrics: Arc<MetricsRegistry>,
 ) {
     match child.wait().await {
         Ok(status) => {
@@ -438,7 +1181,7 @@ pub async fn check_shutdown(
             store.flush();
             if status.success() {
                 tprintln!("[MCRW] Minecraft server stopped gracefully (Exit Code: 0).");
-                let funcs: Vec<Function> = {
+                let funcs: Vec<(Function, String)> = {
                     let g = match stop_triggers.lock() {
                         Ok(g) => g,
                         Err(e) => {
@@ -447,18 +1190,32 @@ pub async fn check_shutdown(
                         }
                     };
                     g.iter()
-                        .filter_map(|st| match lua.registry_value::<Function>(&st.callback) {
-                            Ok(f) => Some(f),
-                            Err(e) => {
-                                teprintln!("[MCRW] [ERROR] stop registry lookup: {e}");
-                                None
+                        .filter_map(|st| {
+                            match lua_ctx::plugin_lua(lua, vms, &st.plugin)
+                                .registry_value::<Function>(&st.callback)
+                            {
+                                Ok(f) => Some((f, st.plugin.clone())),
+                                Err(e) => {
+                                    teprintln!("[MCRW] [ERROR] stop registry lookup: {e}");
+                                    None
+                                }
                             }
                         })
                         .collect()
                 };
-                for f in funcs {
-                    if let Err(e) = f.call_async::<()>(()).await {
-                        teprintln!("[MCRW] [ERROR] stop callback failed: {}", e);
+                for (f, plugin) in funcs {
+                    let started = Instant::now();
+                    let result = f.call_async::<()>(()).await;
+                    metrics.record(
+                        &plugin,
+                        Kind::Stop,
+                        "",
+                        started.elapsed(),
+                        result.is_ok(),
+                        0,
+                    );
+                    if let Err(e) = result {
+                        teprintln!("[MCRW] [ERROR] stop callback failed ({plugin}): {e}");
                     }
                 }
             } else {
@@ -467,7 +1224,7 @@ pub async fn check_shutdown(
                     "[MCRW] [WARNING] Minecraft server crashed or stopped unexpectedly! (Exit Code: {})",
                     code
                 );
-                let funcs: Vec<Function> = {
+                let funcs: Vec<(Function, String)> = {
                     let g = match crash_triggers.lock() {
                         Ok(g) => g,
                         Err(e) => {
@@ -476,18 +1233,32 @@ pub async fn check_shutdown(
                         }
                     };
                     g.iter()
-                        .filter_map(|ct| match lua.registry_value::<Function>(&ct.callback) {
-                            Ok(f) => Some(f),
-                            Err(e) => {
-                                teprintln!("[MCRW] [ERROR] crash registry lookup: {e}");
-                                None
+                        .filter_map(|ct| {
+                            match lua_ctx::plugin_lua(lua, vms, &ct.plugin)
+                                .registry_value::<Function>(&ct.callback)
+                            {
+                                Ok(f) => Some((f, ct.plugin.clone())),
+                                Err(e) => {
+                                    teprintln!("[MCRW] [ERROR] crash registry lookup: {e}");
+                                    None
+                                }
                             }
                         })
                         .collect()
                 };
-                for f in funcs {
-                    if let Err(e) = f.call_async::<()>(()).await {
-                        teprintln!("[MCRW] [ERROR] crash callback failed: {}", e);
+                for (f, plugin) in funcs {
+                    let started = Instant::now();
+                    let result = f.call_async::<()>(()).await;
+                    metrics.record(
+                        &plugin,
+                        Kind::Crash,
+                        "",
+                        started.elapsed(),
+                        result.is_ok(),
+                        0,
+                    );
+                    if let Err(e) = result {
+                        teprintln!("[MCRW] [ERROR] crash callback failed ({plugin}): {e}");
                     }
                 }
             }
@@ -497,3 +1268,134 @@ pub async fn check_shutdown(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn console_builtins_are_parsed() {
+        assert_eq!(
+            parse_console_command("!reload"),
+            Some(Ok(ControlMsg::Reload))
+        );
+        assert_eq!(
+            parse_console_command("!plugin disable  motd"),
+            Some(Ok(ControlMsg::SetPluginEnabled {
+                plugin: "motd".into(),
+                enabled: false,
+            }))
+        );
+        assert_eq!(
+            parse_console_command("!plugin enable motd"),
+            Some(Ok(ControlMsg::SetPluginEnabled {
+                plugin: "motd".into(),
+                enabled: true,
+            }))
+        );
+    }
+
+    #[test]
+    fn single_plugin_builtins_are_parsed() {
+        assert_eq!(
+            parse_console_command("!reload motd"),
+            Some(Ok(ControlMsg::ReloadPlugin("motd".into())))
+        );
+        assert_eq!(
+            parse_console_command("!unload motd"),
+            Some(Ok(ControlMsg::UnloadPlugin("motd".into())))
+        );
+        assert_eq!(
+            parse_console_command("!load motd"),
+            Some(Ok(ControlMsg::LoadPlugin("motd".into())))
+        );
+    }
+
+    #[test]
+    fn parses_plugin_resume() {
+        assert_eq!(
+            parse_console_command("!plugin resume motd"),
+            Some(Ok(ControlMsg::ResumePlugin("motd".into())))
+        );
+        assert!(matches!(
+            parse_console_command("!plugin resume"),
+            Some(Err(_))
+        ));
+    }
+
+    #[test]
+    fn parses_profile_commands() {
+        let profile = |plugin: Option<&str>, reset| {
+            Some(Ok(ControlMsg::Profile {
+                plugin: plugin.map(String::from),
+                reset,
+            }))
+        };
+        assert_eq!(parse_console_command("!profile"), profile(None, false));
+        assert_eq!(
+            parse_console_command("!profile motd"),
+            profile(Some("motd"), false)
+        );
+        assert_eq!(parse_console_command("!profile reset"), profile(None, true));
+        assert_eq!(
+            parse_console_command("!profile reset motd"),
+            profile(Some("motd"), true)
+        );
+        assert_eq!(
+            parse_console_command("!status"),
+            Some(Ok(ControlMsg::Status))
+        );
+    }
+
+    #[test]
+    fn malformed_builtins_report_usage() {
+        assert!(matches!(parse_console_command("!plugin"), Some(Err(_))));
+        assert!(matches!(
+            parse_console_command("!plugin toggle motd"),
+            Some(Err(_))
+        ));
+        assert!(matches!(
+            parse_console_command("!plugin enable a b"),
+            Some(Err(_))
+        ));
+        assert!(matches!(parse_console_command("!reload a b"), Some(Err(_))));
+        assert!(matches!(parse_console_command("!unload"), Some(Err(_))));
+        assert!(matches!(parse_console_command("!load"), Some(Err(_))));
+        assert!(matches!(
+            parse_console_command("!profile a b"),
+            Some(Err(_))
+        ));
+        assert!(matches!(parse_console_command("!status now"), Some(Err(_))));
+    }
+
+    #[tokio::test]
+    async fn server_lines_are_decoded_lossily_and_capped() {
+        let mut input: &[u8] = b"ok\r\nbad \xff byte\n0123456789\n\nabcdefg\xc3\xa9x\nlast";
+        let mut buf = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(l) = read_server_line(&mut input, &mut buf, 8).await.unwrap() {
+            lines.push(l);
+        }
+        let s = |l: &str, skipped| (l.to_string(), skipped);
+        assert_eq!(
+            lines,
+            [
+                s("ok", 0),
+                s("bad \u{fffd} by", 2),
+                s("01234567", 2),
+                s("", 0),
+                // The cut backs off to before the 'é' it would split.
+                s("abcdefg", 3),
+                s("last", 0),
+            ]
+        );
+    }
+
+    // Anything else, including unknown `!` words, goes to the server.
+    #[test]
+    fn other_lines_are_forwarded() {
+        assert_eq!(parse_console_command("say hi"), None);
+        assert_eq!(parse_console_command("!unknown"), None);
+        assert_eq!(parse_console_command(""), None);
+    }
+}
@@ -0,0 +1,204 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Shared Lua libraries in `lua_libs/`.
+//!
+//! `lua_libs/<name>.lua` and `lua_libs/<name>/init.lua` are on `package.path`,
+//! so any plugin can `require("<name>")`. They are never loaded as plugins.
+//! A directory library may carry a `meta.toml` with a `version`, which plugins
+//! can constrain in their own `meta.toml`:
+//!
+//! ```toml
+//! libs = { tellraw = ">=1.2" }
+//! ```
+//!
+//! A full `!reload` drops the loaded libraries so edits are picked up.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use mlua::{Lua, Table, Value};
+use serde::Deserialize;
+
+pub const LIBS_DIR: &str = "lua_libs";
+
+#[derive(Debug, Deserialize)]
+struct LibMeta {
+    version: String,
+}
+
+// Library names in `dir`: `x.lua` files and directories with an `init.lua`.
+pub fn lib_names(dir: &Path) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let path = e.path();
+            if path.is_dir() {
+                path.join("init.lua")
+                    .exists()
+                    .then(|| e.file_name().to_str().map(String::from))?
+            } else {
+                path.extension().filter(|x| *x == "lua")?;
+                path.file_stem()?.to_str().map(String::from)
+            }
+        })
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+// Version of library `name`: `Ok(None)` for a library without a meta.toml
+// (including every single-file library), `Err` if it is not installed.
+fn lib_version(dir: &Path, name: &str) -> Result<Option<String>, String> {
+    let lib_dir = dir.join(name);
+    if !lib_dir.join("init.lua").exists() {
+        return if dir.join(format!("{name}.lua")).exists() {
+            Ok(None)
+        } else {
+            Err(format!(
+                "library '{name}' is not installed in {}/",
+                dir.display()
+            ))
+        };
+    }
+    let text = match fs::read_to_string(lib_dir.join("meta.toml")) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("read {name}/meta.toml: {e}")),
+    };
+    let meta: LibMeta =
+        toml::from_str(&text).map_err(|e| format!("parse {name}/meta.toml: {e}"))?;
+    Ok(Some(meta.version))
+}
+
+/// Check a plugin's `libs` requirements against `dir`. An empty requirement
+/// only asks for the library to be installed.
+pub fn check_lib_requirements(dir: &Path, libs: &BTreeMap<String, String>) -> Result<(), String> {
+    for (name, requirement) in libs {
+        let version = lib_version(dir, name)?;
+        if requirement.trim().is_empty() {
+            continue;
+        }
+        let req = semver::VersionReq::parse(requirement).map_err(|e| {
+            format!("invalid requirement '{requirement}' for library '{name}': {e}")
+        })?;
+        let Some(version) = version else {
+            return Err(format!(
+                "requires library '{name}' {requirement}, but it declares no version"
+            ));
+        };
+        let parsed = semver::Version::parse(&version)
+            .map_err(|e| format!("library '{name}' has invalid version '{version}': {e}"))?;
+        if !req.matches(&parsed) {
+            return Err(format!(
+                "requires library '{name}' {requirement}, installed is {version}"
+            ));
+        }
+    }
+    Ok(())
+}
+
+// Owning library of a `package.loaded` key: `x` and `x.sub` belong to `x`.
+fn lib_owner<'a>(key: &'a str, names: &[String]) -> Option<&'a str> {
+    let head = key.split('.').next()?;
+    names.iter().any(|n| n == head).then_some(head)
+}
+
+/// Remove every loaded library module from `package.loaded`, so the next
+/// `require` re-reads it. Returned for `restore_lib_modules` on rollback.
+pub fn take_lib_modules(lua: &Lua) -> mlua::Result<Vec<(String, Value)>> {
+    let names = lib_names(Path::new(LIBS_DIR));
+    let loaded: Table = lua.globals().get::<Table>("package")?.get("loaded")?;
+    let taken: Vec<(String, Value)> = loaded
+        .pairs::<String, Value>()
+        .filter_map(|p| p.ok())
+        .filter(|(k, _)| lib_owner(k, &names).is_some())
+        .collect();
+    for (k, _) in &taken {
+        loaded.set(k.as_str(), Value::Nil)?;
+    }
+    Ok(taken)
+}
+
+pub fn restore_lib_modules(lua: &Lua, taken: Vec<(String, Value)>) -> mlua::Result<()> {
+    let loaded: Table = lua.globals().get::<Table>("package")?.get("loaded")?;
+    for (k, v) in taken {
+        loaded.set(k, v)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_libs(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcrw_libs_{tag}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("tellraw")).unwrap();
+        fs::write(dir.join("tellraw/init.lua"), "return {}").unwrap();
+        fs::write(dir.join("tellraw/meta.toml"), "version = \"1.3.0\"").unwrap();
+        fs::write(dir.join("strings.lua"), "return {}").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        dir
+    }
+
+    fn reqs(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn lists_file_and_directory_libs() {
+        let dir = temp_libs("names");
+        assert_eq!(lib_names(&dir), ["strings", "tellraw"]);
+        assert!(lib_names(&dir.join("missing")).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn checks_presence_and_versions() {
+        let dir = temp_libs("reqs");
+        assert!(
+            check_lib_requirements(&dir, &reqs(&[("tellraw", ">=1.2"), ("strings", "")])).is_ok()
+        );
+
+        let err = check_lib_requirements(&dir, &reqs(&[("tellraw", ">=2")])).unwrap_err();
+        assert!(err.contains("installed is 1.3.0"), "{err}");
+        let err = check_lib_requirements(&dir, &reqs(&[("strings", "1")])).unwrap_err();
+        assert!(err.contains("declares no version"), "{err}");
+        let err = check_lib_requirements(&dir, &reqs(&[("json", "")])).unwrap_err();
+        assert!(err.contains("not installed"), "{err}");
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn lib_modules_are_matched_by_first_segment() {
+        let names = vec!["tellraw".to_string()];
+        assert_eq!(lib_owner("tellraw", &names), Some("tellraw"));
+        assert_eq!(lib_owner("tellraw.colors", &names), Some("tellraw"));
+        assert_eq!(lib_owner("tellrawx", &names), None);
+        assert_eq!(lib_owner("lua_plugins.tellraw.", &names), None);
+    }
+}
@@ -0,0 +1,524 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Server log lines, split into prefix fields and message.
+//!
+//! The main loop parses every stdout line once into a [`LogRecord`]. The
+//! player registry matches its patterns against the message, and triggers
+//! registered with a table filter (`wrapper:register({level = "WARN", …})`)
+//! receive the record instead of the raw line. The prefix layout depends on
+//! the server flavor, chosen by `[log] format` in mcrw.toml:
+//!
+//! | format    | prefix                                                      |
+//! |-----------|-------------------------------------------------------------|
+//! | `vanilla` | `[12:00:00] [Server thread/INFO]: `                         |
+//! | `paper`   | `[12:00:00 INFO]: `                                         |
+//! | `fabric`  | `[12:00:00] [Server thread/INFO] (Minecraft) `              |
+//! | `forge`   | `[12:00:00] [Server thread/INFO] [minecraft/DedicatedServer]: ` |
+//!
+//! `auto` tries each of them; `prefix_pattern` replaces them with a regex of
+//! the operator's own. Lines without a recognised prefix (stack traces,
+//! startup banners) keep the whole line as the message and no other fields.
+//!
+//! Filter triggers and `wrapper:register_on_exception` see whole records
+//! rather than lines: [`LogAssembler`] folds continuation lines (indented, or
+//! unprefixed after a prefixed line) into the record they follow, so a stack
+//! trace arrives as one record with its [`ExceptionInfo`].
+
+use std::borrow::Cow;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::flavor::Flavor;
+use crate::lua_ctx::LogConfig;
+use crate::teprintln;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Auto,
+    Vanilla,
+    Paper,
+    Fabric,
+    Forge,
+}
+
+const VANILLA: &str =
+    r"^\[(?P<time>\d{2}:\d{2}:\d{2})\] \[(?P<thread>[^\]]+?)/(?P<level>[A-Z]+)\]: ";
+// Paper prints plugin loggers as a leading "[Name] " in the message, which
+// cannot be told apart from chat such as "[Server] hi", so it stays there.
+const PAPER: &str = r"^\[(?P<time>\d{2}:\d{2}:\d{2}) (?P<level>[A-Z]+)\]: ";
+const FABRIC: &str = r"^\[(?P<time>\d{2}:\d{2}:\d{2})\] \[(?P<thread>[^\]]+?)/(?P<level>[A-Z]+)\] \((?P<logger>[^)]+)\) ";
+// The bracket after the level is `[logger/marker]`; the marker is often empty.
+const FORGE: &str =
+    r"^\[(?P<time>[^\]]+)\] \[(?P<thread>[^\]]+?)/(?P<level>[A-Z]+)\] \[(?P<logger>[^\]]+?)/?\]: ";
+
+impl LogFormat {
+    fn patterns(self) -> &'static [&'static str] {
+        match self {
+            // Most specific first; the four never match the same line anyway.
+            LogFormat::Auto => &[FORGE, FABRIC, VANILLA, PAPER],
+            LogFormat::Vanilla => &[VANILLA],
+            LogFormat::Paper => &[PAPER],
+            LogFormat::Fabric => &[FABRIC],
+            LogFormat::Forge => &[FORGE],
+        }
+    }
+}
+
+/// One stdout line, plus any lines [`LogAssembler`] folded into it. Fields
+/// the prefix does not carry are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LogRecord {
+    pub time: Option<String>,
+    pub thread: Option<String>,
+    pub level: Option<String>,
+    pub logger: Option<String>,
+    /// The line after the prefix; the whole line if there is none.
+    pub message: String,
+    pub raw: String,
+    /// The following lines, unparsed.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub continuation: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exception: Option<ExceptionInfo>,
+}
+
+/// A Java exception found in an assembled record.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExceptionInfo {
+    /// Fully qualified, e.g. `java.lang.IllegalStateException`.
+    pub class: String,
+    pub message: Option<String>,
+    /// The exception line and every line after it (frames, `Caused by:`).
+    pub trace: String,
+}
+
+impl LogRecord {
+    fn has_prefix(&self) -> bool {
+        self.message.len() < self.raw.len()
+    }
+}
+
+pub struct LogParser {
+    prefixes: Vec<Regex>,
+}
+
+impl Default for LogParser {
+    fn default() -> Self {
+        Self::new(LogFormat::Auto, None)
+    }
+}
+
+impl LogParser {
+    /// A bad `prefix_pattern` is reported and the `format` presets are used
+    /// instead, like the `[players]` overrides.
+    pub fn new(format: LogFormat, prefix_pattern: Option<&str>) -> Self {
+        if let Some(p) = prefix_pattern {
+            match Regex::new(p) {
+                Ok(r) => return Self { prefixes: vec![r] },
+                Err(e) => teprintln!(
+                    "[MCRW] [ERROR] log.prefix_pattern invalid regex: {e} (using format presets)"
+                ),
+            }
+        }
+        let prefixes = format
+            .patterns()
+            .iter()
+            .map(|p| Regex::new(p).expect("built-in log prefix must compile"))
+            .collect();
+        Self { prefixes }
+    }
+
+    /// `[log] format`, or the flavor's layout while that is `auto`.
+    pub fn from_config(cfg: &LogConfig, flavor: Flavor) -> Self {
+        let format = match cfg.format {
+            LogFormat::Auto => flavor.log_format(),
+            f => f,
+        };
+        Self::new(format, cfg.prefix_pattern.as_deref())
+    }
+
+    pub fn parse(&self, line: &str) -> LogRecord {
+        let field = |c: &regex::Captures, name| c.name(name).map(|m| m.as_str().to_string());
+        for re in &self.prefixes {
+            if let Some(c) = re.captures(line) {
+                return LogRecord {
+                    time: field(&c, "time"),
+                    thread: field(&c, "thread"),
+                    level: field(&c, "level"),
+                    logger: field(&c, "logger"),
+                    message: line[c.get(0).map_or(0, |m| m.end())..].to_string(),
+                    raw: line.to_string(),
+                    ..LogRecord::default()
+                };
+            }
+        }
+        LogRecord {
+            message: line.to_string(),
+            raw: line.to_string(),
+            ..LogRecord::default()
+        }
+    }
+}
+
+/// The prefix conditions of a table trigger. Unset fields match anything;
+/// `level` ignores case.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LogFilter {
+    pub level: Option<String>,
+    pub thread: Option<String>,
+    pub logger: Option<String>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, rec: &LogRecord) -> bool {
+        let same = |want: &Option<String>, got: &Option<String>, fold: bool| match (want, got) {
+            (None, _) => true,
+            (Some(w), Some(g)) if fold => w.eq_ignore_ascii_case(g),
+            (Some(w), Some(g)) => w == g,
+            (Some(_), None) => false,
+        };
+        same(&self.level, &rec.level, true)
+            && same(&self.thread, &rec.thread, false)
+            && same(&self.logger, &rec.logger, false)
+    }
+
+    /// How the filter shows up in `!profile`, next to its message regex.
+    pub fn describe(&self, message: &str) -> String {
+        let mut parts = Vec::new();
+        for (name, v) in [
+            ("level", &self.level),
+            ("thread", &self.thread),
+            ("logger", &self.logger),
+        ] {
+            if let Some(v) = v {
+                parts.push(format!("{name}={v}"));
+            }
+        }
+        if !message.is_empty() {
+            parts.push(format!("message={message}"));
+        }
+        format!("{{{}}}", parts.join(" "))
+    }
+}
+
+// CSI sequences (colors, cursor movement), OSC sequences (window titles,
+// links) and the remaining two-byte escapes.
+static ANSI_ESCAPE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\x1b(?:\[[0-?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\)|[@-_])")
+        .expect("built-in escape pattern must compile")
+});
+
+/// `line` without ANSI escape sequences; borrowed if it has none.
+pub fn strip_ansi(line: &str) -> Cow<'_, str> {
+    ANSI_ESCAPE.replace_all(line, "")
+}
+
+// `java.lang.IllegalStateException: msg`, optionally after the JVM's
+// `Exception in thread "main" `. Only package-qualified names, so chat such
+// as "Error: x" is not taken for one.
+static EXCEPTION_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^(?:Exception in thread "[^"]*" )?((?:[\w$]+\.)+[\w$]*(?:Exception|Error|Throwable))(?::\s*(.*))?$"#)
+        .expect("built-in exception pattern must compile")
+});
+
+/// The first exception line that is followed by at least one `at ...` frame.
+fn find_exception(lines: &[&str]) -> Option<ExceptionInfo> {
+    let is_frame = |l: &&str| l.trim_start().starts_with("at ");
+    lines.iter().enumerate().find_map(|(i, line)| {
+        let c = EXCEPTION_LINE.captures(line.trim())?;
+        lines[i + 1..].iter().any(is_frame).then(|| ExceptionInfo {
+            class: c[1].to_string(),
+            message: c
+                .get(2)
+                .map(|m| m.as_str().to_string())
+                .filter(|m| !m.is_empty()),
+            trace: lines[i..].join("\n"),
+        })
+    })
+}
+
+/// Joins continuation lines onto the record they follow. A line continues the
+/// pending record if it starts with whitespace, or if it has no recognised
+/// prefix while the pending record has one. A record is finished by the next
+/// line that does not continue it, by reaching `max_lines`, or by `max_delay`
+/// passing without another line.
+pub struct LogAssembler {
+    max_lines: usize,
+    max_delay: Duration,
+    pending: Option<LogRecord>,
+    deadline: Option<Instant>,
+}
+
+impl LogAssembler {
+    pub fn new(max_lines: usize, max_delay: Duration) -> Self {
+        Self {
+            max_lines: max_lines.max(1),
+            max_delay,
+            pending: None,
+            deadline: None,
+        }
+    }
+
+    pub fn from_config(cfg: &LogConfig) -> Self {
+        Self::new(cfg.max_lines, Duration::from_millis(cfg.max_delay_ms))
+    }
+
+    /// Take new limits without dropping the pending record.
+    pub fn set_limits(&mut self, cfg: &LogConfig) {
+        self.max_lines = cfg.max_lines.max(1);
+        self.max_delay = Duration::from_millis(cfg.max_delay_ms);
+    }
+
+    /// Add a parsed line; returns the previous record if this line finished it.
+    pub fn push(&mut self, rec: LogRecord, now: Instant) -> Option<LogRecord> {
+        if let Some(head) = &mut self.pending {
+            let continues = rec.raw.starts_with(char::is_whitespace)
+                || (head.has_prefix() && !rec.has_prefix());
+            if continues && head.continuation.len() + 1 < self.max_lines {
+                head.continuation.push(rec.raw);
+                self.deadline = Some(now + self.max_delay);
+                return None;
+            }
+        }
+        let done = self.flush();
+        self.pending = Some(rec);
+        self.deadline = Some(now + self.max_delay);
+        done
+    }
+
+    /// When the pending record is due for [`LogAssembler::flush`].
+    pub fn deadline(&self) -> Option<Instant> {
+        self.deadline
+    }
+
+    /// Finish the pending record, if any.
+    pub fn flush(&mut self) -> Option<LogRecord> {
+        self.deadline = None;
+        let mut rec = self.pending.take()?;
+        if !rec.continuation.is_empty() {
+            let lines: Vec<&str> = std::iter::once(rec.message.as_str())
+                .chain(rec.continuation.iter().map(String::as_str))
+                .collect();
+            rec.exception = find_exception(&lines);
+        }
+        Some(rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rec(
+        time: &str,
+        thread: Option<&str>,
+        level: &str,
+        logger: Option<&str>,
+        message: &str,
+    ) -> LogRecord {
+        LogRecord {
+            time: Some(time.into()),
+            thread: thread.map(Into::into),
+            level: Some(level.into()),
+            logger: logger.map(Into::into),
+            message: message.into(),
+            ..LogRecord::default()
+        }
+    }
+
+    fn parse(p: &LogParser, line: &str) -> LogRecord {
+        LogRecord {
+            raw: String::new(),
+            ..p.parse(line)
+        }
+    }
+
+    #[test]
+    fn auto_recognises_every_flavor() {
+        let p = LogParser::default();
+        assert_eq!(
+            parse(&p, "[12:00:01] [Server thread/INFO]: <alice> hi"),
+            rec(
+                "12:00:01",
+                Some("Server thread"),
+                "INFO",
+                None,
+                "<alice> hi"
+            )
+        );
+        assert_eq!(
+            parse(&p, "[12:00:01 WARN]: Can't keep up!"),
+            rec("12:00:01", None, "WARN", None, "Can't keep up!")
+        );
+        assert_eq!(
+            parse(
+                &p,
+                "[12:00:01] [Server thread/INFO] (Minecraft) Done (3.1s)!"
+            ),
+            rec(
+                "12:00:01",
+                Some("Server thread"),
+                "INFO",
+                Some("Minecraft"),
+                "Done (3.1s)!"
+            )
+        );
+        assert_eq!(
+            parse(
+                &p,
+                "[12:00:01] [Server thread/INFO] [minecraft/DedicatedServer]: Done (3.1s)!"
+            ),
+            rec(
+                "12:00:01",
+                Some("Server thread"),
+                "INFO",
+                Some("minecraft/DedicatedServer"),
+                "Done (3.1s)!"
+            )
+        );
+        assert_eq!(
+            parse(
+                &p,
+                "[12:00:01] [modloading-worker-0/INFO] [ne.mi.co.ForgeMod/]: Loading"
+            ),
+            rec(
+                "12:00:01",
+                Some("modloading-worker-0"),
+                "INFO",
+                Some("ne.mi.co.ForgeMod"),
+                "Loading"
+            )
+        );
+        // No prefix: the whole line is the message.
+        let r = p.parse("\tat java.base/java.lang.Thread.run(Thread.java:833)");
+        assert_eq!((r.level, r.message.as_str()), (None, r.raw.as_str()));
+    }
+
+    #[test]
+    fn fixed_format_and_custom_prefix() {
+        let vanilla = LogParser::new(LogFormat::Vanilla, None);
+        assert_eq!(vanilla.parse("[12:00:01 INFO]: hi").level, None);
+
+        let custom = LogParser::new(LogFormat::Auto, Some(r"^(?P<level>\w+) \| "));
+        let r = custom.parse("ERROR | boom");
+        assert_eq!(
+            (r.level.as_deref(), r.message.as_str()),
+            (Some("ERROR"), "boom")
+        );
+
+        // An invalid override falls back to the presets.
+        let bad = LogParser::new(LogFormat::Paper, Some("("));
+        assert_eq!(bad.parse("[12:00:01 INFO]: hi").message, "hi");
+    }
+
+    #[test]
+    fn filter_matches_prefix_fields() {
+        let r = rec("12:00:01", Some("Server thread"), "WARN", None, "x");
+        let f = |level: Option<&str>, thread: Option<&str>, logger: Option<&str>| LogFilter {
+            level: level.map(Into::into),
+            thread: thread.map(Into::into),
+            logger: logger.map(Into::into),
+        };
+        assert!(f(None, None, None).matches(&r));
+        assert!(f(Some("warn"), Some("Server thread"), None).matches(&r));
+        assert!(!f(Some("INFO"), None, None).matches(&r));
+        assert!(!f(None, Some("server thread"), None).matches(&r));
+        // A condition on a field the line lacks never matches.
+        assert!(!f(None, None, Some("Minecraft")).matches(&r));
+        assert_eq!(
+            f(Some("WARN"), None, None).describe("^<(\\w+)>"),
+            "{level=WARN message=^<(\\w+)>}"
+        );
+    }
+
+    #[test]
+    fn strips_ansi_escapes() {
+        assert_eq!(
+            strip_ansi("\x1b[0;36m[12:00:01 INFO]: \x1b[0m<alice> hi\x1b]0;title\x07!"),
+            "[12:00:01 INFO]: <alice> hi!"
+        );
+        assert!(matches!(strip_ansi("plain"), Cow::Borrowed("plain")));
+    }
+
+    #[test]
+    fn assembler_folds_stack_traces() {
+        let p = LogParser::default();
+        let mut a = LogAssembler::new(200, Duration::from_millis(100));
+        let t0 = Instant::now();
+        let mut push = |line: &str| a.push(p.parse(line), t0);
+        assert_eq!(push("[12:00:01] [Server thread/INFO]: Starting"), None);
+        let first =
+            push("[12:00:02] [Server thread/ERROR]: Encountered an unexpected exception").unwrap();
+        assert_eq!(
+            (first.message.as_str(), first.continuation.len()),
+            ("Starting", 0)
+        );
+        for line in [
+            "java.lang.IllegalStateException: boom",
+            "\tat com.example.Foo.bar(Foo.java:10)",
+            "Caused by: java.io.IOException",
+            "\t... 3 more",
+        ] {
+            assert_eq!(push(line), None);
+        }
+        let rec = push("[12:00:03] [Server thread/INFO]: Done").unwrap();
+        assert_eq!(rec.level.as_deref(), Some("ERROR"));
+        assert_eq!(rec.continuation.len(), 4);
+        let e = rec.exception.unwrap();
+        assert_eq!(
+            (e.class.as_str(), e.message.as_deref()),
+            ("java.lang.IllegalStateException", Some("boom"))
+        );
+        assert!(
+            e.trace
+                .starts_with("java.lang.IllegalStateException: boom\n\tat com.example")
+        );
+        assert!(e.trace.ends_with("\t... 3 more"));
+
+        // The pending "Done" is left for the deadline; no exception without frames.
+        let done = a.flush().unwrap();
+        assert_eq!((done.message.as_str(), done.exception), ("Done", None));
+        assert_eq!(a.deadline(), None);
+    }
+
+    #[test]
+    fn assembler_limits_and_unprefixed_heads() {
+        let p = LogParser::default();
+        let t0 = Instant::now();
+        let mut a = LogAssembler::new(2, Duration::from_millis(100));
+        assert_eq!(a.push(p.parse("[12:00:01 WARN]: a"), t0), None);
+        assert_eq!(a.push(p.parse("  b"), t0), None);
+        assert_eq!(a.deadline(), Some(t0 + Duration::from_millis(100)));
+        // The record is full, so "  c" starts the next one.
+        assert_eq!(a.push(p.parse("  c"), t0).unwrap().continuation, ["  b"]);
+
+        // An unprefixed head only collects indented lines.
+        let mut a = LogAssembler::new(200, Duration::ZERO);
+        a.push(p.parse(r#"Exception in thread "main" java.lang.Error"#), t0);
+        assert_eq!(a.push(p.parse("\tat Main.main(Main.java:1)"), t0), None);
+        let rec = a.push(p.parse("Starting minecraft server"), t0).unwrap();
+        let e = rec.exception.unwrap();
+        assert_eq!((e.class.as_str(), e.message), ("java.lang.Error", None));
+        assert_eq!(a.flush().unwrap().continuation.len(), 0);
+    }
+}
@@ -15,7 +15,7 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
     fs,
     path::Path,
     process::Stdio,
@@ -28,7 +28,8 @@ use std::{
 
 use mlua::LuaSerdeExt;
 use mlua::{
-    Function, Lua, RegistryKey, Table, UserData, UserDataFields, UserDataMethods, Value,
+    Function, IntoLuaMulti, Lua, RegistryKey, SerializeOptions, Table, UserData, UserDataFields,
+    UserDataMethods, UserDataRef, Value, Variadic,
 };
 use regex::Regex;
 use serde::{Deserialize, Serialize};
@@ -36,22 +37,58 @@ use serde_json::Value as JsonValue;
 use tokio::process::Child;
 use tokio::sync::mpsc;
 
+use crate::api_spec::{PLAYER, Registrar, SERVER, WRAPPER};
+use crate::flavor::Flavor;
+use crate::handler::Dispatch;
+use crate::libs::{LIBS_DIR, check_lib_requirements, restore_lib_modules, take_lib_modules};
+use crate::log_line::{LogFilter, LogFormat};
+use crate::metrics::{Kind, MetricsRegistry};
+use crate::permissions::{PLUGIN_ENVS, PLUGIN_MODULES, Permissions, install_plugin_searcher};
 use crate::players::PlayerRegistry;
+use crate::plugin_config::{ConfigWrite, LoadedConfig, load_plugin_config};
+use crate::plugin_state::{PLUGIN_STATE_PATH, PluginState, load_plugin_state};
 use crate::rcon::RconHandle;
+use crate::server_info::SharedServerInfo;
 use crate::store::{StoreHandle, StoreRegistry};
+use crate::timers::{Timer, TimerHandle, TimerList};
+use crate::waiters::{self, LineMatch, LineWaiters, QueryLocks};
 use crate::{teprintln, tprintln};
 
+// Every registration records the directory name of the plugin that made it,
+// so `!unload` / `!reload <plugin>` can remove exactly that plugin's entries.
 pub struct Trigger {
     pub regex: Regex,
+    // Set for `wrapper:register{...}`: the prefix fields must match, `regex`
+    // applies to the message only, and the callback gets the LogRecord.
+    pub filter: Option<LogFilter>,
     pub callback: RegistryKey,
+    pub plugin: String,
+}
+
+impl Trigger {
+    // Metrics key.
+    pub fn key(&self) -> String {
+        match &self.filter {
+            Some(f) => f.describe(self.regex.as_str()),
+            None => self.regex.to_string(),
+        }
+    }
 }
 
 pub struct StopTrigger {
     pub callback: RegistryKey,
+    pub plugin: String,
 }
 
 pub struct CrashTrigger {
     pub callback: RegistryKey,
+    pub plugin: String,
+}
+
+// A bare callback tagged with its owner (join/leave and lifecycle callbacks).
+pub struct PluginCallback {
+    pub callback: RegistryKey,
+    pub plugin: String,
 }
 
 pub struct CronJob {
@@ -72,9 +109,25 @@ pub type TriggerList = Arc<Mutex<Vec<Trigger>>>;
 pub type StopTriggerList = Arc<Mutex<Vec<StopTrigger>>>;
 pub type CrashTriggerList = Arc<Mutex<Vec<CrashTrigger>>>;
 pub type CronJobList = Arc<Mutex<Vec<CronJob>>>;
-// register_on_join / register_on_leave callbacks (plain RegistryKeys, fired by
-// the dispatch loop with a PlayerHandle argument).
-pub type PlayerCallbackList = Arc<Mutex<Vec<RegistryKey>>>;
+// register_on_join / register_on_leave callbacks (fired by the dispatch loop
+// with a PlayerHandle argument).
+pub type PlayerCallbackList = Arc<Mutex<Vec<PluginCallback>>>;
+// register_on_unload / register_on_load_complete callbacks (fired by the
+// control arm around reloads, and by main on shutdown).
+pub type HookList = Arc<Mutex<Vec<PluginCallback>>>;
+// Lua states of plugins loaded with `isolation = "isolated"`, keyed by
+// directory name. Every other plugin runs in the shared state, so a callback's
+// registry key is resolved against `plugin_lua(lua, vms, &cb.plugin)`.
+pub type PluginVms = Arc<Mutex<HashMap<String, Lua>>>;
+
+// The Lua state that owns `plugin`'s registry values and modules.
+pub fn plugin_lua(lua: &Lua, vms: &PluginVms, plugin: &str) -> Lua {
+    vms.lock()
+        .unwrap()
+        .get(plugin)
+        .cloned()
+        .unwrap_or_else(|| lua.clone())
+}
 
 // A per-player handle handed to Lua by `wrapper:players()` / `wrapper:player()`
 // and to join/leave callbacks. Static fields read the current cached record;
@@ -93,37 +146,40 @@ impl PlayerHandle {
 
 impl UserData for PlayerHandle {
     fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
-        fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
-        fields.add_field_method_get("uuid", |_, this| {
+        let mut fields = Registrar::fields(&PLAYER, fields);
+        fields.field_get("name", |_, this| Ok(this.name.clone()));
+        fields.field_get("uuid", |_, this| {
             Ok(this.registry.snapshot(&this.name).and_then(|r| r.uuid))
         });
-        fields.add_field_method_get("ip", |_, this| {
+        fields.field_get("ip", |_, this| {
             Ok(this.registry.snapshot(&this.name).and_then(|r| r.ip))
         });
-        fields.add_field_method_get("online", |_, this| {
+        fields.field_get("online", |_, this| {
             Ok(this
                 .registry
                 .snapshot(&this.name)
                 .map(|r| r.online)
                 .unwrap_or(false))
         });
-        fields.add_field_method_get("first_join", |_, this| {
+        fields.field_get("first_join", |_, this| {
             Ok(this.registry.snapshot(&this.name).and_then(|r| r.first_join))
         });
-        fields.add_field_method_get("last_seen", |_, this| {
+        fields.field_get("last_seen", |_, this| {
             Ok(this
                 .registry
                 .snapshot(&this.name)
                 .map(|r| r.last_seen)
                 .unwrap_or(0))
         });
-        fields.add_field_method_get("join_time", |_, this| {
+        fields.field_get("join_time", |_, this| {
             Ok(this.registry.snapshot(&this.name).and_then(|r| r.join_time))
         });
+        fields.finish();
     }
 
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_async_method("pos", |lua, this, ()| {
+        let mut methods = Registrar::methods(&PLAYER, methods);
+        methods.async_method("pos", |lua, this, ()| {
             let reg = this.registry.clone();
             let name = this.name.clone();
             async move {
@@ -139,11 +195,12 @@ impl UserData for PlayerHandle {
                 }
             }
         });
-        methods.add_async_method("dimension", |_lua, this, ()| {
+        methods.async_method("dimension", |_lua, this, ()| {
             let reg = this.registry.clone();
             let name = this.name.clone();
             async move { Ok(reg.query_dimension(&name).await) }
         });
+        methods.finish();
     }
 }
 
@@ -159,14 +216,88 @@ pub struct PluginMeta {
     pub dependencies: Vec<String>,
     #[serde(default)]
     pub mcrw_version: String,
+    // Capabilities the plugin needs: "http", "python", "rcon", "command", "fs",
+    // "metrics", "store:shared:<name>". Only enforced with [permissions] enforce.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub isolation: Isolation,
+    // lua_libs/ libraries this plugin uses, name → SemVer requirement ("" =
+    // any version). Checked before the plugin loads.
+    #[serde(default)]
+    pub libs: BTreeMap<String, String>,
+}
+
+// Which Lua state a plugin runs in. `Isolated` gives it its own globals,
+// `package.loaded` and garbage collector; registrations still go to the
+// shared lists and are dispatched to that state.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Isolation {
+    #[default]
+    Shared,
+    Isolated,
+}
+
+// Check a plugin's `mcrw_version` requirement (a semver requirement such as
+// ">=0.2.0" or "0.3") against this wrapper's version. Empty means "any".
+pub fn check_mcrw_version(requirement: &str) -> Result<(), String> {
+    if requirement.trim().is_empty() {
+        return Ok(());
+    }
+    let req = semver::VersionReq::parse(requirement)
+        .map_err(|e| format!("invalid mcrw_version '{requirement}': {e}"))?;
+    let current = semver::Version::parse(env!("CARGO_PKG_VERSION"))
+        .expect("[MCRW] [PANIC] crate version is not semver");
+    if req.matches(&current) {
+        Ok(())
+    } else {
+        Err(format!(
+            "requires mcrw_version '{requirement}', this wrapper is {current}"
+        ))
+    }
 }
 
 // key: directory name
 pub type PluginRegistry = Arc<Mutex<HashMap<String, PluginMeta>>>;
+// Service tables published via `wrapper:export`, keyed by the exporting
+// plugin's directory name. Cleared on reload along with every registration.
+pub type PluginExports = Arc<Mutex<HashMap<String, RegistryKey>>>;
+
+// Per-plugin `config.json` state, keyed by directory name: what load_config
+// was called with (to redo the merge when the file changes) and the
+// register_on_config_change callbacks to notify.
+#[derive(Default)]
+pub struct PluginConfigState {
+    pub loaded: Option<LoadedConfig>,
+    pub callbacks: Vec<RegistryKey>,
+}
+pub type PluginConfigs = Arc<Mutex<HashMap<String, PluginConfigState>>>;
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ControlMsg {
     Reload,
+    // `!plugin enable|disable <dir>`: persist to .mcrw/plugins.toml, then reload.
+    SetPluginEnabled { plugin: String, enabled: bool },
+    // `!reload <plugin>` / `!unload <plugin>` / `!load <dir>`: one plugin only.
+    ReloadPlugin(String),
+    UnloadPlugin(String),
+    LoadPlugin(String),
+    // `[dev] watch`: files under lua_plugins/<dir>/ changed. Reloads the
+    // plugin if loaded, loads it otherwise.
+    PluginChanged(String),
+    // lua_plugins/<dir>/config.json changed: re-merge, notify the plugin.
+    ConfigChanged(String),
+    // mcrw.toml changed: apply the settings that are safe to change live.
+    McrwConfigChanged,
+    // `!profile [reset] [plugin]`: print or clear callback metrics.
+    Profile { plugin: Option<String>, reset: bool },
+    // A dispatch task tripped the circuit breaker (see breaker.rs).
+    Quarantined { plugin: String, reason: String },
+    // `!plugin resume <dir>`: lift a quarantine.
+    ResumePlugin(String),
+    // `!status`: print what is known about the server (server_info.rs).
+    Status,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -193,11 +324,19 @@ pub struct CompiledPattern {
 
 pub struct LifecycleEventState {
     pub patterns: Vec<CompiledPattern>,
-    pub callbacks: Vec<RegistryKey>,
+    pub callbacks: Vec<PluginCallback>,
 }
 
 pub type LifecycleEvents = Arc<Mutex<HashMap<String, LifecycleEventState>>>;
 
+// Lifecycle event with no stdout patterns, fired by the wrapper itself when a
+// plugin is quarantined; subscribed to via wrapper:register_on_quarantine.
+pub const QUARANTINE_EVENT: &str = "plugin_quarantined";
+
+// Lifecycle event fed from assembled log records that carry a Java exception;
+// subscribed to via wrapper:register_on_exception.
+pub const EXCEPTION_EVENT: &str = "exception";
+
 // ---------------------------------------------------------------------------
 // mcrw.toml — wrapper-level config (sibling to server.jar / trigger_config.toml)
 // ---------------------------------------------------------------------------
@@ -320,20 +459,135 @@ impl Default for RconConfig {
 
 // The wrapped server process. Only the Java executable is configurable; the
 // args following it on the command line are still passed through verbatim.
+// `flavor` picks the bundled log, player and start patterns (see flavor.rs).
 #[derive(Debug, Clone, Deserialize)]
 pub struct ServerConfig {
     #[serde(default = "default_java")]
     pub java: String,
+    #[serde(default)]
+    pub flavor: Flavor,
 }
 fn default_java() -> String {
     "java".into()
 }
 impl Default for ServerConfig {
     fn default() -> Self {
-        Self { java: default_java() }
+        Self {
+            java: default_java(),
+            flavor: Flavor::default(),
+        }
+    }
+}
+
+// Development conveniences, all off by default. `watch` hot-reloads plugins
+// when their files change (see watch.rs).
+#[derive(Debug, Clone, Deserialize)]
+pub struct DevConfig {
+    #[serde(default)]
+    pub watch: bool,
+    #[serde(default = "default_watch_debounce_ms")]
+    pub debounce_ms: u64,
+}
+fn default_watch_debounce_ms() -> u64 {
+    300
+}
+impl Default for DevConfig {
+    fn default() -> Self {
+        Self {
+            watch: false,
+            debounce_ms: default_watch_debounce_ms(),
+        }
+    }
+}
+
+// Full `!reload` commits only if every plugin that was running initializes
+// again. `critical` narrows that to the named plugin directories; empty means
+// every plugin.
+// `unload_timeout_ms` bounds each register_on_unload callback.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReloadConfig {
+    #[serde(default)]
+    pub critical: Vec<String>,
+    #[serde(default = "default_unload_timeout_ms")]
+    pub unload_timeout_ms: u64,
+}
+fn default_unload_timeout_ms() -> u64 {
+    2000
+}
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            critical: Vec::new(),
+            unload_timeout_ms: default_unload_timeout_ms(),
+        }
+    }
+}
+
+// How stdout lines are split into prefix fields and message, and joined
+// into records (see log_line.rs). `prefix_pattern`, a regex with optional
+// named groups time/thread/level/logger, replaces the `format` presets.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct LogConfig {
+    #[serde(default)]
+    pub format: LogFormat,
+    #[serde(default)]
+    pub prefix_pattern: Option<String>,
+    #[serde(default = "default_log_max_lines")]
+    pub max_lines: usize,
+    #[serde(default = "default_log_max_delay_ms")]
+    pub max_delay_ms: u64,
+}
+fn default_log_max_lines() -> usize {
+    200
+}
+fn default_log_max_delay_ms() -> u64 {
+    100
+}
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            prefix_pattern: None,
+            max_lines: default_log_max_lines(),
+            max_delay_ms: default_log_max_delay_ms(),
+        }
+    }
+}
+
+// Circuit breaker limits (see breaker.rs); 0 turns a limit off.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct QuarantineConfig {
+    #[serde(default = "default_consecutive_failures")]
+    pub consecutive_failures: u32,
+    #[serde(default = "default_failures_per_minute")]
+    pub failures_per_minute: u32,
+}
+fn default_consecutive_failures() -> u32 {
+    10
+}
+fn default_failures_per_minute() -> u32 {
+    30
+}
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        Self {
+            consecutive_failures: default_consecutive_failures(),
+            failures_per_minute: default_failures_per_minute(),
+        }
     }
 }
 
+// Operator approval of plugin permissions (see permissions.rs). With
+// `enforce`, a plugin gets the capabilities its meta.toml declares that are
+// also listed under `grants.<dir>`; off, every plugin keeps the full API.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PermissionsConfig {
+    #[serde(default)]
+    pub enforce: bool,
+    #[serde(default)]
+    pub grants: HashMap<String, Vec<String>>,
+}
+
 #[derive(Debug, Clone, Default, Deserialize)]
 pub struct McrwConfig {
     #[serde(default)]
@@ -346,6 +600,16 @@ pub struct McrwConfig {
     pub players: PlayersConfig,
     #[serde(default)]
     pub rcon: RconConfig,
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    #[serde(default)]
+    pub quarantine: QuarantineConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub dev: DevConfig,
+    #[serde(default)]
+    pub permissions: PermissionsConfig,
 }
 
 // Default mcrw.toml written on first run (when none exists), so users get a
@@ -358,6 +622,7 @@ const DEFAULT_MCRW_TOML: &str = r#"# mcrw.toml — MCRW wrapper configuration.
 
 [server]
 java = "java"                    # Path or PATH-lookup name for the Java executable
+flavor = "auto"                  # Server software: auto, vanilla, paper, fabric, forge or velocity
 
 [python]
 interpreter        = "python3"   # Path or PATH-lookup name for the Python interpreter
@@ -369,8 +634,9 @@ default_timeout_ms = 30000       # Default per-request timeout for wrapper:http_
 [players]
 enabled        = true            # Master switch for the player registry
 pos_timeout_ms = 3000            # Stdio-fallback timeout for p:pos()/p:dimension()
-# Optional Rust-regex overrides for non-vanilla server flavors. Omit to use the
-# built-in vanilla defaults. Capture groups must match the documented order.
+# Optional Rust-regex overrides, matched against the log message (the line
+# after its [log] prefix). Omit to use the built-in defaults. Capture groups
+# must match the documented order.
 # join_pattern  = '...'          # captures: name
 # leave_pattern = '...'          # captures: name
 # login_pattern = '...'          # captures: name, ip
@@ -378,6 +644,12 @@ pos_timeout_ms = 3000            # Stdio-fallback timeout for p:pos()/p:dimensio
 # pos_pattern   = '...'          # captures: name, x, y, z
 # dim_pattern   = '...'          # captures: name, dim
 
+[log]
+format = "auto"                  # Log prefix layout: auto, vanilla, paper, fabric or forge
+# prefix_pattern = '...'         # Rust regex for another layout; named groups time, thread, level, logger
+max_lines = 200                  # Most lines (stack trace frames etc.) joined into one record
+max_delay_ms = 100               # How long a record waits for another continuation line
+
 [rcon]
 # RCON is auto-detected from server.properties; this section only overrides it.
 # enabled  = true                # Omit to auto-detect from server.properties' enable-rcon
@@ -385,6 +657,23 @@ pos_timeout_ms = 3000            # Stdio-fallback timeout for p:pos()/p:dimensio
 # port     = 25575               # Omit to use server.properties' rcon.port
 # password = "..."               # Omit to use server.properties' rcon.password
 timeout_ms = 5000                # Per-call timeout for wrapper:rcon_command
+
+[reload]
+critical = []                    # Running plugin dirs that must reload for !reload to commit; [] = all
+unload_timeout_ms = 2000         # Per-callback limit for wrapper:register_on_unload hooks
+
+[quarantine]
+consecutive_failures = 10        # Suspend a plugin after this many failed callbacks in a row; 0 = off
+failures_per_minute  = 30        # ... or after this many failures within a minute; 0 = off
+
+[dev]
+watch       = false              # Reload plugins automatically when their files change
+debounce_ms = 300                # Quiet period after the last change before reloading
+
+[permissions]
+enforce = false                  # Limit plugins to the meta.toml permissions granted below
+# [permissions.grants]           # Per plugin directory, e.g.:
+# shop = ["http", "store:shared:economy"]
 "#;
 
 pub fn load_mcrw_config(path: &Path) -> Arc<McrwConfig> {
@@ -403,43 +692,159 @@ pub fn load_mcrw_config(path: &Path) -> Arc<McrwConfig> {
         }
         return Arc::new(McrwConfig::default());
     }
-    match fs::read_to_string(path) {
-        Ok(s) => match toml::from_str::<McrwConfig>(&s) {
-            Ok(cfg) => {
-                tprintln!("[MCRW] Loaded mcrw.toml");
-                Arc::new(cfg)
+    match read_mcrw_config(path) {
+        Ok(cfg) => {
+            tprintln!("[MCRW] Loaded mcrw.toml");
+            Arc::new(cfg)
+        }
+        Err(e) => {
+            teprintln!("[MCRW] [ERROR] {e} (using defaults)");
+            Arc::new(McrwConfig::default())
+        }
+    }
+}
+
+fn read_mcrw_config(path: &Path) -> Result<McrwConfig, String> {
+    let s = fs::read_to_string(path).map_err(|e| format!("read mcrw.toml: {e}"))?;
+    toml::from_str(&s).map_err(|e| format!("parse mcrw.toml: {e}"))
+}
+
+// The mcrw.toml in effect. An edit swaps in a new Arc (reload_mcrw_config);
+// readers clone the inner Arc and use that snapshot for the whole call.
+pub type SharedMcrwConfig = Arc<Mutex<Arc<McrwConfig>>>;
+
+// Result of merging an edited mcrw.toml into the running config.
+pub struct HotSettings {
+    pub config: McrwConfig,
+    // Changed settings that were applied.
+    pub applied: Vec<&'static str>,
+    // Changed settings that keep their startup value until a restart.
+    pub restart: Vec<&'static str>,
+}
+
+// Only timeouts, player log patterns and the [reload], [quarantine] and [log]
+// sections change live: everything else is wired up once at startup (the java
+// command, the RCON connection, the watcher, the player registry switch). The
+// [permissions] section is cold too, since loaded plugins and their module
+// environments keep the grants they were loaded with.
+pub fn merge_hot_settings(current: &McrwConfig, new: &McrwConfig) -> HotSettings {
+    let mut config = current.clone();
+    let mut applied = Vec::new();
+    let mut restart = Vec::new();
+    macro_rules! hot {
+        ($($section:ident . $field:ident),* $(,)?) => {$(
+            if new.$section.$field != current.$section.$field {
+                config.$section.$field = new.$section.$field.clone();
+                applied.push(concat!(stringify!($section), ".", stringify!($field)));
             }
-            Err(e) => {
-                teprintln!("[MCRW] [ERROR] parse mcrw.toml: {} (using defaults)", e);
-                Arc::new(McrwConfig::default())
+        )*};
+    }
+    macro_rules! cold {
+        ($($section:ident . $field:ident),* $(,)?) => {$(
+            if new.$section.$field != current.$section.$field {
+                restart.push(concat!(stringify!($section), ".", stringify!($field)));
             }
-        },
+        )*};
+    }
+    hot!(
+        python.default_timeout_ms,
+        http.default_timeout_ms,
+        rcon.timeout_ms,
+        players.pos_timeout_ms,
+        players.join_pattern,
+        players.leave_pattern,
+        players.login_pattern,
+        players.uuid_pattern,
+        players.pos_pattern,
+        players.dim_pattern,
+        reload.critical,
+        reload.unload_timeout_ms,
+        quarantine.consecutive_failures,
+        quarantine.failures_per_minute,
+        log.format,
+        log.prefix_pattern,
+        log.max_lines,
+        log.max_delay_ms,
+    );
+    cold!(
+        server.java,
+        server.flavor,
+        python.interpreter,
+        players.enabled,
+        rcon.enabled,
+        rcon.host,
+        rcon.port,
+        rcon.password,
+        dev.watch,
+        dev.debounce_ms,
+        permissions.enforce,
+        permissions.grants,
+    );
+    HotSettings {
+        config,
+        applied,
+        restart,
+    }
+}
+
+// mcrw.toml changed on disk: apply the live subset. A file that no longer
+// parses leaves every setting as it was.
+pub fn reload_mcrw_config(
+    path: &Path,
+    shared: &SharedMcrwConfig,
+    player_registry: &PlayerRegistry,
+) {
+    let new = match read_mcrw_config(path) {
+        Ok(c) => c,
         Err(e) => {
-            teprintln!("[MCRW] [ERROR] read mcrw.toml: {e} (using defaults)");
-            Arc::new(McrwConfig::default())
+            teprintln!("[MCRW] [ERROR] {e}; keeping the current settings");
+            return;
         }
+    };
+    let current = shared.lock().unwrap().clone();
+    let hot = merge_hot_settings(&current, &new);
+    if !hot.restart.is_empty() {
+        teprintln!(
+            "[MCRW] [WARNING] mcrw.toml: {} only take effect after a restart",
+            hot.restart.join(", ")
+        );
     }
+    if hot.applied.is_empty() {
+        return;
+    }
+    player_registry.apply_config(&hot.config.players);
+    *shared.lock().unwrap() = Arc::new(hot.config);
+    tprintln!(
+        "[MCRW] Applied mcrw.toml changes: {}",
+        hot.applied.join(", ")
+    );
 }
 
 // ---------------------------------------------------------------------------
 // Child process tracker — one per ServerApi, shared across PluginApi clones.
 // `!reload` drains and start_kill()s every entry so no in-flight python script
-// outlives the Lua state it was spawned from.
+// outlives the Lua state it was spawned from; `!reload <plugin>` / `!unload`
+// only kill the children owned by that plugin.
 // ---------------------------------------------------------------------------
 
-pub type ChildTracker = Arc<Mutex<HashMap<u64, Child>>>;
+pub struct TrackedChild {
+    pub child: Child,
+    pub plugin: String,
+}
+
+pub type ChildTracker = Arc<Mutex<HashMap<u64, TrackedChild>>>;
 pub type ChildIdCounter = Arc<AtomicU64>;
 
 // ---------------------------------------------------------------------------
 // trigger_config.toml (unchanged)
 // ---------------------------------------------------------------------------
 
-fn builtin_trigger_config() -> TriggerConfig {
+fn builtin_trigger_config(flavor: Flavor) -> TriggerConfig {
     let mut events = HashMap::new();
     events.insert(
         "start".to_string(),
         vec![PatternSpec {
-            text: r#"Done \([0-9.]+s\)! For help"#.to_string(),
+            text: flavor.start_pattern().to_string(),
             once: true,
         }],
     );
@@ -453,8 +858,9 @@ const DEFAULT_TRIGGER_CONFIG_TOML: &str = r#"# trigger_config.toml — lifecycle
 #
 # Each key is an event name; its value is a list of stdout regex patterns (Rust
 # regex syntax) that fire the event. The wrapper ships a built-in "start" pattern
-# matching the vanilla "Done (..s)! For help" line, so this file is OPTIONAL —
-# define an event here only to OVERRIDE a built-in or ADD a new one.
+# matching the "Done (..s)!" line of the [server] flavor in mcrw.toml, so this
+# file is OPTIONAL — define an event here only to OVERRIDE a built-in or ADD a
+# new one.
 #
 # `once = true` (the default) fires the event at most once per server run.
 #
@@ -469,8 +875,8 @@ const DEFAULT_TRIGGER_CONFIG_TOML: &str = r#"# trigger_config.toml — lifecycle
 # once = false
 "#;
 
-pub fn load_trigger_config(path: &Path) -> TriggerConfig {
-    let mut cfg = builtin_trigger_config();
+pub fn load_trigger_config(path: &Path, flavor: Flavor) -> TriggerConfig {
+    let mut cfg = builtin_trigger_config(flavor);
     if !path.exists() {
         // First run: drop a documented template (all comments → no behavior
         // change; the built-in patterns below still apply).
@@ -561,6 +967,32 @@ fn read_op_names() -> Vec<String> {
     }
 }
 
+// `wrapper:register{level=..., thread=..., logger=..., message=...}`: the
+// message regex (default: any) plus the prefix conditions.
+fn parse_log_filter(t: &Table) -> mlua::Result<(String, Option<LogFilter>)> {
+    for pair in t.pairs::<String, Value>() {
+        let (k, _) = pair?;
+        if !matches!(k.as_str(), "level" | "thread" | "logger" | "message") {
+            return Err(mlua::Error::external(format!(
+                "wrapper:register: unknown filter key '{k}' (expected level, thread, logger, message)"
+            )));
+        }
+    }
+    let filter = LogFilter {
+        level: t.get("level")?,
+        thread: t.get("thread")?,
+        logger: t.get("logger")?,
+    };
+    Ok((
+        t.get::<Option<String>>("message")?.unwrap_or_default(),
+        Some(filter),
+    ))
+}
+
+// wrapper:wait_for and wrapper:query give up after this long unless
+// opts.timeout_ms says otherwise.
+const RESPONSE_TIMEOUT_MS: u64 = 5000;
+
 // ---------------------------------------------------------------------------
 // PluginApi — exposed to Lua as the per-plugin `wrapper` handle.
 // ---------------------------------------------------------------------------
@@ -573,35 +1005,59 @@ pub struct PluginApi {
     stop_triggers: StopTriggerList,
     crash_triggers: CrashTriggerList,
     lifecycle_events: LifecycleEvents,
-    mcrw_config: Arc<McrwConfig>,
+    mcrw_config: SharedMcrwConfig,
     children: ChildTracker,
     next_child_id: ChildIdCounter,
     cmd_tx: mpsc::Sender<String>,
     cron_jobs: CronJobList,
+    timers: TimerList,
+    line_waiters: LineWaiters,
+    query_locks: Arc<QueryLocks>,
+    server_info: SharedServerInfo,
     http_client: reqwest::Client,
     player_registry: Arc<PlayerRegistry>,
     join_triggers: PlayerCallbackList,
     leave_triggers: PlayerCallbackList,
+    unload_hooks: HookList,
+    load_complete_hooks: HookList,
     rcon: Option<RconHandle>,
     store: Arc<StoreRegistry>,
+    metrics: Arc<MetricsRegistry>,
+    plugins: PluginRegistry,
+    exports: PluginExports,
+    configs: PluginConfigs,
+    permissions: Permissions,
 }
 
 impl UserData for PluginApi {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_method(
+        let mut methods = Registrar::methods(&WRAPPER, methods);
+        methods.method(
             "register",
-            |lua: &Lua, this: &Self, (pattern, func): (String, Function)| {
+            |lua: &Lua, this: &Self, (pattern, func): (Value, Function)| {
+                let (pattern, filter) = match pattern {
+                    Value::String(s) => (s.to_str()?.to_string(), None),
+                    Value::Table(t) => parse_log_filter(&t)?,
+                    other => {
+                        return Err(mlua::Error::external(format!(
+                            "wrapper:register: expected a pattern string or filter table, got {}",
+                            other.type_name()
+                        )));
+                    }
+                };
                 let regex = Regex::new(&pattern).map_err(mlua::Error::external)?;
                 let callback = lua.create_registry_value(func)?;
-                this.triggers
-                    .lock()
-                    .unwrap()
-                    .push(Trigger { regex, callback });
+                this.triggers.lock().unwrap().push(Trigger {
+                    regex,
+                    filter,
+                    callback,
+                    plugin: this.dirname.clone(),
+                });
                 Ok(())
             },
         );
 
-        methods.add_method(
+        methods.method(
             "register_cron",
             |lua: &Lua, this: &Self, (expr, func): (String, Function)| {
                 let schedule = expr.parse::<cron::Schedule>().map_err(|e| {
@@ -627,51 +1083,176 @@ impl UserData for PluginApi {
             },
         );
 
-        methods.add_method(
+        // Relative timers (timers.rs). The callback runs like a cron job and
+        // may return commands.
+        methods.method(
+            "after",
+            |lua: &Lua, this: &Self, (ms, func): (u64, Function)| {
+                let callback = lua.create_registry_value(func)?;
+                let id = this
+                    .timers
+                    .add(&this.dirname, callback, Duration::from_millis(ms), None);
+                Ok(TimerHandle::new(this.timers.clone(), id))
+            },
+        );
+
+        methods.method(
+            "every",
+            |lua: &Lua, this: &Self, (ms, func): (u64, Function)| {
+                if ms == 0 {
+                    return Err(mlua::Error::external(
+                        "wrapper:every: interval must be at least 1 ms",
+                    ));
+                }
+                let period = Duration::from_millis(ms);
+                let callback = lua.create_registry_value(func)?;
+                let id = this
+                    .timers
+                    .add(&this.dirname, callback, period, Some(period));
+                Ok(TimerHandle::new(this.timers.clone(), id))
+            },
+        );
+
+        methods.async_method("sleep", |_lua, _this, ms: u64| async move {
+            tokio::time::sleep(Duration::from_millis(ms)).await;
+            Ok(())
+        });
+
+        // One-shot trigger for the calling coroutine (waiters.rs). The matcher
+        // is registered before `after_command` is queued, so the reply cannot
+        // slip past. Returns the line and its captures like a trigger callback
+        // receives them, or nil on timeout.
+        methods.async_method(
+            "wait_for",
+            |lua, this, (pattern, opts): (String, Option<Table>)| {
+                let parsed = (|| {
+                    let regex = Regex::new(&pattern).map_err(|e| {
+                        mlua::Error::external(format!("wrapper:wait_for: invalid pattern: {e}"))
+                    })?;
+                    let (timeout_ms, command) = match &opts {
+                        Some(t) => (
+                            t.get::<Option<u64>>("timeout_ms")?,
+                            t.get::<Option<String>>("after_command")?,
+                        ),
+                        None => (None, None),
+                    };
+                    let timeout_ms = timeout_ms.unwrap_or(RESPONSE_TIMEOUT_MS);
+                    if timeout_ms == 0 {
+                        return Err(mlua::Error::external(
+                            "wrapper:wait_for: timeout_ms must be at least 1",
+                        ));
+                    }
+                    if command.is_some() {
+                        this.permissions.check("wait_for", "command")?;
+                    }
+                    Ok((regex, Duration::from_millis(timeout_ms), command))
+                })();
+                let waiters = this.line_waiters.clone();
+                let tx = this.cmd_tx.clone();
+                let plugin = this.dirname.clone();
+                async move {
+                    let (regex, timeout, command) = parsed?;
+                    if let Some(cmd) = &command {
+                        tprintln!("[MCRW -> Server]: {}", cmd);
+                    }
+                    let caps = waiters
+                        .request(
+                            LineMatch { plugin, regex },
+                            &tx,
+                            command.as_deref(),
+                            timeout,
+                        )
+                        .await;
+                    match caps {
+                        Some(caps) => Variadic::from_iter(caps).into_lua_multi(&lua),
+                        None => Value::Nil.into_lua_multi(&lua),
+                    }
+                }
+            },
+        );
+
+        methods.method(
             "register_on_stop",
             |lua: &Lua, this: &Self, func: Function| {
                 let callback = lua.create_registry_value(func)?;
-                this.stop_triggers
-                    .lock()
-                    .unwrap()
-                    .push(StopTrigger { callback });
+                this.stop_triggers.lock().unwrap().push(StopTrigger {
+                    callback,
+                    plugin: this.dirname.clone(),
+                });
                 Ok(())
             },
         );
 
-        methods.add_method(
+        methods.method(
             "register_on_crash",
             |lua: &Lua, this: &Self, func: Function| {
                 let callback = lua.create_registry_value(func)?;
-                this.crash_triggers
-                    .lock()
-                    .unwrap()
-                    .push(CrashTrigger { callback });
+                this.crash_triggers.lock().unwrap().push(CrashTrigger {
+                    callback,
+                    plugin: this.dirname.clone(),
+                });
                 Ok(())
             },
         );
 
         // Fired with a PlayerHandle when a player joins / leaves the game.
-        methods.add_method(
+        methods.method(
             "register_on_join",
             |lua: &Lua, this: &Self, func: Function| {
                 let callback = lua.create_registry_value(func)?;
-                this.join_triggers.lock().unwrap().push(callback);
+                this.join_triggers.lock().unwrap().push(PluginCallback {
+                    callback,
+                    plugin: this.dirname.clone(),
+                });
                 Ok(())
             },
         );
-        methods.add_method(
+        methods.method(
             "register_on_leave",
             |lua: &Lua, this: &Self, func: Function| {
                 let callback = lua.create_registry_value(func)?;
-                this.leave_triggers.lock().unwrap().push(callback);
+                this.leave_triggers.lock().unwrap().push(PluginCallback {
+                    callback,
+                    plugin: this.dirname.clone(),
+                });
+                Ok(())
+            },
+        );
+
+        // Called with "reload", "unload" or "shutdown" before this plugin's
+        // registrations are cleared, each call bounded by
+        // [reload].unload_timeout_ms.
+        methods.method(
+            "register_on_unload",
+            |lua: &Lua, this: &Self, func: Function| {
+                let callback = lua.create_registry_value(func)?;
+                this.unload_hooks.lock().unwrap().push(PluginCallback {
+                    callback,
+                    plugin: this.dirname.clone(),
+                });
+                Ok(())
+            },
+        );
+        // Called once every plugin of a load pass has initialized, so imports
+        // and other cross-plugin wiring can be done here.
+        methods.method(
+            "register_on_load_complete",
+            |lua: &Lua, this: &Self, func: Function| {
+                let callback = lua.create_registry_value(func)?;
+                this.load_complete_hooks
+                    .lock()
+                    .unwrap()
+                    .push(PluginCallback {
+                        callback,
+                        plugin: this.dirname.clone(),
+                    });
                 Ok(())
             },
         );
 
         // Online player handles (array). Static fields read the cache; pos()/
         // dimension() fetch live data.
-        methods.add_method("players", |lua: &Lua, this: &Self, ()| {
+        methods.method("players", |lua: &Lua, this: &Self, ()| {
             let t = lua.create_table()?;
             for (i, name) in this.player_registry.online_names().into_iter().enumerate() {
                 t.set(i + 1, PlayerHandle::new(this.player_registry.clone(), name))?;
@@ -680,7 +1261,7 @@ impl UserData for PluginApi {
         });
 
         // A single player handle, or nil if the name has never been seen.
-        methods.add_method("player", |_lua: &Lua, this: &Self, name: String| {
+        methods.method("player", |_lua: &Lua, this: &Self, name: String| {
             Ok(this
                 .player_registry
                 .snapshot(&name)
@@ -688,18 +1269,28 @@ impl UserData for PluginApi {
         });
 
         // True when a live RCON connection backs the active-query path.
-        methods.add_method("is_rcon", |_lua: &Lua, this: &Self, ()| {
+        methods.method("is_rcon", |_lua: &Lua, this: &Self, ()| {
             Ok(this.rcon.as_ref().map(|h| h.is_connected()).unwrap_or(false))
         });
 
+        // A snapshot; fields the wrapper has not learned yet are nil.
+        methods.method("server_info", |lua: &Lua, this: &Self, ()| {
+            let info = this.server_info.lock().unwrap().clone();
+            lua.to_value_with(&info, SerializeOptions::new().serialize_none_to_null(false))
+        });
+
         // Persistent KV store handle. No argument → this plugin's private
         // namespace ("plugin:<dirname>"); a name → a shared namespace
         // ("shared:<name>") for cross-plugin data. Data survives !reload and
         // restarts (.mcrw/store.json). See StoreHandle for the get/set/delete/
         // keys/flush methods.
-        methods.add_method("store", |_lua: &Lua, this: &Self, ns: Option<String>| {
+        methods.method("store", |_lua: &Lua, this: &Self, ns: Option<String>| {
             let namespace = match ns {
-                Some(n) => format!("shared:{n}"),
+                Some(n) => {
+                    this.permissions
+                        .check("store", &format!("store:shared:{n}"))?;
+                    format!("shared:{n}")
+                }
                 None => format!("plugin:{}", this.dirname),
             };
             Ok(StoreHandle::new(this.store.clone(), namespace))
@@ -709,10 +1300,12 @@ impl UserData for PluginApi {
         // RCON is not enabled, not connected, or the call times out
         // ([rcon].timeout_ms, default 5000). Unlike wrapper:command (fire-and-
         // forget to stdin), this captures the command's response text.
-        methods.add_async_method("rcon_command", |_lua, this, cmd: String| {
+        methods.async_method("rcon_command", |_lua, this, cmd: String| {
+            let allowed = this.permissions.check("rcon_command", "rcon");
             let handle = this.rcon.clone();
-            let timeout_ms = this.mcrw_config.rcon.timeout_ms;
+            let timeout_ms = this.mcrw_config.lock().unwrap().rcon.timeout_ms;
             async move {
+                allowed?;
                 let handle = handle.ok_or_else(|| {
                     mlua::Error::external(
                         "wrapper:rcon_command: RCON is not enabled (set enable-rcon in server.properties or [rcon] in mcrw.toml)",
@@ -731,7 +1324,61 @@ impl UserData for PluginApi {
             }
         });
 
-        methods.add_method(
+        // Command with a reply, over RCON when it is connected and over the
+        // console otherwise (waiters.rs). Either way the reply must match
+        // response_pattern; returns it and its captures, or nil.
+        methods.async_method("query", |lua, this, (cmd, opts): (String, Table)| {
+            let allowed = this.permissions.check("query", "command");
+            let parsed = (|| {
+                let pattern = opts
+                    .get::<Option<String>>("response_pattern")?
+                    .ok_or_else(|| {
+                        mlua::Error::external("wrapper:query: opts.response_pattern is required")
+                    })?;
+                let regex = Regex::new(&pattern).map_err(|e| {
+                    mlua::Error::external(format!("wrapper:query: invalid response_pattern: {e}"))
+                })?;
+                let timeout_ms = opts
+                    .get::<Option<u64>>("timeout_ms")?
+                    .unwrap_or(RESPONSE_TIMEOUT_MS);
+                if timeout_ms == 0 {
+                    return Err(mlua::Error::external(
+                        "wrapper:query: timeout_ms must be at least 1",
+                    ));
+                }
+                Ok((regex, Duration::from_millis(timeout_ms)))
+            })();
+            let rcon = this.rcon.clone().filter(|h| h.is_connected());
+            let waiters = this.line_waiters.clone();
+            let locks = this.query_locks.clone();
+            let tx = this.cmd_tx.clone();
+            let plugin = this.dirname.clone();
+            async move {
+                allowed?;
+                let (regex, timeout) = parsed?;
+                let reply = tokio::time::timeout(timeout, async {
+                    if let Some(rcon) = rcon {
+                        return waiters::captures(&regex, &rcon.command(&cmd).await?);
+                    }
+                    // Queries for the same pattern take turns, so each one
+                    // takes the reply to its own command.
+                    let _turn = locks.acquire(regex.as_str()).await;
+                    tprintln!("[MCRW -> Server]: {}", cmd);
+                    waiters
+                        .request(LineMatch { plugin, regex }, &tx, Some(&cmd), timeout)
+                        .await
+                })
+                .await
+                .ok()
+                .flatten();
+                match reply {
+                    Some(caps) => Variadic::from_iter(caps).into_lua_multi(&lua),
+                    None => Value::Nil.into_lua_multi(&lua),
+                }
+            }
+        });
+
+        methods.method(
             "register_start",
             |lua: &Lua, this: &Self, func: Function| {
                 let callback = lua.create_registry_value(func)?;
@@ -742,25 +1389,148 @@ impl UserData for PluginApi {
                         callbacks: Vec::new(),
                     })
                     .callbacks
-                    .push(callback);
+                    .push(PluginCallback {
+                        callback,
+                        plugin: this.dirname.clone(),
+                    });
+                Ok(())
+            },
+        );
+
+        methods.method(
+            "register_on_quarantine",
+            |lua: &Lua, this: &Self, func: Function| {
+                let callback = lua.create_registry_value(func)?;
+                let mut map = this.lifecycle_events.lock().unwrap();
+                map.entry(QUARANTINE_EVENT.to_string())
+                    .or_insert_with(|| LifecycleEventState {
+                        patterns: Vec::new(),
+                        callbacks: Vec::new(),
+                    })
+                    .callbacks
+                    .push(PluginCallback {
+                        callback,
+                        plugin: this.dirname.clone(),
+                    });
+                Ok(())
+            },
+        );
+
+        methods.method(
+            "register_on_exception",
+            |lua: &Lua, this: &Self, func: Function| {
+                let callback = lua.create_registry_value(func)?;
+                let mut map = this.lifecycle_events.lock().unwrap();
+                map.entry(EXCEPTION_EVENT.to_string())
+                    .or_insert_with(|| LifecycleEventState {
+                        patterns: Vec::new(),
+                        callbacks: Vec::new(),
+                    })
+                    .callbacks
+                    .push(PluginCallback {
+                        callback,
+                        plugin: this.dirname.clone(),
+                    });
                 Ok(())
             },
         );
 
-        methods.add_method("log", |_lua: &Lua, this: &Self, msg: String| {
+        methods.method("log", |_lua: &Lua, this: &Self, msg: String| {
             tprintln!("[{}] {}", this.meta.name, msg);
             Ok(())
         });
 
-        methods.add_method("meta", |lua: &Lua, this: &Self, ()| {
+        methods.method("meta", |lua: &Lua, this: &Self, ()| {
             lua.to_value(&this.meta)
         });
 
+        // Capability probe: true if this wrapper provides the named API, i.e.
+        // it is declared in api_spec (and therefore registered).
+        methods.method("has_feature", |_lua: &Lua, _this: &Self, name: String| {
+            Ok(WRAPPER.method(&name).is_some())
+        });
+
+        // Callback metrics; this plugin's own unless opts.plugin says otherwise,
+        // which needs the "metrics" permission: reading or resetting another
+        // plugin's numbers is not a plugin's own business.
+        methods.method("stats", |lua: &Lua, this: &Self, opts: Option<Table>| {
+            let (plugin, reset) = match opts {
+                Some(t) => (
+                    t.get::<Option<String>>("plugin")?,
+                    t.get::<Option<bool>>("reset")?.unwrap_or(false),
+                ),
+                None => (None, false),
+            };
+            let plugin = plugin.unwrap_or_else(|| this.dirname.clone());
+            if plugin != this.dirname {
+                this.permissions.check("stats", "metrics")?;
+            }
+            let filter = (plugin != "*").then_some(plugin.as_str());
+            let stats = if reset {
+                this.metrics.take(filter)
+            } else {
+                this.metrics.snapshot(filter)
+            };
+            lua.to_value(&stats)
+        });
+
+        // Publish this plugin's service table for dependents to `import`. A
+        // second call replaces the first. The table is shared by reference, so
+        // functions in it run as ordinary calls inside the importer's coroutine.
+        methods.method("export", |lua: &Lua, this: &Self, api: Table| {
+            let key = lua.create_registry_value(api)?;
+            this.exports
+                .lock()
+                .unwrap()
+                .insert(this.dirname.clone(), key);
+            Ok(())
+        });
+
+        // Fetch the table another plugin published with `wrapper:export`. The
+        // target must be listed in this plugin's meta.toml `dependencies`: that
+        // is what guarantees the loader initialized it first.
+        methods.method("import", |lua: &Lua, this: &Self, name: String| {
+            let target = {
+                let plugins = this.plugins.lock().unwrap();
+                let resolved = resolve_plugin_name(&plugins, &name);
+                let declared = this.meta.dependencies.iter().any(|d| {
+                    *d == name
+                        || (resolved.is_some() && resolve_plugin_name(&plugins, d) == resolved)
+                });
+                if !declared {
+                    return Err(mlua::Error::external(format!(
+                        "wrapper:import: '{name}' is not listed in this plugin's dependencies"
+                    )));
+                }
+                resolved.ok_or_else(|| {
+                    mlua::Error::external(format!(
+                        "wrapper:import: dependency '{name}' is not loaded"
+                    ))
+                })?
+            };
+            // Tables cannot cross Lua states.
+            let target_isolation = this.plugins.lock().unwrap().get(&target).map(|m| m.isolation);
+            if this.meta.isolation == Isolation::Isolated
+                || target_isolation == Some(Isolation::Isolated)
+            {
+                return Err(mlua::Error::external(format!(
+                    "wrapper:import: '{name}' and this plugin do not share a Lua state (isolation = \"isolated\")"
+                )));
+            }
+            let exports = this.exports.lock().unwrap();
+            match exports.get(&target) {
+                Some(key) => lua.registry_value::<Table>(key),
+                None => Err(mlua::Error::external(format!(
+                    "wrapper:import: plugin '{name}' did not call wrapper:export"
+                ))),
+            }
+        });
+
         // Permission check against the server's standard `ops.json`. Match is
         // case-insensitive (mirrors Minecraft's own command parser). Missing
         // or malformed ops.json degrade to `false`; hard IO/parse errors are
         // logged to stderr so admins still notice misconfiguration.
-        methods.add_method(
+        methods.method(
             "is_op",
             |_lua: &Lua, _this: &Self, name: String| -> mlua::Result<bool> {
                 Ok(read_op_names()
@@ -769,49 +1539,80 @@ impl UserData for PluginApi {
             },
         );
 
-        methods.add_method(
+        // Deep-merge config.json over `default_cfg`, writing newly added keys
+        // back; validate against `schema` if given. See plugin_config.rs.
+        methods.method(
             "load_config",
-            |lua: &Lua, this: &PluginApi, default_cfg: Value| {
+            |lua: &Lua, this: &PluginApi, (default_cfg, schema): (Value, Option<Table>)| {
                 let config_path = Path::new("lua_plugins")
                     .join(&this.dirname)
                     .join("config.json");
-                let mut final_config: JsonValue = lua.from_value(default_cfg)?;
-                tprintln!("{}", config_path.display());
-
-                if config_path.exists() {
-                    // if exists: read config file
-                    let content = fs::read_to_string(&config_path).map_err(|e| {
-                        mlua::Error::external(format!("Failed to read config: {}", e))
-                    })?;
-
-                    let file_config: JsonValue =
-                        serde_json::from_str(&content).map_err(|e: serde_json::Error| {
-                            mlua::Error::external(format!("Config JSON syntax error: {}", e))
-                        })?;
-                    final_config = file_config;
-                } else {
-                    // if not exists: save default config
-                    let json_str = serde_json::to_string_pretty(&final_config)
-                        .map_err(mlua::Error::external)?;
-
-                    fs::write(&config_path, json_str).map_err(|e| {
-                        mlua::Error::external(format!("Failed to write config: {}", e))
-                    })?;
-
-                    tprintln!("[{}] Created new config file.", this.meta.name);
+                let defaults: JsonValue = lua.from_value(default_cfg)?;
+                let schema: Option<JsonValue> = schema
+                    .map(|t| lua.from_value(Value::Table(t)))
+                    .transpose()?;
+                let (config, write) =
+                    load_plugin_config(&config_path, defaults.clone(), schema.as_ref()).map_err(
+                        |e| {
+                            mlua::Error::external(format!(
+                                "wrapper:load_config: {}: {e}",
+                                config_path.display()
+                            ))
+                        },
+                    )?;
+                match write {
+                    ConfigWrite::Unchanged => {}
+                    ConfigWrite::Created => {
+                        tprintln!("[{}] Created new config file.", this.meta.name)
+                    }
+                    ConfigWrite::Added(keys) => tprintln!(
+                        "[{}] Added new config keys: {}",
+                        this.meta.name,
+                        keys.join(", ")
+                    ),
                 }
-                let result_lua_value = lua.to_value(&final_config)?;
-                Ok(result_lua_value)
+                let result = lua.to_value(&config)?;
+                this.configs
+                    .lock()
+                    .unwrap()
+                    .entry(this.dirname.clone())
+                    .or_default()
+                    .loaded = Some(LoadedConfig {
+                    defaults,
+                    schema,
+                    current: config,
+                });
+                Ok(result)
             },
         );
 
-        // Active push: queue one command to the server immediately, without waiting
+        // Called with (new, old) config tables after an edit to config.json
+        // passed load_config's merge and schema. The table load_config
+        // returned is not updated in place.
+        methods.method(
+            "register_on_config_change",
+            |lua: &Lua, this: &Self, func: Function| {
+                let callback = lua.create_registry_value(func)?;
+                this.configs
+                    .lock()
+                    .unwrap()
+                    .entry(this.dirname.clone())
+                    .or_default()
+                    .callbacks
+                    .push(callback);
+                Ok(())
+            },
+        );
+
+        // Active push: queue one command to the server immediately, without waiting
         // for the current callback to return. Yields (Lua coroutine pauses) if the
         // 1000-slot command queue is full — same backpressure as callback-returned
         // commands. Errors only on shutdown (receiver dropped).
-        methods.add_async_method("command", |_lua, this, cmd: String| {
+        methods.async_method("command", |_lua, this, cmd: String| {
+            let allowed = this.permissions.check("command", "command");
             let tx = this.cmd_tx.clone();
             async move {
+                allowed?;
                 match tx.send(format!("{}\n", cmd)).await {
                     Ok(_) => {
                         tprintln!("[MCRW -> Server]: {}", cmd);
@@ -829,7 +1630,7 @@ impl UserData for PluginApi {
 
         // Async escape-hatch: run a Python script located inside this plugin's directory.
         // Returns a table { stdout = <parsed-JSON-of-last-stdout-line>, stderr = string, code = int }.
-        methods.add_async_method(
+        methods.async_method(
             "run_python",
             |lua,
              this,
@@ -837,12 +1638,14 @@ impl UserData for PluginApi {
                 // Snapshot everything we need out of `this` synchronously so the
                 // returned future captures only owned data (and is therefore Send + 'static
                 // without depending on UserDataRef's Send-ness).
+                let allowed = this.permissions.check("run_python", "python");
                 let dirname = this.dirname.clone();
                 let plugin_name = this.meta.name.clone();
-                let mcrw_config = this.mcrw_config.clone();
+                let mcrw_config = this.mcrw_config.lock().unwrap().clone();
                 let children = this.children.clone();
                 let next_child_id = this.next_child_id.clone();
                 async move {
+                    allowed?;
                     run_python_impl(
                         lua,
                         dirname,
@@ -862,13 +1665,13 @@ impl UserData for PluginApi {
         // JSON helpers. Lua 5.4 ships no JSON library, so plugins cannot build a
         // request body or parse a response without these. Backed by serde_json,
         // the same codec `run_python` uses for its stdout protocol.
-        methods.add_method("json_encode", |lua: &Lua, _this: &Self, v: Value| {
+        methods.method("json_encode", |lua: &Lua, _this: &Self, v: Value| {
             let jv: JsonValue = lua.from_value(v)?;
             serde_json::to_string(&jv)
                 .map_err(|e| mlua::Error::external(format!("wrapper:json_encode: {e}")))
         });
 
-        methods.add_method("json_decode", |lua: &Lua, _this: &Self, s: String| {
+        methods.method("json_decode", |lua: &Lua, _this: &Self, s: String| {
             let jv: JsonValue = serde_json::from_str(&s)
                 .map_err(|e| mlua::Error::external(format!("wrapper:json_decode: {e}")))?;
             lua.to_value(&jv)
@@ -879,12 +1682,24 @@ impl UserData for PluginApi {
         // failures (DNS, connect, timeout) raise; a non-2xx status returns
         // normally with `ok = false`. Streaming (`http_stream`) is a reserved,
         // not-yet-implemented namespace — see docs.
-        methods.add_async_method("http_request", |lua, this, opts: Table| {
+        methods.async_method("http_request", |lua, this, opts: Table| {
+            let allowed = this.permissions.check("http_request", "http");
             let client = this.http_client.clone();
-            let default_timeout_ms = this.mcrw_config.http.default_timeout_ms;
+            let default_timeout_ms = this.mcrw_config.lock().unwrap().http.default_timeout_ms;
             let plugin = this.meta.name.clone();
-            async move { http_request_impl(lua, client, default_timeout_ms, plugin, opts).await }
+            async move {
+                allowed?;
+                http_request_impl(lua, client, default_timeout_ms, plugin, opts).await
+            }
         });
+
+        // True if this plugin may use `cap` ("http", "store:shared:bank", …),
+        // so optional features can be skipped instead of raising. Always true
+        // unless [permissions] enforce is on.
+        methods.method("has_permission", |_lua: &Lua, this: &Self, cap: String| {
+            Ok(this.permissions.allows(&cap))
+        });
+        methods.finish();
     }
 }
 
@@ -1089,7 +1904,13 @@ async fn run_python_impl(
     let stdin_handle = child.stdin.take();
     {
         let mut g = children.lock().unwrap();
-        g.insert(task_id, child);
+        g.insert(
+            task_id,
+            TrackedChild {
+                child,
+                plugin: dirname.clone(),
+            },
+        );
     }
 
     // (e) stdin feeder
@@ -1134,6 +1955,7 @@ async fn run_python_impl(
         };
         match child_opt {
             Some(mut c) => c
+                .child
                 .wait()
                 .await
                 .map_err(|e| mlua::Error::external(format!("wait python: {e}"))),
@@ -1145,7 +1967,7 @@ async fn run_python_impl(
         Err(_) => {
             // timeout: if it's still in the tracker, kill it (kill_on_drop is the safety net).
             if let Some(mut c) = children.lock().unwrap().remove(&task_id) {
-                let _ = c.start_kill();
+                let _ = c.child.start_kill();
             }
             return Err(mlua::Error::external(format!(
                 "python script timed out after {timeout_ms}ms ({})",
@@ -1187,28 +2009,40 @@ async fn run_python_impl(
 // ServerApi — the global `Server` userdata exposed to Lua.
 // ---------------------------------------------------------------------------
 
+// Cloned into each isolated plugin's Lua state (see new_plugin_state).
+#[derive(Clone)]
 pub struct ServerApi {
     pub triggers: TriggerList,
     pub stop_triggers: StopTriggerList,
     pub crash_triggers: CrashTriggerList,
     pub plugins: PluginRegistry,
     pub lifecycle_events: LifecycleEvents,
-    pub mcrw_config: Arc<McrwConfig>,
+    pub mcrw_config: SharedMcrwConfig,
     pub children: ChildTracker,
     pub next_child_id: ChildIdCounter,
     pub cmd_tx: mpsc::Sender<String>,
     pub cron_jobs: CronJobList,
+    pub timers: TimerList,
+    pub line_waiters: LineWaiters,
+    pub query_locks: Arc<QueryLocks>,
+    pub server_info: SharedServerInfo,
     pub http_client: reqwest::Client,
     pub player_registry: Arc<PlayerRegistry>,
     pub join_triggers: PlayerCallbackList,
     pub leave_triggers: PlayerCallbackList,
+    pub unload_hooks: HookList,
+    pub load_complete_hooks: HookList,
     pub rcon: Option<RconHandle>,
     pub store: Arc<StoreRegistry>,
+    pub metrics: Arc<MetricsRegistry>,
+    pub exports: PluginExports,
+    pub configs: PluginConfigs,
 }
 
 impl UserData for ServerApi {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
-        methods.add_method(
+        let mut methods = Registrar::methods(&SERVER, methods);
+        methods.method(
             "get_context",
             |_lua: &Lua, this: &Self, module_path: String| {
                 // module_path looks like "lua_plugins.<dirname>."
@@ -1229,6 +2063,11 @@ impl UserData for ServerApi {
                     })?
                 };
 
+                let permissions = Permissions::resolve(
+                    &dirname,
+                    &meta.permissions,
+                    &this.mcrw_config.lock().unwrap().permissions,
+                );
                 Ok(PluginApi {
                     dirname,
                     meta,
@@ -1241,28 +2080,78 @@ impl UserData for ServerApi {
                     next_child_id: this.next_child_id.clone(),
                     cmd_tx: this.cmd_tx.clone(),
                     cron_jobs: this.cron_jobs.clone(),
+                    timers: this.timers.clone(),
+                    line_waiters: this.line_waiters.clone(),
+                    query_locks: this.query_locks.clone(),
+                    server_info: this.server_info.clone(),
                     http_client: this.http_client.clone(),
                     player_registry: this.player_registry.clone(),
                     join_triggers: this.join_triggers.clone(),
                     leave_triggers: this.leave_triggers.clone(),
+                    unload_hooks: this.unload_hooks.clone(),
+                    load_complete_hooks: this.load_complete_hooks.clone(),
                     rcon: this.rcon.clone(),
                     store: this.store.clone(),
+                    metrics: this.metrics.clone(),
+                    plugins: this.plugins.clone(),
+                    exports: this.exports.clone(),
+                    configs: this.configs.clone(),
+                    permissions,
                 })
             },
         );
+        methods.finish();
     }
 }
 
-pub fn load_plugins(lua: &Lua, registry: &PluginRegistry) -> mlua::Result<()> {
+// What a load pass did besides filling the registry, for the summary.
+#[derive(Debug, Default)]
+pub struct LoadReport {
+    // Directory names listed in `.mcrw/plugins.toml`'s `disabled`.
+    pub disabled: Vec<String>,
+    // (dirname, reason) for every plugin that was skipped or whose init.lua
+    // raised an error.
+    pub failed: Vec<(String, String)>,
+}
+
+impl LoadReport {
+    fn skip(&mut self, dirname: String, reason: String) {
+        teprintln!("[MCRW] [ERROR] skip plugin '{}': {}", dirname, reason);
+        self.failed.push((dirname, reason));
+    }
+}
+
+const DISABLED_REASON: &str = "disabled in .mcrw/plugins.toml";
+
+// `state` says which plugins are disabled: normally what .mcrw/plugins.toml
+// holds, or the edit `!plugin enable|disable` writes once the reload commits.
+// `force_incompatible` (the `--force-incompatible` flag) loads plugins whose
+// `mcrw_version` excludes this wrapper, with a warning instead of a skip.
+pub fn load_plugins(
+    lua: &Lua,
+    registry: &PluginRegistry,
+    vms: &PluginVms,
+    state: &PluginState,
+    force_incompatible: bool,
+) -> mlua::Result<LoadReport> {
     let plugins_dir = Path::new("lua_plugins");
+    let mut report = LoadReport::default();
 
     let globals = lua.globals();
     let package: Table = globals.get("package")?;
     let current_path: String = package.get("path")?;
 
-    let new_path = format!("lua_plugins/?.lua;lua_plugins/?/init.lua;{}", current_path);
-    package.set("path", new_path)?;
+    // lua_libs/ comes after lua_plugins/, so `require("lua_plugins.x")` never
+    // resolves into a library. Only prepended once per Lua state.
+    let prefix = "lua_plugins/?.lua;lua_plugins/?/init.lua;lua_libs/?.lua;lua_libs/?/init.lua;";
+    if !current_path.starts_with(prefix) {
+        package.set("path", format!("{prefix}{current_path}"))?;
+    }
 
+    // Pass 1: collect every plugin with an init.lua and a valid meta.toml, so
+    // the load order can be decided from the whole set's dependencies.
+    let mut candidates = Vec::new();
+    let mut disabled = Vec::new();
     for entry in fs::read_dir(plugins_dir)? {
         let entry = entry?;
         let path = entry.path();
@@ -1273,44 +2162,381 @@ pub fn load_plugins(lua: &Lua, registry: &PluginRegistry) -> mlua::Result<()> {
 
         let dirname = path.file_name().unwrap().to_str().unwrap().to_string();
         let init_path = path.join("init.lua");
-        let meta_path = path.join("meta.toml");
 
         if !init_path.exists() {
             continue;
         }
 
-        let meta: PluginMeta = match fs::read_to_string(&meta_path)
-            .map_err(|e| format!("read meta.toml: {}", e))
-            .and_then(|s| {
-                toml::from_str::<PluginMeta>(&s)
-                    .map_err(|e| format!("parse meta.toml: {}", e))
-            }) {
+        let is_disabled = state.is_disabled(&dirname);
+        let meta: PluginMeta = match read_plugin_meta(&path) {
             Ok(m) => m,
+            Err(_) if is_disabled => {
+                report.disabled.push(dirname);
+                continue;
+            }
             Err(e) => {
-                teprintln!("[MCRW] [ERROR] skip plugin '{}': {}", dirname, e);
+                report.skip(dirname, e);
                 continue;
             }
         };
+        // Disabled plugins still take part in ordering, so their dependents are
+        // reported as such instead of as "missing dependency".
+        if is_disabled {
+            disabled.push(dirname.clone());
+            candidates.push((dirname, meta));
+            continue;
+        }
+        if let Err(e) = gate_mcrw_version(&dirname, &meta, force_incompatible) {
+            report.skip(dirname, e);
+            continue;
+        }
+        if let Err(e) = check_lib_requirements(Path::new(LIBS_DIR), &meta.libs) {
+            report.skip(dirname, e);
+            continue;
+        }
+        candidates.push((dirname, meta));
+    }
 
+    let (ordered, skipped) = resolve_load_order(candidates, &disabled);
+    for (dirname, reason) in skipped {
+        if reason == DISABLED_REASON {
+            report.disabled.push(dirname);
+        } else {
+            report.skip(dirname, reason);
+        }
+    }
+    report.disabled.sort();
+
+    // Pass 2: require in dependency order. A dependency whose init.lua threw is
+    // absent from the registry by now, so its dependents are skipped too.
+    for (dirname, meta) in ordered {
+        let failed_dep = {
+            let loaded = registry.lock().unwrap();
+            meta.dependencies
+                .iter()
+                .find(|d| resolve_plugin_name(&loaded, d).is_none())
+                .cloned()
+        };
+        if let Some(dep) = failed_dep {
+            report.skip(dirname, format!("dependency '{dep}' failed to load"));
+            continue;
+        }
+
+        if let Err(e) = require_plugin(lua, registry, vms, &dirname, &meta) {
+            teprintln!("[Error] Failed to load plugin {}: {}", dirname, e);
+            report.failed.push((dirname, e.to_string()));
+        }
+    }
+    Ok(report)
+}
+
+pub fn read_plugin_meta(plugin_dir: &Path) -> Result<PluginMeta, String> {
+    fs::read_to_string(plugin_dir.join("meta.toml"))
+        .map_err(|e| format!("read meta.toml: {}", e))
+        .and_then(|s| {
+            toml::from_str::<PluginMeta>(&s).map_err(|e| format!("parse meta.toml: {}", e))
+        })
+}
+
+// Apply the `mcrw_version` check. `Err` means the plugin must not load.
+fn gate_mcrw_version(
+    dirname: &str,
+    meta: &PluginMeta,
+    force_incompatible: bool,
+) -> Result<(), String> {
+    if let Err(e) = check_mcrw_version(&meta.mcrw_version) {
+        if !force_incompatible {
+            return Err(format!(
+                "{e} (start with --force-incompatible to load it anyway)"
+            ));
+        }
+        teprintln!(
+            "[MCRW] [WARNING] loading incompatible plugin '{}' (--force-incompatible): {}",
+            dirname,
+            e
+        );
+    }
+    Ok(())
+}
+
+// Run one plugin's init.lua. The plugin is registered first so its init.lua
+// can see itself (and its dependencies) in the registry. On error everything
+// it registered and the python children it spawned are discarded again.
+fn require_plugin(
+    lua: &Lua,
+    registry: &PluginRegistry,
+    vms: &PluginVms,
+    dirname: &str,
+    meta: &PluginMeta,
+) -> mlua::Result<()> {
+    let isolated = meta.isolation == Isolation::Isolated;
+    tprintln!(
+        "[MCRW] Loading plugin: {} v{} (dir: {}{})",
+        meta.name,
+        meta.version,
+        dirname,
+        if isolated { ", isolated" } else { "" }
+    );
+
+    let server = lua
+        .globals()
+        .get::<UserDataRef<ServerApi>>("Server")?
+        .clone();
+    let old_children: Vec<u64> = server
+        .children
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, c)| c.plugin == dirname)
+        .map(|(id, _)| *id)
+        .collect();
+    registry
+        .lock()
+        .unwrap()
+        .insert(dirname.to_string(), meta.clone());
+
+    let module_name = format!("lua_plugins.{}.", dirname);
+    let result = (|| {
+        let state = if isolated {
+            let state = new_plugin_state(lua)?;
+            // Registered before init.lua runs so its callbacks can be looked
+            // up; taken out again below with everything else if it fails.
+            vms.lock()
+                .unwrap()
+                .insert(dirname.to_string(), state.clone());
+            state
+        } else {
+            lua.clone()
+        };
+        let require: Function = state.globals().get("require")?;
+        require.call::<Value>(module_name)
+    })();
+    if result.is_err() {
+        // Whatever init.lua registered before it threw would otherwise keep
+        // firing for a plugin that is not loaded, out of reach of !unload.
+        kill_children_where(&server.children, |id, c| {
+            c.plugin == dirname && !old_children.contains(&id)
+        });
+        drop(take_plugins(
+            lua,
+            |p| p == dirname,
+            &server.triggers,
+            &server.stop_triggers,
+            &server.crash_triggers,
+            registry,
+            &server.lifecycle_events,
+            &server.cron_jobs,
+            &server.timers,
+            &server.join_triggers,
+            &server.leave_triggers,
+            &server.unload_hooks,
+            &server.load_complete_hooks,
+            &server.exports,
+            &server.configs,
+            vms,
+        )?);
+        lua.expire_registry_values();
+    }
+    result.map(|_| ())
+}
+
+// A fresh Lua state for an isolated plugin, set up like the shared one: same
+// package.path, a clone of its `Server` global (so registrations land in the
+// shared lists) and the permissions searcher.
+fn new_plugin_state(lua: &Lua) -> mlua::Result<Lua> {
+    let server = lua
+        .globals()
+        .get::<UserDataRef<ServerApi>>("Server")?
+        .clone();
+    let path: String = lua.globals().get::<Table>("package")?.get("path")?;
+
+    let state = Lua::new();
+    state.globals().get::<Table>("package")?.set("path", path)?;
+    install_plugin_searcher(&state, server.plugins.clone(), server.mcrw_config.clone())?;
+    state.globals().set("Server", server)?;
+    Ok(state)
+}
+
+// Loaded plugins (other than `dirname`) that list `dirname` as a dependency.
+pub fn loaded_dependents(plugins: &HashMap<String, PluginMeta>, dirname: &str) -> Vec<String> {
+    let mut out: Vec<String> = plugins
+        .iter()
+        .filter(|(dir, meta)| {
+            *dir != dirname
+                && meta
+                    .dependencies
+                    .iter()
+                    .any(|d| resolve_plugin_name(plugins, d).as_deref() == Some(dirname))
+        })
+        .map(|(dir, _)| dir.clone())
+        .collect();
+    out.sort();
+    out
+}
+
+// The "Loaded N plugins" block printed after startup and after `!reload`.
+// Disabled plugins get their own line so they are not mistaken for failures.
+pub fn print_load_summary(registry: &PluginRegistry, report: &LoadReport) {
+    let plugins = registry.lock().unwrap();
+    tprintln!("[MCRW] Loaded {} plugins:", plugins.len());
+    for (dirname, meta) in plugins.iter() {
+        let isolated = if meta.isolation == Isolation::Isolated {
+            ", isolated"
+        } else {
+            ""
+        };
         tprintln!(
-            "[MCRW] Loading plugin: {} v{} (dir: {})",
-            meta.name, meta.version, dirname
+            "  - {} v{} (dir: {}{})",
+            meta.name,
+            meta.version,
+            dirname,
+            isolated
         );
+    }
+    if !report.disabled.is_empty() {
+        tprintln!(
+            "[MCRW] Disabled plugins ({}): {}",
+            PLUGIN_STATE_PATH,
+            report.disabled.join(", ")
+        );
+    }
+}
 
-        registry
-            .lock()
-            .unwrap()
-            .insert(dirname.clone(), meta.clone());
+// Map a dependency (or `wrapper:import`) name onto a plugin directory name.
+// The directory name is the registry key and wins; a meta.toml `name` is
+// accepted as a fallback so manifests may refer to plugins by display name.
+pub fn resolve_plugin_name(plugins: &HashMap<String, PluginMeta>, name: &str) -> Option<String> {
+    if plugins.contains_key(name) {
+        return Some(name.to_string());
+    }
+    plugins
+        .iter()
+        .filter(|(_, m)| m.name == name)
+        .map(|(dir, _)| dir.clone())
+        .min()
+}
 
-        let require: Function = globals.get("require")?;
-        let module_name = format!("lua_plugins.{}.", dirname);
+// (plugins to load in order, (dirname, reason) for each skipped plugin)
+pub type LoadOrder = (Vec<(String, PluginMeta)>, Vec<(String, String)>);
+
+// Order plugin candidates so each one loads after everything in its
+// `dependencies`. Returns the load order plus `(dirname, reason)` for every
+// plugin that has to be skipped: a missing dependency, a dependency cycle, or a
+// dependency that was itself skipped. Ties break on directory name, so the
+// order no longer depends on `read_dir`. Plugins in `disabled` are skipped up
+// front; they are passed in so dependents can say why.
+pub fn resolve_load_order(candidates: Vec<(String, PluginMeta)>, disabled: &[String]) -> LoadOrder {
+    let mut metas: HashMap<String, PluginMeta> = candidates.into_iter().collect();
+    let mut deps: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut skipped: BTreeMap<String, String> = disabled
+        .iter()
+        .map(|d| (d.clone(), DISABLED_REASON.to_string()))
+        .collect();
 
-        if let Err(e) = require.call::<Value>(module_name) {
-            teprintln!("[Error] Failed to load plugin {}: {}", dirname, e);
-            registry.lock().unwrap().remove(&dirname);
+    for (dir, meta) in &metas {
+        let mut resolved = Vec::new();
+        for d in &meta.dependencies {
+            match resolve_plugin_name(&metas, d) {
+                Some(r) => resolved.push(r),
+                None => {
+                    skipped
+                        .entry(dir.clone())
+                        .or_insert_with(|| format!("missing dependency '{d}'"));
+                }
+            }
         }
+        deps.insert(dir.clone(), resolved);
     }
-    Ok(())
+
+    // A plugin depending on a skipped plugin is skipped as well (to a fixpoint,
+    // so whole chains fall away).
+    loop {
+        let newly: Vec<(String, String)> = deps
+            .iter()
+            .filter(|(dir, _)| !skipped.contains_key(*dir))
+            .filter_map(|(dir, ds)| {
+                ds.iter().find(|d| skipped.contains_key(*d)).map(|d| {
+                    let why = if disabled.contains(d) {
+                        "is disabled"
+                    } else {
+                        "was skipped"
+                    };
+                    (dir.clone(), format!("dependency '{d}' {why}"))
+                })
+            })
+            .collect();
+        if newly.is_empty() {
+            break;
+        }
+        skipped.extend(newly);
+    }
+
+    // Kahn's algorithm, always taking the smallest ready directory name.
+    let mut remaining: BTreeSet<String> = deps
+        .keys()
+        .filter(|d| !skipped.contains_key(*d))
+        .cloned()
+        .collect();
+    let mut order = Vec::new();
+    while let Some(next) = remaining
+        .iter()
+        .find(|dir| deps[*dir].iter().all(|d| !remaining.contains(d)))
+        .cloned()
+    {
+        remaining.remove(&next);
+        order.push(next);
+    }
+
+    // Whatever is left is on a cycle, or depends on one.
+    for dir in &remaining {
+        let mut path = vec![dir.clone()];
+        let mut seen = HashSet::new();
+        let reason = if cycle_through(dir, dir, &deps, &remaining, &mut path, &mut seen) {
+            format!("dependency cycle: {}", path.join(" -> "))
+        } else {
+            let dep = deps[dir].iter().find(|d| remaining.contains(*d)).unwrap();
+            format!("dependency '{dep}' is part of a dependency cycle")
+        };
+        skipped.insert(dir.clone(), reason);
+    }
+
+    let ordered = order
+        .into_iter()
+        .map(|dir| {
+            let meta = metas.remove(&dir).unwrap();
+            (dir, meta)
+        })
+        .collect();
+    (ordered, skipped.into_iter().collect())
+}
+
+// Depth-first search for a path from `node` back to `start` through the
+// unresolved plugins, appending it to `path` for the error message.
+fn cycle_through(
+    start: &str,
+    node: &str,
+    deps: &BTreeMap<String, Vec<String>>,
+    remaining: &BTreeSet<String>,
+    path: &mut Vec<String>,
+    seen: &mut HashSet<String>,
+) -> bool {
+    for d in &deps[node] {
+        if !remaining.contains(d) {
+            continue;
+        }
+        if d == start {
+            path.push(d.clone());
+            return true;
+        }
+        if seen.insert(d.clone()) {
+            path.push(d.clone());
+            if cycle_through(start, d, deps, remaining, path, seen) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
 }
 
 // Earliest cached fire time across all registered cron jobs, in
@@ -1331,6 +2557,7 @@ pub fn next_cron_fire(jobs: &CronJobList) -> Option<chrono::DateTime<chrono::Loc
 // dispatch on tokio tasks.
 pub fn drain_due_cron_jobs(
     lua: &Lua,
+    vms: &PluginVms,
     jobs: &CronJobList,
     now: chrono::DateTime<chrono::Local>,
 ) -> Vec<(Function, String, String, String)> {
@@ -1349,7 +2576,7 @@ pub fn drain_due_cron_jobs(
             continue;
         }
         job.next_fire = job.schedule.after(&fire).next();
-        match lua.registry_value::<Function>(&job.callback) {
+        match plugin_lua(lua, vms, &job.plugin).registry_value::<Function>(&job.callback) {
             Ok(f) => due.push((
                 f,
                 fire.to_rfc3339(),
@@ -1365,8 +2592,197 @@ pub fn drain_due_cron_jobs(
     due
 }
 
-// Mirrors run_main_loop's signature: reload must clear every shared state list,
+// Callbacks from `hooks` owned by plugins for which `owned(dirname)` holds, in
+// registration order, looked up now so no lock is held while Lua runs.
+fn hook_functions(
+    lua: &Lua,
+    vms: &PluginVms,
+    hooks: &HookList,
+    owned: impl Fn(&str) -> bool,
+) -> Vec<(String, Function)> {
+    hooks
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|h| owned(&h.plugin))
+        .filter_map(|h| {
+            match plugin_lua(lua, vms, &h.plugin).registry_value::<Function>(&h.callback) {
+                Ok(f) => Some((h.plugin.clone(), f)),
+                Err(e) => {
+                    teprintln!("[MCRW] [ERROR] hook registry lookup: {e}");
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+// Run the register_on_unload callbacks of the plugins about to be unloaded,
+// before any of their registrations are cleared. Each call gets `timeout_ms`;
+// one that overruns is abandoned mid-yield and logged, so a stuck hook cannot
+// stall a reload or shutdown.
+pub async fn run_unload_hooks(
+    lua: &Lua,
+    vms: &PluginVms,
+    hooks: &HookList,
+    metrics: &MetricsRegistry,
+    owned: impl Fn(&str) -> bool,
+    reason: &str,
+    timeout_ms: u64,
+) {
+    let dur = std::time::Duration::from_millis(timeout_ms);
+    for (plugin, f) in hook_functions(lua, vms, hooks, owned) {
+        let started = std::time::Instant::now();
+        let result = tokio::time::timeout(dur, f.call_async::<()>(reason)).await;
+        metrics.record(
+            &plugin,
+            Kind::Unload,
+            reason,
+            started.elapsed(),
+            matches!(result, Ok(Ok(()))),
+            0,
+        );
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => teprintln!("[MCRW] [ERROR] unload callback failed ({plugin}): {e}"),
+            Err(_) => teprintln!(
+                "[MCRW] [WARNING] unload callback of '{plugin}' timed out after {timeout_ms}ms"
+            ),
+        }
+    }
+}
+
+// Fire the register_on_load_complete callbacks of the plugins a load pass just
+// initialized, with reason "load". After a rolled-back reload the restored
+// instances get "rollback" instead: their unload callbacks already ran, so
+// this is their cue to take back whatever those released. Like line dispatch
+// they run on a spawned task, so a slow one does not hold up the main loop,
+// and are accounted and guarded by `dispatch` as lifecycle callbacks.
+pub fn spawn_load_complete_hooks(
+    lua: &Lua,
+    vms: &PluginVms,
+    hooks: &HookList,
+    dispatch: Dispatch,
+    owned: impl Fn(&str) -> bool,
+    reason: &'static str,
+) {
+    let funcs = hook_functions(lua, vms, hooks, owned);
+    if funcs.is_empty() {
+        return;
+    }
+    tokio::spawn(async move {
+        for (plugin, f) in funcs {
+            dispatch.notify(&f, reason, &plugin, "load_complete").await;
+        }
+    });
+}
+
+// Hand (plugin, reason) to the register_on_quarantine callbacks of every other
+// plugin, on a spawned task like the load-complete hooks.
+pub fn spawn_quarantine_event(
+    lua: &Lua,
+    vms: &PluginVms,
+    lifecycle_events: &LifecycleEvents,
+    dispatch: Dispatch,
+    plugin: &str,
+    reason: &str,
+) {
+    let funcs: Vec<(String, Function)> = {
+        let g = lifecycle_events.lock().unwrap();
+        let Some(state) = g.get(QUARANTINE_EVENT) else {
+            return;
+        };
+        state
+            .callbacks
+            .iter()
+            .filter(|cb| cb.plugin != plugin)
+            .filter_map(|cb| {
+                match plugin_lua(lua, vms, &cb.plugin).registry_value::<Function>(&cb.callback) {
+                    Ok(f) => Some((cb.plugin.clone(), f)),
+                    Err(e) => {
+                        teprintln!("[MCRW] [ERROR] quarantine registry lookup: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    };
+    if funcs.is_empty() {
+        return;
+    }
+    let (plugin, reason) = (plugin.to_string(), reason.to_string());
+    tokio::spawn(async move {
+        for (owner, f) in funcs {
+            dispatch
+                .notify(
+                    &f,
+                    (plugin.as_str(), reason.as_str()),
+                    &owner,
+                    QUARANTINE_EVENT,
+                )
+                .await;
+        }
+    });
+}
+
+// lua_plugins/<dir>/config.json changed: redo the plugin's load_config merge
+// and hand (new, old) to its register_on_config_change callbacks on a spawned
+// task. A file that no longer parses or validates is reported and the plugin
+// keeps the config it has. Plugins that never called load_config are skipped.
+pub fn reload_plugin_config(
+    lua: &Lua,
+    vms: &PluginVms,
+    configs: &PluginConfigs,
+    dispatch: Dispatch,
+    dirname: &str,
+) -> mlua::Result<()> {
+    let lua = &plugin_lua(lua, vms, dirname);
+    let path = Path::new("lua_plugins").join(dirname).join("config.json");
+    let (new, old, funcs) = {
+        let mut g = configs.lock().unwrap();
+        let Some(state) = g.get_mut(dirname) else {
+            return Ok(());
+        };
+        let Some(loaded) = state.loaded.as_mut() else {
+            return Ok(());
+        };
+        let old = match loaded.reread(&path) {
+            Ok(Some(old)) => old,
+            Ok(None) => return Ok(()),
+            Err(e) => {
+                return Err(mlua::Error::external(format!(
+                    "{}: {e}; keeping the previous config",
+                    path.display()
+                )));
+            }
+        };
+        let funcs = state
+            .callbacks
+            .iter()
+            .map(|k| lua.registry_value::<Function>(k))
+            .collect::<mlua::Result<Vec<_>>>()?;
+        (lua.to_value(&loaded.current)?, lua.to_value(&old)?, funcs)
+    };
+    tprintln!("[MCRW] Reloaded {}", path.display());
+    let plugin = dirname.to_string();
+    tokio::spawn(async move {
+        for f in funcs {
+            dispatch
+                .notify(&f, (new.clone(), old.clone()), &plugin, "config_change")
+                .await;
+        }
+    });
+    Ok(())
+}
+
+// Mirrors run_main_loop's signature: reload must swap every shared state list,
 // so it borrows each one explicitly rather than hiding them behind a struct.
+//
+// Two-phase: the running plugin set is taken out (not destroyed), the new set
+// is loaded into the emptied lists, and unless a plugin that was running
+// failed to come back — any of them, or one named in `critical` when that is
+// non-empty — the old set is dropped and its python children killed. Otherwise
+// the new set is discarded and the old one put back exactly as it was.
 #[allow(clippy::too_many_arguments)]
 pub fn reload_plugins(
     lua: &Lua,
@@ -1377,59 +2793,571 @@ pub fn reload_plugins(
     lifecycle_events: &LifecycleEvents,
     children: &ChildTracker,
     cron_jobs: &CronJobList,
+    timers: &TimerList,
     join_triggers: &PlayerCallbackList,
     leave_triggers: &PlayerCallbackList,
+    unload_hooks: &HookList,
+    load_complete_hooks: &HookList,
+    exports: &PluginExports,
+    configs: &PluginConfigs,
+    vms: &PluginVms,
+    state: &PluginState,
+    force_incompatible: bool,
+    critical: &[String],
+    flavor: Flavor,
 ) -> mlua::Result<()> {
     tprintln!("[MCRW] Reloading plugins...");
 
-    // kill any in-flight python children before invalidating Lua state.
-    // we do not await wait() — kill_on_drop(true) is the safety net.
-    {
-        let drained: Vec<(u64, Child)> = {
-            let mut g = children.lock().unwrap();
-            g.drain().collect()
-        };
-        for (_id, mut c) in drained {
-            let _ = c.start_kill();
+    // NB: the player registry's online set/records are intentionally preserved
+    // across reload — a reload must not lose who is online.
+    let old_children: Vec<u64> = children.lock().unwrap().keys().copied().collect();
+    let old_lifecycle = {
+        let new_map = compile_trigger_config(load_trigger_config(
+            Path::new("trigger_config.toml"),
+            flavor,
+        ));
+        std::mem::replace(&mut *lifecycle_events.lock().unwrap(), new_map)
+    };
+    let take_all = || {
+        take_plugins(
+            lua,
+            |_| true,
+            triggers,
+            stop_triggers,
+            crash_triggers,
+            plugins,
+            lifecycle_events,
+            cron_jobs,
+            timers,
+            join_triggers,
+            leave_triggers,
+            unload_hooks,
+            load_complete_hooks,
+            exports,
+            configs,
+            vms,
+        )
+    };
+    let old = take_all()?;
+    // Libraries are re-read too; the old plugins keep the tables they hold.
+    let old_libs = take_lib_modules(lua)?;
+
+    let report = load_plugins(lua, plugins, vms, state, force_incompatible)?;
+    let was_loaded: Vec<&str> = old.plugins.iter().map(|(dir, _)| dir.as_str()).collect();
+    let blocking = blocking_failures(&report, &was_loaded, critical);
+    if blocking.is_empty() {
+        // Commit. We do not await wait() on the old children — kill_on_drop(true)
+        // is the safety net.
+        kill_children_where(children, |id, _| old_children.contains(&id));
+        drop(old);
+        drop(old_lifecycle);
+        lua.expire_registry_values();
+        print_load_summary(plugins, &report);
+        let count = plugins.lock().unwrap().len();
+        tprintln!("[MCRW] Reloaded {} plugins.", count);
+        return Ok(());
+    }
+
+    // Roll back: discard whatever the new set registered, then reinstate.
+    drop(take_all()?);
+    drop(take_lib_modules(lua)?);
+    restore_lib_modules(lua, old_libs)?;
+    kill_children_where(children, |id, _| !old_children.contains(&id));
+    *lifecycle_events.lock().unwrap() = old_lifecycle;
+    restore_plugins(
+        lua,
+        old,
+        triggers,
+        stop_triggers,
+        crash_triggers,
+        plugins,
+        lifecycle_events,
+        cron_jobs,
+        timers,
+        join_triggers,
+        leave_triggers,
+        unload_hooks,
+        load_complete_hooks,
+        exports,
+        configs,
+        vms,
+    )?;
+    lua.expire_registry_values();
+    let names: Vec<&str> = blocking.iter().map(|(dir, _)| dir.as_str()).collect();
+    Err(mlua::Error::external(format!(
+        "{} failed to initialize; kept the previous {} plugins running",
+        names.join(", "),
+        plugins.lock().unwrap().len()
+    )))
+}
+
+// Failures that abort a full reload: those of plugins that were running before
+// it (a plugin that already failed is no worse off), narrowed to the `critical`
+// plugins when any are configured.
+fn blocking_failures<'a>(
+    report: &'a LoadReport,
+    was_loaded: &[&str],
+    critical: &[String],
+) -> Vec<&'a (String, String)> {
+    report
+        .failed
+        .iter()
+        .filter(|(dir, _)| was_loaded.contains(&dir.as_str()))
+        .filter(|(dir, _)| critical.is_empty() || critical.contains(dir))
+        .collect()
+}
+
+// Registrations, registry entries, exports and `package.loaded` modules taken
+// out of the shared state for a set of plugins. A reload holds on to them
+// while the new code initializes, so a failure can put the old instance back
+// instead of leaving the plugin unloaded. List entries keep the index they
+// had, so the old instance's callbacks go back to their place in dispatch
+// order.
+#[derive(Default)]
+pub struct TakenPlugins {
+    triggers: Taken<Trigger>,
+    stop_triggers: Taken<StopTrigger>,
+    crash_triggers: Taken<CrashTrigger>,
+    cron_jobs: Taken<CronJob>,
+    timers: Vec<Timer>,
+    join_triggers: Taken<PluginCallback>,
+    leave_triggers: Taken<PluginCallback>,
+    unload_hooks: Taken<PluginCallback>,
+    load_complete_hooks: Taken<PluginCallback>,
+    lifecycle: Vec<(String, Taken<PluginCallback>)>,
+    exports: Vec<(String, RegistryKey)>,
+    configs: Vec<(String, PluginConfigState)>,
+    plugins: Vec<(String, PluginMeta)>,
+    modules: Vec<(String, Value)>,
+    envs: Vec<(String, Value)>,
+    vms: Vec<(String, Lua)>,
+}
+
+// Entries taken out of a list, each with the index it had.
+type Taken<T> = Vec<(usize, T)>;
+
+fn take_where<T>(list: &Mutex<Vec<T>>, owned: impl Fn(&T) -> bool) -> Taken<T> {
+    let mut g = list.lock().unwrap();
+    take_indexed(&mut g, owned)
+}
+
+fn take_indexed<T>(list: &mut Vec<T>, owned: impl Fn(&T) -> bool) -> Taken<T> {
+    let mut taken = Vec::new();
+    let mut kept = Vec::new();
+    for (i, t) in std::mem::take(list).into_iter().enumerate() {
+        if owned(&t) {
+            taken.push((i, t));
+        } else {
+            kept.push(t);
         }
     }
+    *list = kept;
+    taken
+}
 
-    triggers.lock().unwrap().clear();
-    stop_triggers.lock().unwrap().clear();
-    crash_triggers.lock().unwrap().clear();
-    cron_jobs.lock().unwrap().clear();
-    join_triggers.lock().unwrap().clear();
-    leave_triggers.lock().unwrap().clear();
-    plugins.lock().unwrap().clear();
-    // NB: the player registry's online set/records are intentionally preserved
-    // across reload — a reload must not lose who is online.
+// Undo `take_indexed`: with the rest of the list as it was left, inserting in
+// ascending index order puts every entry back where it was.
+fn put_back<T>(list: &mut Vec<T>, taken: Taken<T>) {
+    for (i, t) in taken {
+        let i = i.min(list.len());
+        list.insert(i, t);
+    }
+}
+
+// Owning plugin of a `package.loaded` key: `lua_plugins.<dir>.` and
+// `lua_plugins.<dir>.sub` both belong to `<dir>`. Plugin files required under
+// other names are recorded by the permissions searcher (PLUGIN_MODULES).
+pub fn module_owner(key: &str) -> Option<&str> {
+    key.strip_prefix("lua_plugins.")?.split('.').next()
+}
 
+// Take everything owned by plugins for which `owned(dirname)` holds. Python
+// children are not touched; callers decide when to kill them.
+#[allow(clippy::too_many_arguments)]
+pub fn take_plugins(
+    lua: &Lua,
+    owned: impl Fn(&str) -> bool,
+    triggers: &TriggerList,
+    stop_triggers: &StopTriggerList,
+    crash_triggers: &CrashTriggerList,
+    plugins: &PluginRegistry,
+    lifecycle_events: &LifecycleEvents,
+    cron_jobs: &CronJobList,
+    timers: &TimerList,
+    join_triggers: &PlayerCallbackList,
+    leave_triggers: &PlayerCallbackList,
+    unload_hooks: &HookList,
+    load_complete_hooks: &HookList,
+    exports: &PluginExports,
+    configs: &PluginConfigs,
+    vms: &PluginVms,
+) -> mlua::Result<TakenPlugins> {
+    let mut taken = TakenPlugins {
+        triggers: take_where(triggers, |t| owned(&t.plugin)),
+        stop_triggers: take_where(stop_triggers, |t| owned(&t.plugin)),
+        crash_triggers: take_where(crash_triggers, |t| owned(&t.plugin)),
+        cron_jobs: take_where(cron_jobs, |j| owned(&j.plugin)),
+        timers: timers.take_where(&owned),
+        join_triggers: take_where(join_triggers, |c| owned(&c.plugin)),
+        leave_triggers: take_where(leave_triggers, |c| owned(&c.plugin)),
+        unload_hooks: take_where(unload_hooks, |c| owned(&c.plugin)),
+        load_complete_hooks: take_where(load_complete_hooks, |c| owned(&c.plugin)),
+        ..Default::default()
+    };
+    for (event, state) in lifecycle_events.lock().unwrap().iter_mut() {
+        let mine = take_indexed(&mut state.callbacks, |c| owned(&c.plugin));
+        if !mine.is_empty() {
+            taken.lifecycle.push((event.clone(), mine));
+        }
+    }
     {
-        let new_map =
-            compile_trigger_config(load_trigger_config(Path::new("trigger_config.toml")));
-        let mut guard = lifecycle_events.lock().unwrap();
-        *guard = new_map;
+        let mut g = exports.lock().unwrap();
+        let keys: Vec<String> = g.keys().filter(|k| owned(k)).cloned().collect();
+        taken.exports = keys
+            .into_iter()
+            .filter_map(|k| g.remove(&k).map(|v| (k, v)))
+            .collect();
+    }
+    {
+        let mut g = configs.lock().unwrap();
+        let keys: Vec<String> = g.keys().filter(|k| owned(k)).cloned().collect();
+        taken.configs = keys
+            .into_iter()
+            .filter_map(|k| g.remove(&k).map(|v| (k, v)))
+            .collect();
+    }
+    {
+        let mut g = vms.lock().unwrap();
+        let keys: Vec<String> = g.keys().filter(|k| owned(k)).cloned().collect();
+        taken.vms = keys
+            .into_iter()
+            .filter_map(|k| g.remove(&k).map(|v| (k, v)))
+            .collect();
+    }
+    {
+        let mut g = plugins.lock().unwrap();
+        let keys: Vec<String> = g.keys().filter(|k| owned(k)).cloned().collect();
+        taken.plugins = keys
+            .into_iter()
+            .filter_map(|k| g.remove(&k).map(|v| (k, v)))
+            .collect();
     }
-
     let loaded: Table = lua.globals().get::<Table>("package")?.get("loaded")?;
-    let keys_to_clear: Vec<String> = loaded
-        .clone()
+    let aliases = lua.named_registry_value::<Option<Table>>(PLUGIN_MODULES)?;
+    let owner = |k: &str| match module_owner(k) {
+        Some(dir) => Some(dir.to_string()),
+        None => aliases.as_ref()?.get::<Option<String>>(k).ok()?,
+    };
+    taken.modules = loaded
         .pairs::<String, Value>()
         .filter_map(|p| p.ok())
-        .map(|(k, _)| k)
-        .filter(|k| k.starts_with("lua_plugins"))
+        .filter(|(k, _)| owner(k).is_some_and(|dir| owned(&dir)))
         .collect();
-    for k in keys_to_clear {
-        loaded.set(k, Value::Nil)?;
+    for (k, _) in &taken.modules {
+        loaded.set(k.as_str(), Value::Nil)?;
+    }
+    if let Some(envs) = lua.named_registry_value::<Option<Table>>(PLUGIN_ENVS)? {
+        taken.envs = envs
+            .pairs::<String, Value>()
+            .filter_map(|p| p.ok())
+            .filter(|(k, _)| owned(k))
+            .collect();
+        for (k, _) in &taken.envs {
+            envs.set(k.as_str(), Value::Nil)?;
+        }
+    }
+    Ok(taken)
+}
+
+// Put back what `take_plugins` removed, after the entries a failed load added
+// have been taken out again. Registrations return to their old positions, so
+// dispatch order is the same as before the reload.
+#[allow(clippy::too_many_arguments)]
+pub fn restore_plugins(
+    lua: &Lua,
+    taken: TakenPlugins,
+    triggers: &TriggerList,
+    stop_triggers: &StopTriggerList,
+    crash_triggers: &CrashTriggerList,
+    plugins: &PluginRegistry,
+    lifecycle_events: &LifecycleEvents,
+    cron_jobs: &CronJobList,
+    timers: &TimerList,
+    join_triggers: &PlayerCallbackList,
+    leave_triggers: &PlayerCallbackList,
+    unload_hooks: &HookList,
+    load_complete_hooks: &HookList,
+    exports: &PluginExports,
+    configs: &PluginConfigs,
+    vms: &PluginVms,
+) -> mlua::Result<()> {
+    put_back(&mut triggers.lock().unwrap(), taken.triggers);
+    put_back(&mut stop_triggers.lock().unwrap(), taken.stop_triggers);
+    put_back(&mut crash_triggers.lock().unwrap(), taken.crash_triggers);
+    put_back(&mut cron_jobs.lock().unwrap(), taken.cron_jobs);
+    timers.restore(taken.timers);
+    put_back(&mut join_triggers.lock().unwrap(), taken.join_triggers);
+    put_back(&mut leave_triggers.lock().unwrap(), taken.leave_triggers);
+    put_back(&mut unload_hooks.lock().unwrap(), taken.unload_hooks);
+    put_back(
+        &mut load_complete_hooks.lock().unwrap(),
+        taken.load_complete_hooks,
+    );
+    {
+        let mut g = lifecycle_events.lock().unwrap();
+        for (event, callbacks) in taken.lifecycle {
+            let state = g.entry(event).or_insert_with(|| LifecycleEventState {
+                patterns: Vec::new(),
+                callbacks: Vec::new(),
+            });
+            put_back(&mut state.callbacks, callbacks);
+        }
+    }
+    exports.lock().unwrap().extend(taken.exports);
+    configs.lock().unwrap().extend(taken.configs);
+    vms.lock().unwrap().extend(taken.vms);
+    plugins.lock().unwrap().extend(taken.plugins);
+    let loaded: Table = lua.globals().get::<Table>("package")?.get("loaded")?;
+    for (k, v) in taken.modules {
+        loaded.set(k, v)?;
+    }
+    if let Some(envs) = lua.named_registry_value::<Option<Table>>(PLUGIN_ENVS)? {
+        for (k, v) in taken.envs {
+            envs.set(k, v)?;
+        }
     }
+    Ok(())
+}
 
-    load_plugins(lua, plugins)?;
+// start_kill() every tracked child matching `which(task_id, child)`.
+fn kill_children_where(children: &ChildTracker, which: impl Fn(u64, &TrackedChild) -> bool) {
+    let mut g = children.lock().unwrap();
+    let doomed: Vec<u64> = g
+        .iter()
+        .filter(|(id, c)| which(**id, c))
+        .map(|(id, _)| *id)
+        .collect();
+    for id in doomed {
+        if let Some(mut c) = g.remove(&id) {
+            let _ = c.child.start_kill();
+        }
+    }
+}
 
-    let count = plugins.lock().unwrap().len();
-    tprintln!("[MCRW] Reloaded {} plugins.", count);
+// `!unload <plugin>`: kill the plugin's python children, drop every
+// registration it made and its export, and forget its `package.loaded`
+// entries. Other plugins keep running with their state intact. Same wide
+// signature as reload_plugins, for the same reason.
+#[allow(clippy::too_many_arguments)]
+pub fn unload_plugin(
+    lua: &Lua,
+    dirname: &str,
+    triggers: &TriggerList,
+    stop_triggers: &StopTriggerList,
+    crash_triggers: &CrashTriggerList,
+    plugins: &PluginRegistry,
+    lifecycle_events: &LifecycleEvents,
+    children: &ChildTracker,
+    cron_jobs: &CronJobList,
+    timers: &TimerList,
+    join_triggers: &PlayerCallbackList,
+    leave_triggers: &PlayerCallbackList,
+    unload_hooks: &HookList,
+    load_complete_hooks: &HookList,
+    exports: &PluginExports,
+    configs: &PluginConfigs,
+    vms: &PluginVms,
+) -> mlua::Result<()> {
+    let name = loaded_plugin_name(plugins, dirname)?;
+    warn_dependents(plugins, dirname, "they keep its old export");
+
+    kill_children_where(children, |_, c| c.plugin == dirname);
+    drop(take_plugins(
+        lua,
+        |p| p == dirname,
+        triggers,
+        stop_triggers,
+        crash_triggers,
+        plugins,
+        lifecycle_events,
+        cron_jobs,
+        timers,
+        join_triggers,
+        leave_triggers,
+        unload_hooks,
+        load_complete_hooks,
+        exports,
+        configs,
+        vms,
+    )?);
+    lua.expire_registry_values();
+
+    tprintln!("[MCRW] Unloaded plugin: {} (dir: {})", name, dirname);
     Ok(())
 }
 
+fn loaded_plugin_name(plugins: &PluginRegistry, dirname: &str) -> mlua::Result<String> {
+    plugins
+        .lock()
+        .unwrap()
+        .get(dirname)
+        .map(|m| m.name.clone())
+        .ok_or_else(|| mlua::Error::external(format!("plugin '{dirname}' is not loaded")))
+}
+
+fn warn_dependents(plugins: &PluginRegistry, dirname: &str, consequence: &str) {
+    let dependents = loaded_dependents(&plugins.lock().unwrap(), dirname);
+    if !dependents.is_empty() {
+        teprintln!(
+            "[MCRW] [WARNING] still loaded and depending on '{}': {} ({})",
+            dirname,
+            dependents.join(", "),
+            consequence
+        );
+    }
+}
+
+// `!load <dir>`: load one plugin that is not currently loaded. Its
+// dependencies must already be loaded; nothing else is touched.
+pub fn load_plugin(
+    lua: &Lua,
+    dirname: &str,
+    plugins: &PluginRegistry,
+    vms: &PluginVms,
+    force_incompatible: bool,
+) -> mlua::Result<()> {
+    let fail = |msg: String| Err(mlua::Error::external(msg));
+    if dirname.is_empty() || dirname.contains(['/', '\\', '.']) {
+        return fail(format!("invalid plugin directory name '{dirname}'"));
+    }
+    if plugins.lock().unwrap().contains_key(dirname) {
+        return fail(format!(
+            "plugin '{dirname}' is already loaded (use !reload {dirname})"
+        ));
+    }
+    let plugin_dir = Path::new("lua_plugins").join(dirname);
+    if !plugin_dir.join("init.lua").exists() {
+        return fail(format!("no lua_plugins/{dirname}/init.lua"));
+    }
+    if load_plugin_state(Path::new(PLUGIN_STATE_PATH)).is_disabled(dirname) {
+        return fail(format!(
+            "plugin '{dirname}' is disabled (use !plugin enable {dirname})"
+        ));
+    }
+    let meta = read_plugin_meta(&plugin_dir).map_err(mlua::Error::external)?;
+    gate_mcrw_version(dirname, &meta, force_incompatible).map_err(mlua::Error::external)?;
+    check_lib_requirements(Path::new(LIBS_DIR), &meta.libs).map_err(mlua::Error::external)?;
+    let missing = {
+        let loaded = plugins.lock().unwrap();
+        meta.dependencies
+            .iter()
+            .find(|d| resolve_plugin_name(&loaded, d).is_none())
+            .cloned()
+    };
+    if let Some(dep) = missing {
+        return fail(format!("dependency '{dep}' is not loaded"));
+    }
+    require_plugin(lua, plugins, vms, dirname, &meta)
+}
+
+// `!reload <plugin>`: re-run one plugin's init.lua, leaving every other plugin
+// alone. The old instance is taken out rather than destroyed; if the new one
+// fails to initialize, whatever it registered is discarded and the old one is
+// put back, so a typo never leaves the plugin unloaded. The old instance's
+// python children are only killed once the new one is in. Dependents keep
+// the table they imported from the old instance until reloaded themselves.
+#[allow(clippy::too_many_arguments)]
+pub fn reload_plugin(
+    lua: &Lua,
+    dirname: &str,
+    triggers: &TriggerList,
+    stop_triggers: &StopTriggerList,
+    crash_triggers: &CrashTriggerList,
+    plugins: &PluginRegistry,
+    lifecycle_events: &LifecycleEvents,
+    children: &ChildTracker,
+    cron_jobs: &CronJobList,
+    timers: &TimerList,
+    join_triggers: &PlayerCallbackList,
+    leave_triggers: &PlayerCallbackList,
+    unload_hooks: &HookList,
+    load_complete_hooks: &HookList,
+    exports: &PluginExports,
+    configs: &PluginConfigs,
+    vms: &PluginVms,
+    force_incompatible: bool,
+) -> mlua::Result<()> {
+    loaded_plugin_name(plugins, dirname)?;
+    let old_children: Vec<u64> = children
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, c)| c.plugin == dirname)
+        .map(|(id, _)| *id)
+        .collect();
+    let old = take_plugins(
+        lua,
+        |p| p == dirname,
+        triggers,
+        stop_triggers,
+        crash_triggers,
+        plugins,
+        lifecycle_events,
+        cron_jobs,
+        timers,
+        join_triggers,
+        leave_triggers,
+        unload_hooks,
+        load_complete_hooks,
+        exports,
+        configs,
+        vms,
+    )?;
+
+    match load_plugin(lua, dirname, plugins, vms, force_incompatible) {
+        Ok(()) => {
+            // Only the previous instance's children; the new one may already
+            // have spawned its own during init.
+            kill_children_where(children, |id, _| old_children.contains(&id));
+            drop(old);
+            lua.expire_registry_values();
+            warn_dependents(
+                plugins,
+                dirname,
+                "they keep the previous export until reloaded",
+            );
+            Ok(())
+        }
+        Err(e) => {
+            // require_plugin already discarded what the new instance added.
+            restore_plugins(
+                lua,
+                old,
+                triggers,
+                stop_triggers,
+                crash_triggers,
+                plugins,
+                lifecycle_events,
+                cron_jobs,
+                timers,
+                join_triggers,
+                leave_triggers,
+                unload_hooks,
+                load_complete_hooks,
+                exports,
+                configs,
+                vms,
+            )?;
+            lua.expire_registry_values();
+            teprintln!(
+                "[MCRW] [WARNING] kept the previous instance of '{}' running",
+                dirname
+            );
+            Err(e)
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // Tests
 // ---------------------------------------------------------------------------
@@ -1575,6 +3503,42 @@ mod tests {
         assert_eq!(parsed.rcon.port, def.rcon.port);
         assert_eq!(parsed.rcon.timeout_ms, def.rcon.timeout_ms);
         assert_eq!(parsed.server.java, def.server.java);
+        assert_eq!(parsed.reload.critical, def.reload.critical);
+        assert_eq!(
+            parsed.reload.unload_timeout_ms,
+            def.reload.unload_timeout_ms
+        );
+        assert_eq!(parsed.quarantine, def.quarantine);
+        assert_eq!(parsed.dev.watch, def.dev.watch);
+        assert_eq!(parsed.dev.debounce_ms, def.dev.debounce_ms);
+    }
+
+    // Edits to timeouts and player patterns apply live; the rest, permissions
+    // included, is reported and keeps its startup value.
+    #[test]
+    fn hot_settings_apply_only_the_safe_subset() {
+        let current = McrwConfig::default();
+        let new: McrwConfig = toml::from_str(
+            "[server]\njava = \"/opt/java\"\n[http]\ndefault_timeout_ms = 5\n\
+             [players]\njoin_pattern = 'J (\\w+)'\n[dev]\nwatch = true\n\
+             [permissions]\nenforce = true\n",
+        )
+        .unwrap();
+        let hot = merge_hot_settings(&current, &new);
+        assert_eq!(
+            hot.applied,
+            ["http.default_timeout_ms", "players.join_pattern"]
+        );
+        assert_eq!(
+            hot.restart,
+            ["server.java", "dev.watch", "permissions.enforce"]
+        );
+        assert!(!hot.config.permissions.enforce);
+        assert_eq!(hot.config.http.default_timeout_ms, 5);
+        assert_eq!(hot.config.players.join_pattern.as_deref(), Some("J (\\w+)"));
+        assert_eq!(hot.config.server.java, "java");
+        assert!(!hot.config.dev.watch);
+        assert!(merge_hot_settings(&current, &current).applied.is_empty());
     }
 
     // A mcrw.toml that predates the [server] section must still load, with the
@@ -1618,4 +3582,564 @@ mod tests {
         let _ = load_mcrw_config(&path);
         let _ = fs::remove_file(&path);
     }
+
+    fn meta(name: &str, deps: &[&str]) -> PluginMeta {
+        PluginMeta {
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+            description: String::new(),
+            authors: Vec::new(),
+            dependencies: deps.iter().map(|d| d.to_string()).collect(),
+            mcrw_version: String::new(),
+            permissions: Vec::new(),
+            isolation: Isolation::Shared,
+            libs: BTreeMap::new(),
+        }
+    }
+
+    fn order_of(candidates: Vec<(String, PluginMeta)>) -> (Vec<String>, Vec<(String, String)>) {
+        let (ordered, skipped) = resolve_load_order(candidates, &[]);
+        (ordered.into_iter().map(|(dir, _)| dir).collect(), skipped)
+    }
+
+    // Dependencies load first; independent plugins keep alphabetical order.
+    #[test]
+    fn load_order_puts_dependencies_first() {
+        let (order, skipped) = order_of(vec![
+            ("zeta".into(), meta("zeta", &[])),
+            ("app".into(), meta("app", &["lib"])),
+            ("lib".into(), meta("lib", &["base"])),
+            ("base".into(), meta("base", &[])),
+        ]);
+        assert_eq!(order, ["base", "lib", "app", "zeta"]);
+        assert!(skipped.is_empty());
+    }
+
+    // A dependency may name a plugin by its meta.toml name instead of its dir.
+    #[test]
+    fn load_order_resolves_meta_names() {
+        let (order, _) = order_of(vec![
+            ("a_app".into(), meta("app", &["Economy"])),
+            ("eco".into(), meta("Economy", &[])),
+        ]);
+        assert_eq!(order, ["eco", "a_app"]);
+    }
+
+    // A missing dependency skips the plugin and everything depending on it.
+    #[test]
+    fn load_order_skips_missing_dependency_chain() {
+        let (order, skipped) = order_of(vec![
+            ("app".into(), meta("app", &["lib"])),
+            ("lib".into(), meta("lib", &["ghost"])),
+            ("ok".into(), meta("ok", &[])),
+        ]);
+        assert_eq!(order, ["ok"]);
+        assert_eq!(
+            skipped[0],
+            ("app".into(), "dependency 'lib' was skipped".into())
+        );
+        assert_eq!(
+            skipped[1],
+            ("lib".into(), "missing dependency 'ghost'".into())
+        );
+    }
+
+    // Cycles are reported with their path; dependents of a cycle are skipped.
+    #[test]
+    fn load_order_reports_cycles() {
+        let (order, skipped) = order_of(vec![
+            ("a".into(), meta("a", &["b"])),
+            ("b".into(), meta("b", &["a"])),
+            ("c".into(), meta("c", &["a"])),
+            ("self".into(), meta("self", &["self"])),
+        ]);
+        assert!(order.is_empty());
+        assert_eq!(skipped[0].1, "dependency cycle: a -> b -> a");
+        assert_eq!(skipped[1].1, "dependency cycle: b -> a -> b");
+        assert_eq!(skipped[2].1, "dependency 'a' is part of a dependency cycle");
+        assert_eq!(skipped[3].1, "dependency cycle: self -> self");
+    }
+
+    // mcrw_version is a semver requirement checked against CARGO_PKG_VERSION.
+    #[test]
+    fn mcrw_version_requirement_checks() {
+        let current = env!("CARGO_PKG_VERSION");
+        assert!(check_mcrw_version("").is_ok());
+        assert!(check_mcrw_version(&format!(">={current}")).is_ok());
+        assert!(check_mcrw_version(&format!("={current}")).is_ok());
+        assert!(
+            check_mcrw_version(">=999.0.0")
+                .unwrap_err()
+                .contains("requires")
+        );
+        assert!(
+            check_mcrw_version("not a version")
+                .unwrap_err()
+                .contains("invalid")
+        );
+    }
+
+    // The capability list is probed by name, so duplicates would only hide
+    // a copy-paste mistake when a method is added.
+    #[test]
+    fn features_are_unique() {
+        let mut seen = HashSet::new();
+        for f in WRAPPER.methods.iter().map(|m| m.name) {
+            assert!(seen.insert(f), "duplicate feature '{f}'");
+        }
+        assert!(WRAPPER.method("rcon_command").is_some());
+    }
+
+    // Disabled plugins are skipped with a fixed reason, and their dependents
+    // say the dependency is disabled rather than missing.
+    #[test]
+    fn load_order_skips_disabled_and_dependents() {
+        let (ordered, skipped) = resolve_load_order(
+            vec![
+                ("eco".into(), meta("eco", &[])),
+                ("shop".into(), meta("shop", &["eco"])),
+                ("motd".into(), meta("motd", &[])),
+            ],
+            &["eco".to_string()],
+        );
+        assert_eq!(ordered.len(), 1);
+        assert_eq!(ordered[0].0, "motd");
+        assert_eq!(skipped[0], ("eco".into(), DISABLED_REASON.into()));
+        assert_eq!(
+            skipped[1],
+            ("shop".into(), "dependency 'eco' is disabled".into())
+        );
+    }
+
+    // Unloading one plugin removes exactly its registrations, export and
+    // package.loaded entries; another plugin's entries are untouched.
+    #[test]
+    fn unload_plugin_removes_only_its_registrations() {
+        let lua = Lua::new();
+        let key = || {
+            let f = lua.create_function(|_, ()| Ok(())).unwrap();
+            lua.create_registry_value(f).unwrap()
+        };
+        let triggers: TriggerList = Arc::new(Mutex::new(Vec::new()));
+        let stop: StopTriggerList = Arc::new(Mutex::new(Vec::new()));
+        let crash: CrashTriggerList = Arc::new(Mutex::new(Vec::new()));
+        let plugins: PluginRegistry = Arc::new(Mutex::new(HashMap::new()));
+        let lifecycle: LifecycleEvents = Arc::new(Mutex::new(HashMap::new()));
+        let children: ChildTracker = Arc::new(Mutex::new(HashMap::new()));
+        let cron: CronJobList = Arc::new(Mutex::new(Vec::new()));
+        let timers: TimerList = Arc::default();
+        let join: PlayerCallbackList = Arc::new(Mutex::new(Vec::new()));
+        let leave: PlayerCallbackList = Arc::new(Mutex::new(Vec::new()));
+        let exports: PluginExports = Arc::new(Mutex::new(HashMap::new()));
+        let unload_hooks: HookList = Arc::new(Mutex::new(Vec::new()));
+        let complete_hooks: HookList = Arc::new(Mutex::new(Vec::new()));
+        let configs: PluginConfigs = Arc::new(Mutex::new(HashMap::new()));
+        let vms: PluginVms = Arc::new(Mutex::new(HashMap::new()));
+
+        let regex = Regex::new("x").unwrap();
+        for dir in ["a", "ab"] {
+            plugins.lock().unwrap().insert(dir.into(), meta(dir, &[]));
+            triggers.lock().unwrap().push(Trigger {
+                regex: regex.clone(),
+                filter: None,
+                callback: key(),
+                plugin: dir.into(),
+            });
+            stop.lock().unwrap().push(StopTrigger {
+                callback: key(),
+                plugin: dir.into(),
+            });
+            join.lock().unwrap().push(PluginCallback {
+                callback: key(),
+                plugin: dir.into(),
+            });
+            unload_hooks.lock().unwrap().push(PluginCallback {
+                callback: key(),
+                plugin: dir.into(),
+            });
+            lifecycle
+                .lock()
+                .unwrap()
+                .entry("start".into())
+                .or_insert_with(|| LifecycleEventState {
+                    patterns: Vec::new(),
+                    callbacks: Vec::new(),
+                })
+                .callbacks
+                .push(PluginCallback {
+                    callback: key(),
+                    plugin: dir.into(),
+                });
+            let api = lua.create_table().unwrap();
+            exports
+                .lock()
+                .unwrap()
+                .insert(dir.into(), lua.create_registry_value(api).unwrap());
+        }
+        let loaded: Table = lua
+            .globals()
+            .get::<Table>("package")
+            .unwrap()
+            .get("loaded")
+            .unwrap();
+        for k in ["lua_plugins.a.", "lua_plugins.a.util", "lua_plugins.ab."] {
+            loaded.set(k, true).unwrap();
+        }
+
+        let unload = |dir: &str| {
+            unload_plugin(
+                &lua,
+                dir,
+                &triggers,
+                &stop,
+                &crash,
+                &plugins,
+                &lifecycle,
+                &children,
+                &cron,
+                &timers,
+                &join,
+                &leave,
+                &unload_hooks,
+                &complete_hooks,
+                &exports,
+                &configs,
+                &vms,
+            )
+        };
+        unload("a").unwrap();
+
+        assert!(!plugins.lock().unwrap().contains_key("a"));
+        assert!(plugins.lock().unwrap().contains_key("ab"));
+        assert!(triggers.lock().unwrap().iter().all(|t| t.plugin == "ab"));
+        assert_eq!(triggers.lock().unwrap().len(), 1);
+        assert_eq!(stop.lock().unwrap().len(), 1);
+        assert_eq!(join.lock().unwrap().len(), 1);
+        assert_eq!(unload_hooks.lock().unwrap().len(), 1);
+        assert_eq!(lifecycle.lock().unwrap()["start"].callbacks.len(), 1);
+        assert!(!exports.lock().unwrap().contains_key("a"));
+        assert!(exports.lock().unwrap().contains_key("ab"));
+        assert!(loaded.get::<Value>("lua_plugins.a.").unwrap().is_nil());
+        assert!(loaded.get::<Value>("lua_plugins.a.util").unwrap().is_nil());
+        assert!(!loaded.get::<Value>("lua_plugins.ab.").unwrap().is_nil());
+
+        // A second unload reports the plugin as not loaded.
+        assert!(unload("a").unwrap_err().to_string().contains("not loaded"));
+    }
+
+    // A shared Lua state with the Server global, whose package.path finds
+    // lua_plugins/<dir>/init.lua under a fresh temp dir.
+    fn plugin_sandbox(tag: &str, plugins: &[(&str, &str)]) -> (Lua, ServerApi) {
+        let root = std::env::temp_dir().join(format!("mcrw_lua_ctx_test_{tag}"));
+        let _ = fs::remove_dir_all(&root);
+        for (dir, source) in plugins {
+            let dir = root.join("lua_plugins").join(dir);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("init.lua"), source).unwrap();
+        }
+        let (tx, _) = mpsc::channel(1);
+        let server = ServerApi {
+            triggers: Arc::default(),
+            stop_triggers: Arc::default(),
+            crash_triggers: Arc::default(),
+            plugins: Arc::default(),
+            lifecycle_events: Arc::default(),
+            mcrw_config: Arc::default(),
+            children: Arc::default(),
+            next_child_id: Arc::default(),
+            cmd_tx: tx.clone(),
+            cron_jobs: Arc::default(),
+            timers: Arc::default(),
+            line_waiters: Arc::default(),
+            query_locks: Arc::default(),
+            server_info: Arc::default(),
+            http_client: reqwest::Client::new(),
+            player_registry: Arc::new(PlayerRegistry::new(
+                &PlayersConfig::default(),
+                Flavor::Auto,
+                tx,
+                root.join("players.json"),
+            )),
+            join_triggers: Arc::default(),
+            leave_triggers: Arc::default(),
+            unload_hooks: Arc::default(),
+            load_complete_hooks: Arc::default(),
+            rcon: None,
+            store: Arc::new(StoreRegistry::new(root.join("store.json"))),
+            metrics: Arc::default(),
+            exports: Arc::default(),
+            configs: Arc::default(),
+        };
+        let lua = Lua::new();
+        let path = format!("{0}/?.lua;{0}/?/init.lua", root.display());
+        lua.globals()
+            .get::<Table>("package")
+            .unwrap()
+            .set("path", path)
+            .unwrap();
+        lua.globals().set("Server", server.clone()).unwrap();
+        (lua, server)
+    }
+
+    // A plugin whose init.lua throws leaves nothing behind: no registry entry
+    // and none of the registrations it made before the error.
+    #[test]
+    fn failed_load_discards_registrations() {
+        let (lua, server) = plugin_sandbox(
+            "failed_load",
+            &[(
+                "broken",
+                "local wrapper = Server:get_context(...)\n\
+                 wrapper:register('x', function() end)\n\
+                 error('boom')\n",
+            )],
+        );
+        let vms: PluginVms = Arc::default();
+        let err = require_plugin(&lua, &server.plugins, &vms, "broken", &meta("broken", &[]))
+            .unwrap_err();
+        assert!(err.to_string().contains("boom"));
+        assert!(server.triggers.lock().unwrap().is_empty());
+        assert!(server.plugins.lock().unwrap().is_empty());
+    }
+
+    // An isolated plugin's state goes with its failed load, so the next
+    // attempt starts without orphaned callbacks pointing into it.
+    #[test]
+    fn failed_isolated_load_drops_its_state() {
+        let (lua, server) = plugin_sandbox(
+            "failed_isolated",
+            &[(
+                "broken",
+                "local wrapper = Server:get_context(...)\n\
+                 wrapper:register('x', function() end)\n\
+                 error('boom')\n",
+            )],
+        );
+        let vms: PluginVms = Arc::default();
+        let mut isolated = meta("broken", &[]);
+        isolated.isolation = Isolation::Isolated;
+        assert!(require_plugin(&lua, &server.plugins, &vms, "broken", &isolated).is_err());
+        assert!(vms.lock().unwrap().is_empty());
+        assert!(server.triggers.lock().unwrap().is_empty());
+    }
+
+    // take_plugins followed by restore_plugins is lossless: the failed-reload
+    // path relies on it to put the previous instance back.
+    #[test]
+    fn take_then_restore_roundtrips() {
+        let lua = Lua::new();
+        let f = lua.create_function(|_, ()| Ok(())).unwrap();
+        let triggers: TriggerList = Arc::new(Mutex::new(Vec::new()));
+        let stop: StopTriggerList = Arc::new(Mutex::new(Vec::new()));
+        let crash: CrashTriggerList = Arc::new(Mutex::new(Vec::new()));
+        let plugins: PluginRegistry = Arc::new(Mutex::new(HashMap::new()));
+        let lifecycle: LifecycleEvents = Arc::new(Mutex::new(HashMap::new()));
+        let cron: CronJobList = Arc::new(Mutex::new(Vec::new()));
+        let timers: TimerList = Arc::default();
+        let join: PlayerCallbackList = Arc::new(Mutex::new(Vec::new()));
+        let leave: PlayerCallbackList = Arc::new(Mutex::new(Vec::new()));
+        let exports: PluginExports = Arc::new(Mutex::new(HashMap::new()));
+        let unload_hooks: HookList = Arc::new(Mutex::new(Vec::new()));
+        let complete_hooks: HookList = Arc::new(Mutex::new(Vec::new()));
+        let configs: PluginConfigs = Arc::new(Mutex::new(HashMap::new()));
+        let vms: PluginVms = Arc::new(Mutex::new(HashMap::new()));
+
+        plugins
+            .lock()
+            .unwrap()
+            .insert("eco".into(), meta("eco", &[]));
+        vms.lock().unwrap().insert("eco".into(), Lua::new());
+        for plugin in ["a", "eco", "b"] {
+            crash.lock().unwrap().push(CrashTrigger {
+                callback: lua.create_registry_value(f.clone()).unwrap(),
+                plugin: plugin.into(),
+            });
+        }
+        leave.lock().unwrap().push(PluginCallback {
+            callback: lua.create_registry_value(f.clone()).unwrap(),
+            plugin: "eco".into(),
+        });
+        complete_hooks.lock().unwrap().push(PluginCallback {
+            callback: lua.create_registry_value(f.clone()).unwrap(),
+            plugin: "eco".into(),
+        });
+        configs
+            .lock()
+            .unwrap()
+            .insert("eco".into(), PluginConfigState::default());
+        let timer = timers.add(
+            "eco",
+            lua.create_registry_value(f).unwrap(),
+            Duration::from_secs(60),
+            None,
+        );
+        let loaded: Table = lua
+            .globals()
+            .get::<Table>("package")
+            .unwrap()
+            .get("loaded")
+            .unwrap();
+        loaded.set("lua_plugins.eco.", "module").unwrap();
+
+        let take = || {
+            take_plugins(
+                &lua,
+                |p| p == "eco",
+                &triggers,
+                &stop,
+                &crash,
+                &plugins,
+                &lifecycle,
+                &cron,
+                &timers,
+                &join,
+                &leave,
+                &unload_hooks,
+                &complete_hooks,
+                &exports,
+                &configs,
+                &vms,
+            )
+            .unwrap()
+        };
+        let taken = take();
+        assert!(plugins.lock().unwrap().is_empty());
+        assert_eq!(crash.lock().unwrap().len(), 2);
+        assert!(leave.lock().unwrap().is_empty());
+        assert!(complete_hooks.lock().unwrap().is_empty());
+        assert!(configs.lock().unwrap().is_empty());
+        assert!(vms.lock().unwrap().is_empty());
+        assert!(!timers.is_pending(timer));
+        assert!(loaded.get::<Value>("lua_plugins.eco.").unwrap().is_nil());
+
+        restore_plugins(
+            &lua,
+            taken,
+            &triggers,
+            &stop,
+            &crash,
+            &plugins,
+            &lifecycle,
+            &cron,
+            &timers,
+            &join,
+            &leave,
+            &unload_hooks,
+            &complete_hooks,
+            &exports,
+            &configs,
+            &vms,
+        )
+        .unwrap();
+        assert!(plugins.lock().unwrap().contains_key("eco"));
+        // Back in its old place, between the plugins that stayed.
+        let order: Vec<String> = crash
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|t| t.plugin.clone())
+            .collect();
+        assert_eq!(order, ["a", "eco", "b"]);
+        assert_eq!(leave.lock().unwrap().len(), 1);
+        assert_eq!(complete_hooks.lock().unwrap().len(), 1);
+        assert!(configs.lock().unwrap().contains_key("eco"));
+        assert!(vms.lock().unwrap().contains_key("eco"));
+        assert!(timers.is_pending(timer));
+        assert_eq!(loaded.get::<String>("lua_plugins.eco.").unwrap(), "module");
+    }
+
+    // With no critical list every plugin that was running and fails now blocks
+    // the reload; with one, only the listed plugins do. A plugin that was not
+    // running before never blocks.
+    #[test]
+    fn blocking_failures_respect_critical_list() {
+        let report = LoadReport {
+            disabled: Vec::new(),
+            failed: vec![
+                ("motd".into(), "boom".into()),
+                ("eco".into(), "missing dependency 'bank'".into()),
+                ("broken".into(), "missing dependency 'gone'".into()),
+            ],
+        };
+        let was_loaded = ["motd", "eco", "shop"];
+        assert_eq!(blocking_failures(&report, &was_loaded, &[]).len(), 2);
+        let critical = ["eco".to_string()];
+        let blocking = blocking_failures(&report, &was_loaded, &critical);
+        assert_eq!(blocking.len(), 1);
+        assert_eq!(blocking[0].0, "eco");
+        assert!(blocking_failures(&report, &was_loaded, &["shop".to_string()]).is_empty());
+        assert!(blocking_failures(&report, &was_loaded, &["broken".to_string()]).is_empty());
+    }
+
+    // Unload hooks run only for the selected plugins, receive the reason, and
+    // one that never returns is cut off at the timeout.
+    #[tokio::test]
+    async fn unload_hooks_get_reason_and_time_out() {
+        let lua = Lua::new();
+        let hooks: HookList = Arc::new(Mutex::new(Vec::new()));
+        let record = lua
+            .load("seen = {}; return function(reason) table.insert(seen, reason) end")
+            .eval::<Function>()
+            .unwrap();
+        let stuck = lua
+            .create_async_function(|_, _reason: String| async {
+                tokio::time::sleep(std::time::Duration::from_secs(3600)).await;
+                Ok(())
+            })
+            .unwrap();
+        for (dir, f) in [("a", &stuck), ("b", &record), ("c", &record)] {
+            hooks.lock().unwrap().push(PluginCallback {
+                callback: lua.create_registry_value(f.clone()).unwrap(),
+                plugin: dir.into(),
+            });
+        }
+
+        let started = std::time::Instant::now();
+        let vms: PluginVms = Arc::new(Mutex::new(HashMap::new()));
+        let metrics = MetricsRegistry::default();
+        run_unload_hooks(&lua, &vms, &hooks, &metrics, |p| p != "c", "unload", 50).await;
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        let seen: Vec<String> = lua.globals().get("seen").unwrap();
+        assert_eq!(seen, ["unload"]);
+        // The timeout counts as a failed call.
+        let stuck = &metrics.snapshot(Some("a"))[0];
+        assert_eq!(
+            (stuck.kind, stuck.key.as_str(), stuck.errors),
+            (Kind::Unload, "unload", 1)
+        );
+    }
+
+    // A callback registered in an isolated plugin's state is looked up there;
+    // everything else resolves against the shared state.
+    #[test]
+    fn callbacks_resolve_in_their_plugins_state() {
+        let shared = Lua::new();
+        let isolated = Lua::new();
+        let vms: PluginVms = Arc::new(Mutex::new(HashMap::new()));
+        vms.lock().unwrap().insert("solo".into(), isolated.clone());
+
+        isolated.globals().set("who", "solo").unwrap();
+        shared.globals().set("who", "shared").unwrap();
+        let f: Function = isolated
+            .load("return function() return who end")
+            .eval()
+            .unwrap();
+        let key = isolated.create_registry_value(f).unwrap();
+
+        let owner = plugin_lua(&shared, &vms, "solo");
+        let f: Function = owner.registry_value(&key).unwrap();
+        assert_eq!(f.call::<String>(()).unwrap(), "solo");
+        assert!(shared.registry_value::<Function>(&key).is_err());
+        let other = plugin_lua(&shared, &vms, "motd");
+        assert_eq!(other.globals().get::<String>("who").unwrap(), "shared");
+    }
+
+    #[test]
+    fn isolation_defaults_to_shared() {
+        let m: PluginMeta = toml::from_str("name = \"a\"\nversion = \"1\"").unwrap();
+        assert_eq!(m.isolation, Isolation::Shared);
+        let m: PluginMeta =
+            toml::from_str("name = \"a\"\nversion = \"1\"\nisolation = \"isolated\"").unwrap();
+        assert_eq!(m.isolation, Isolation::Isolated);
+    }
 }
@@ -14,21 +14,35 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod api_spec;
+mod breaker;
+mod flavor;
 mod handler;
+mod libs;
+mod log_line;
 mod lua_ctx;
+mod metrics;
+mod pack;
+mod permissions;
 mod players;
+mod plugin_config;
+mod plugin_state;
 mod rcon;
 mod scaffold;
+mod server_info;
 mod store;
 mod term;
+mod timers;
 mod utils;
+mod waiters;
+mod watch;
 
 use lua_ctx::TriggerList;
 use mlua::Lua;
 use rustyline::DefaultEditor;
 use std::collections::HashMap;
 use std::env;
-use std::io::IsTerminal;
+use std::io::{self, IsTerminal};
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
 use std::sync::atomic::AtomicU64;
@@ -37,21 +51,38 @@ use tokio::process::Command;
 use tokio::sync::mpsc;
 
 use crate::lua_ctx::{
-    ChildIdCounter, ChildTracker, ControlMsg, CrashTriggerList, CronJobList, LifecycleEvents,
-    PlayerCallbackList, PluginRegistry, ServerApi, StopTriggerList,
+    ChildIdCounter, ChildTracker, ControlMsg, CrashTriggerList, CronJobList, HookList,
+    LifecycleEvents, PlayerCallbackList, PluginConfigs, PluginExports, PluginRegistry, PluginVms,
+    ServerApi, SharedMcrwConfig, StopTriggerList,
 };
 use crate::players::PlayerRegistry;
+use crate::plugin_state::{PLUGIN_STATE_PATH, load_plugin_state};
+use crate::server_info::{ServerInfo, SharedServerInfo};
+use crate::timers::TimerList;
+use crate::waiters::{LineWaiters, QueryLocks};
 
 #[tokio::main]
 async fn main() {
-    let server_args: Vec<String> = env::args().collect();
+    let mut server_args: Vec<String> = env::args().collect();
 
     // Subcommands are intercepted before any server/Lua/RCON setup. `init`
-    // scaffolds a new plugin and exits; anything else is treated as java args.
-    if server_args.get(1).map(String::as_str) == Some("init") {
-        std::process::exit(run_init_command(&server_args));
+    // scaffolds a new plugin, `pack`/`install`/`uninstall` manage packaged
+    // plugins, `gen-types` prints the LuaLS stubs; each exits. Anything else
+    // is treated as java args.
+    match server_args.get(1).map(String::as_str) {
+        Some("init") => std::process::exit(run_init_command(&server_args)),
+        Some("gen-types") => std::process::exit(run_gen_types_command(&server_args)),
+        Some("pack") => std::process::exit(run_pack_command(&server_args)),
+        Some("install") => std::process::exit(run_install_command(&server_args)),
+        Some("uninstall") => std::process::exit(run_uninstall_command(&server_args)),
+        _ => {}
     }
 
+    // Wrapper-only flag: load plugins whose `mcrw_version` excludes this build.
+    // Stripped here so it never reaches the java command line.
+    let force_incompatible = server_args.iter().any(|a| a == "--force-incompatible");
+    server_args.retain(|a| a != "--force-incompatible");
+
     utils::print_logo();
     let max_cmd_queue = 1000;
 
@@ -61,14 +92,21 @@ async fn main() {
     let stop_triggers: StopTriggerList = Arc::new(Mutex::new(Vec::new()));
     let crash_triggers: CrashTriggerList = Arc::new(Mutex::new(Vec::new()));
     let plugins: PluginRegistry = Arc::new(Mutex::new(HashMap::new()));
-    let trigger_cfg = lua_ctx::load_trigger_config(Path::new("trigger_config.toml"));
+    let mcrw_config = lua_ctx::load_mcrw_config(Path::new("mcrw.toml"));
+    let trigger_cfg =
+        lua_ctx::load_trigger_config(Path::new("trigger_config.toml"), mcrw_config.server.flavor);
     let lifecycle_events: LifecycleEvents = Arc::new(Mutex::new(
         lua_ctx::compile_trigger_config(trigger_cfg),
     ));
-    let mcrw_config = lua_ctx::load_mcrw_config(Path::new("mcrw.toml"));
     let children: ChildTracker = Arc::new(Mutex::new(HashMap::new()));
     let next_child_id: ChildIdCounter = Arc::new(AtomicU64::new(1));
     let cron_jobs: CronJobList = Arc::new(Mutex::new(Vec::new()));
+    let timers: TimerList = Arc::default();
+    let line_waiters: LineWaiters = Arc::default();
+    let server_info: SharedServerInfo = Arc::new(Mutex::new(ServerInfo::new(
+        mcrw_config.server.flavor,
+        &rcon::read_server_properties(),
+    )));
 
     // init game command channel — created here (ahead of ServerApi) so the
     // sender can be cloned into ServerApi for the new wrapper:command API.
@@ -90,6 +128,7 @@ async fn main() {
     });
     let mut registry = PlayerRegistry::new(
         &mcrw_config.players,
+        mcrw_config.server.flavor,
         tx.clone(),
         PathBuf::from(".mcrw/players.json"),
     );
@@ -99,11 +138,22 @@ async fn main() {
     let player_registry = Arc::new(registry);
     let join_triggers: PlayerCallbackList = Arc::new(Mutex::new(Vec::new()));
     let leave_triggers: PlayerCallbackList = Arc::new(Mutex::new(Vec::new()));
+    let unload_hooks: HookList = Arc::new(Mutex::new(Vec::new()));
+    let load_complete_hooks: HookList = Arc::new(Mutex::new(Vec::new()));
+    let exports: PluginExports = Arc::new(Mutex::new(HashMap::new()));
+    let configs: PluginConfigs = Arc::new(Mutex::new(HashMap::new()));
+    let vms: PluginVms = Arc::new(Mutex::new(HashMap::new()));
+    // Live view of mcrw.toml; the file watcher swaps in edits to the safe subset.
+    let shared_config: SharedMcrwConfig = Arc::new(Mutex::new(mcrw_config.clone()));
 
     // Persistent KV store for plugins (wrapper:store). Loaded once, shared, and —
     // like the player registry and HTTP client — held on the persistent Server
     // global so stored data survives !reload. Flushed on shutdown (below).
     let store = Arc::new(store::StoreRegistry::new(PathBuf::from(".mcrw/store.json")));
+    // Per-plugin callback timings for `!profile` / wrapper:stats().
+    let metrics = Arc::new(metrics::MetricsRegistry::default());
+    // Quarantines plugins whose callbacks keep failing.
+    let breaker = Arc::new(breaker::CircuitBreaker::default());
 
     // Interactive console: when stdin/stdout are a real terminal, run an
     // rustyline line editor (Up/Down history, line editing). Its ExternalPrinter
@@ -146,31 +196,58 @@ async fn main() {
         crash_triggers: crash_triggers.clone(),
         plugins: plugins.clone(),
         lifecycle_events: lifecycle_events.clone(),
-        mcrw_config: mcrw_config.clone(),
+        mcrw_config: shared_config.clone(),
         children: children.clone(),
         next_child_id: next_child_id.clone(),
         cmd_tx: tx.clone(),
         cron_jobs: cron_jobs.clone(),
+        timers: timers.clone(),
+        line_waiters: line_waiters.clone(),
+        query_locks: Arc::new(QueryLocks::default()),
+        server_info: server_info.clone(),
         http_client,
         player_registry: player_registry.clone(),
         join_triggers: join_triggers.clone(),
         leave_triggers: leave_triggers.clone(),
+        unload_hooks: unload_hooks.clone(),
+        load_complete_hooks: load_complete_hooks.clone(),
         rcon: rcon_handle,
         store: store.clone(),
+        metrics: metrics.clone(),
+        exports: exports.clone(),
+        configs: configs.clone(),
     };
     lua.globals()
         .set("Server", server_api)
         .expect("[MCRW] [PANIC] Fail to attach Server to lua");
+    // Loads plugin modules into their restricted environments while
+    // [permissions] enforce is on; a no-op otherwise.
+    permissions::install_plugin_searcher(&lua, plugins.clone(), shared_config.clone())
+        .expect("[MCRW] [PANIC] Fail to install the plugin searcher");
+
+    // wrapper control channel (e.g. `!reload` typed at wrapper terminal)
+    let (ctl_tx, ctl_rx) = mpsc::channel::<ControlMsg>(16);
 
     // load plugins
-    lua_ctx::load_plugins(&lua, &plugins).expect("[MCRW] [PANIC] Fail to load plugins");
-    {
-        let plugins_guard = plugins.lock().unwrap();
-        tprintln!("[MCRW] Loaded {} plugins:", plugins_guard.len());
-        for (dirname, meta) in plugins_guard.iter() {
-            tprintln!("  - {} v{} (dir: {})", meta.name, meta.version, dirname);
-        }
-    }
+    let plugin_state = load_plugin_state(Path::new(PLUGIN_STATE_PATH));
+    let load_report =
+        lua_ctx::load_plugins(&lua, &plugins, &vms, &plugin_state, force_incompatible)
+            .expect("[MCRW] [PANIC] Fail to load plugins");
+    lua_ctx::print_load_summary(&plugins, &load_report);
+    let dispatch = handler::Dispatch {
+        metrics: metrics.clone(),
+        breaker: breaker.clone(),
+        limits: mcrw_config.quarantine.clone(),
+        ctl_tx: ctl_tx.clone(),
+    };
+    lua_ctx::spawn_load_complete_hooks(
+        &lua,
+        &vms,
+        &load_complete_hooks,
+        dispatch,
+        |_| true,
+        "load",
+    );
     tprintln!(
         "[MCRW] Lua script loaded. Registered {} regex triggers, {} stop functions, {} crash functions.",
         triggers.lock().unwrap().len(),
@@ -198,18 +275,30 @@ async fn main() {
     let stdout = child.stdout.take().expect("Failed to open stdout");
     let stdin = child.stdin.take().expect("Failed to open stdin");
 
-    // wrapper control channel (e.g. `!reload` typed at wrapper terminal)
-    let (ctl_tx, ctl_rx) = mpsc::channel::<ControlMsg>(16);
-
     // Command consumer
     handler::spawn_cmd_sender(rx, stdin);
 
+    // Config hot reload (config.json, mcrw.toml) and, with `[dev] watch`, code
+    // hot reload: file changes become control messages like typed `!reload`
+    // commands. A watcher failure only costs the convenience.
+    let debounce = std::time::Duration::from_millis(mcrw_config.dev.debounce_ms);
+    let started = env::current_dir()
+        .map_err(notify::Error::io)
+        .and_then(|base| {
+            watch::spawn_file_watcher(base, debounce, mcrw_config.dev.watch, ctl_tx.clone())
+        });
+    if let Err(e) = started {
+        teprintln!("[MCRW] [ERROR] could not start file watcher ({e}); hot reload disabled");
+    }
+
     // CMD producer: terminal stdin. Interactive → rustyline editor (history);
     // otherwise → plain line reader (headless/piped). The editor gets the
     // server's pid so a force-quit can SIGKILL the child instead of orphaning it.
     match console_editor {
-        Some(editor) => handler::spawn_console_editor(editor, tx.clone(), ctl_tx, child.id()),
-        None => handler::spawn_terminal_receiver(tx.clone(), ctl_tx),
+        Some(editor) => {
+            handler::spawn_console_editor(editor, tx.clone(), ctl_tx.clone(), child.id())
+        }
+        None => handler::spawn_terminal_receiver(tx.clone(), ctl_tx.clone()),
     }
 
     // main loop producer
@@ -223,9 +312,22 @@ async fn main() {
         lifecycle_events.clone(),
         children.clone(),
         cron_jobs.clone(),
+        timers,
+        line_waiters,
+        server_info,
         player_registry.clone(),
         join_triggers.clone(),
         leave_triggers.clone(),
+        unload_hooks.clone(),
+        load_complete_hooks.clone(),
+        exports.clone(),
+        configs.clone(),
+        vms.clone(),
+        force_incompatible,
+        shared_config.clone(),
+        metrics.clone(),
+        breaker,
+        ctl_tx,
         ctl_rx,
         &lua,
     )
@@ -235,24 +337,73 @@ async fn main() {
 
     handler::check_shutdown(
         &lua,
+        &vms,
         child,
         stop_triggers.clone(),
         crash_triggers.clone(),
         player_registry.clone(),
         store.clone(),
+        metrics.clone(),
+    )
+    .await;
+
+    // Last chance for plugins to persist in-memory state; flush again so what
+    // the unload hooks wrote reaches disk.
+    let unload_timeout_ms = shared_config.lock().unwrap().reload.unload_timeout_ms;
+    lua_ctx::run_unload_hooks(
+        &lua,
+        &vms,
+        &unload_hooks,
+        &metrics,
+        |_| true,
+        "shutdown",
+        unload_timeout_ms,
     )
     .await;
+    store.flush();
 }
 
-/// Handle `mcrstw init <name>`. Returns the process exit code. Plugins are
-/// scaffolded under `lua_plugins/` relative to the current working directory,
-/// matching where the wrapper loads them from at runtime.
+/// Handle `mcrstw init <name> [--template <template>]`. Returns the process
+/// exit code. Plugins are scaffolded under `lua_plugins/` relative to the
+/// current working directory, matching where the wrapper loads them from at
+/// runtime. On a terminal, asks for the description and authors first.
 fn run_init_command(args: &[String]) -> i32 {
-    let Some(name) = args.get(2) else {
-        teprintln!("[MCRW] [ERROR] usage: mcrstw init <name>");
-        return 2;
+    let usage = || {
+        teprintln!("[MCRW] [ERROR] usage: mcrstw init <name> [--template <template>]");
+        2
+    };
+    let mut name = None;
+    let mut opts = scaffold::InitOptions {
+        user_templates: scaffold::user_templates_dir(),
+        ..Default::default()
+    };
+    let mut rest = args[2..].iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--template" => match rest.next() {
+                Some(t) => opts.template = Some(t.clone()),
+                None => return usage(),
+            },
+            _ if name.is_none() => name = Some(arg.as_str()),
+            _ => return usage(),
+        }
+    }
+    let Some(name) = name else {
+        return usage();
     };
-    match scaffold::run_init(Path::new("."), name) {
+    if io::stdin().is_terminal() {
+        match scaffold::prompt_details(&mut io::stdin().lock(), &mut io::stdout(), name) {
+            Ok((description, authors)) => {
+                opts.description = description;
+                opts.authors = authors;
+            }
+            Err(e) => {
+                teprintln!("[MCRW] [ERROR] {e}");
+                return 1;
+            }
+        }
+    }
+    match scaffold::run_init(Path::new("."), name, &opts) {
         Ok(created) => {
             tprintln!("[MCRW] Created plugin '{name}':");
             for path in &created {
@@ -261,6 +412,90 @@ fn run_init_command(args: &[String]) -> i32 {
             tprintln!(
                 "[MCRW] Edit lua_plugins/{name}/init.lua, then start the server to load it."
             );
+            if opts.template.is_some() {
+                tprintln!(
+                    "[MCRW] Run its tests from lua_plugins/{name}/ with `lua tests/init_spec.lua`."
+                );
+            }
+            0
+        }
+        Err(e) => {
+            teprintln!("[MCRW] [ERROR] {e}");
+            1
+        }
+    }
+}
+
+/// Handle `mcrstw pack <plugin>`: write `<plugin>-<version>.mcrwpkg` to the
+/// current directory.
+fn run_pack_command(args: &[String]) -> i32 {
+    let Some(plugin) = args.get(2) else {
+        teprintln!("[MCRW] [ERROR] usage: mcrstw pack <plugin>");
+        return 2;
+    };
+    match pack::pack(Path::new("."), plugin, Path::new(".")) {
+        Ok(out) => {
+            tprintln!("[MCRW] Packed '{plugin}' into {}", out.display());
+            0
+        }
+        Err(e) => {
+            teprintln!("[MCRW] [ERROR] {e}");
+            1
+        }
+    }
+}
+
+/// Handle `mcrstw install <pkg> [--force-incompatible]`.
+fn run_install_command(args: &[String]) -> i32 {
+    let force = args.iter().any(|a| a == "--force-incompatible");
+    let Some(pkg) = args[2..].iter().find(|a| *a != "--force-incompatible") else {
+        teprintln!("[MCRW] [ERROR] usage: mcrstw install <file.mcrwpkg> [--force-incompatible]");
+        return 2;
+    };
+    match pack::install(Path::new("."), Path::new(pkg), force) {
+        Ok(done) => {
+            let dir = &done.plugin;
+            match &done.upgraded_from {
+                Some(old) => tprintln!(
+                    "[MCRW] Upgraded '{}' {old} -> {} in lua_plugins/{dir}",
+                    done.meta.name,
+                    done.meta.version
+                ),
+                None => tprintln!(
+                    "[MCRW] Installed '{}' {} into lua_plugins/{dir}",
+                    done.meta.name,
+                    done.meta.version
+                ),
+            }
+            if done.kept_config {
+                tprintln!("[MCRW] Kept the existing lua_plugins/{dir}/config.json.");
+            }
+            let verb = if done.upgraded_from.is_some() {
+                "reload"
+            } else {
+                "load"
+            };
+            tprintln!("[MCRW] On a running server, use `!{verb} {dir}` to pick it up.");
+            0
+        }
+        Err(e) => {
+            teprintln!("[MCRW] [ERROR] {e}");
+            1
+        }
+    }
+}
+
+/// Handle `mcrstw uninstall <name>`.
+fn run_uninstall_command(args: &[String]) -> i32 {
+    let Some(name) = args.get(2) else {
+        teprintln!("[MCRW] [ERROR] usage: mcrstw uninstall <name>");
+        return 2;
+    };
+    match pack::uninstall(Path::new("."), name) {
+        Ok(dir) => {
+            tprintln!(
+                "[MCRW] Removed lua_plugins/{dir}. On a running server, use `!unload {dir}`."
+            );
             0
         }
         Err(e) => {
@@ -269,3 +504,23 @@ fn run_init_command(args: &[String]) -> i32 {
         }
     }
 }
+
+/// Handle `mcrstw gen-types [file]`: write the LuaLS stubs for this wrapper's
+/// API to `file`, or to stdout.
+fn run_gen_types_command(args: &[String]) -> i32 {
+    let stubs = api_spec::lua_stubs();
+    let Some(path) = args.get(2) else {
+        print!("{stubs}");
+        return 0;
+    };
+    match std::fs::write(path, stubs) {
+        Ok(()) => {
+            tprintln!("[MCRW] Wrote {path}");
+            0
+        }
+        Err(e) => {
+            teprintln!("[MCRW] [ERROR] write {path}: {e}");
+            1
+        }
+    }
+}
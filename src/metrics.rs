@@ -0,0 +1,337 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Per-plugin callback metrics, shown by `!profile` and `wrapper:stats()`.
+//!
+//! Every Lua callback the wrapper dispatches (regex triggers, cron jobs,
+//! timers, player and lifecycle events, stop/crash callbacks, unload hooks,
+//! and the load-complete, quarantine and config-change callbacks) is timed
+//! and counted under its plugin, its kind, and a key: the trigger pattern, the
+//! cron expression, the timer kind, or the lifecycle event name.
+//! Time is wall time from the call to its return, so a callback waiting on
+//! `wrapper:http_request` is charged for the wait. Percentiles are over the
+//! last [`SAMPLES`] calls of each entry; counts and totals cover everything
+//! since start or the last reset. Counters survive `!reload`.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Latency samples kept per entry for the percentiles.
+pub const SAMPLES: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Trigger,
+    Cron,
+    Timer,
+    Join,
+    Leave,
+    Lifecycle,
+    Stop,
+    Crash,
+    Unload,
+}
+
+impl Kind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Kind::Trigger => "trigger",
+            Kind::Cron => "cron",
+            Kind::Timer => "timer",
+            Kind::Join => "join",
+            Kind::Leave => "leave",
+            Kind::Lifecycle => "lifecycle",
+            Kind::Stop => "stop",
+            Kind::Crash => "crash",
+            Kind::Unload => "unload",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    calls: u64,
+    errors: u64,
+    commands: u64,
+    total: Duration,
+    recent: VecDeque<Duration>,
+}
+
+/// One entry, as reported by `!profile` and `wrapper:stats()`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Stat {
+    pub plugin: String,
+    pub kind: Kind,
+    pub key: String,
+    pub calls: u64,
+    pub errors: u64,
+    /// Commands returned by the callback for the server.
+    pub commands: u64,
+    pub total_ms: f64,
+    pub p50_ms: f64,
+    pub p99_ms: f64,
+}
+
+type Key = (String, Kind, String);
+
+#[derive(Default)]
+pub struct MetricsRegistry {
+    counters: Mutex<BTreeMap<Key, Counters>>,
+}
+
+fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
+
+// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[Duration], q: f64) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+impl MetricsRegistry {
+    pub fn record(
+        &self,
+        plugin: &str,
+        kind: Kind,
+        key: &str,
+        elapsed: Duration,
+        ok: bool,
+        commands: usize,
+    ) {
+        let mut g = self.counters.lock().unwrap();
+        let c = g
+            .entry((plugin.to_string(), kind, key.to_string()))
+            .or_default();
+        c.calls += 1;
+        c.errors += u64::from(!ok);
+        c.commands += commands as u64;
+        c.total += elapsed;
+        if c.recent.len() == SAMPLES {
+            c.recent.pop_front();
+        }
+        c.recent.push_back(elapsed);
+    }
+
+    /// Record a callback that may return commands, timed from `started`.
+    pub fn record_call(
+        &self,
+        plugin: &str,
+        kind: Kind,
+        key: &str,
+        started: Instant,
+        result: &mlua::Result<Option<Vec<String>>>,
+    ) {
+        let commands = match result {
+            Ok(Some(cmds)) => cmds.len(),
+            _ => 0,
+        };
+        self.record(
+            plugin,
+            kind,
+            key,
+            started.elapsed(),
+            result.is_ok(),
+            commands,
+        );
+    }
+
+    /// Entries of `plugin` (all plugins for `None`), slowest total first.
+    pub fn snapshot(&self, plugin: Option<&str>) -> Vec<Stat> {
+        stats_of(&self.counters.lock().unwrap(), plugin)
+    }
+
+    /// Drop the counters of `plugin` (all plugins for `None`); returns how
+    /// many entries were removed.
+    pub fn reset(&self, plugin: Option<&str>) -> usize {
+        let mut g = self.counters.lock().unwrap();
+        let before = g.len();
+        retain_others(&mut g, plugin);
+        before - g.len()
+    }
+
+    /// `snapshot` and `reset` under one lock, so a call recorded in between
+    /// is not lost to both.
+    pub fn take(&self, plugin: Option<&str>) -> Vec<Stat> {
+        let mut g = self.counters.lock().unwrap();
+        let stats = stats_of(&g, plugin);
+        retain_others(&mut g, plugin);
+        stats
+    }
+}
+
+fn stats_of(counters: &BTreeMap<Key, Counters>, plugin: Option<&str>) -> Vec<Stat> {
+    let mut stats: Vec<Stat> = counters
+        .iter()
+        .filter(|((p, _, _), _)| plugin.is_none_or(|want| p == want))
+        .map(|((p, kind, key), c)| {
+            let mut recent: Vec<Duration> = c.recent.iter().copied().collect();
+            recent.sort();
+            Stat {
+                plugin: p.clone(),
+                kind: *kind,
+                key: key.clone(),
+                calls: c.calls,
+                errors: c.errors,
+                commands: c.commands,
+                total_ms: ms(c.total),
+                p50_ms: ms(percentile(&recent, 0.50)),
+                p99_ms: ms(percentile(&recent, 0.99)),
+            }
+        })
+        .collect();
+    stats.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    stats
+}
+
+// Keep only the entries not selected by `plugin` (none for `None`).
+fn retain_others(counters: &mut BTreeMap<Key, Counters>, plugin: Option<&str>) {
+    counters.retain(|(p, _, _), _| plugin.is_some_and(|want| p != want));
+}
+
+// Long trigger patterns would push the numbers off screen.
+fn shorten(key: &str, max: usize) -> String {
+    if key.chars().count() <= max {
+        return key.to_string();
+    }
+    let head: String = key.chars().take(max - 1).collect();
+    format!("{head}…")
+}
+
+/// The `!profile` table, one line per entry plus a header.
+pub fn format_profile(stats: &[Stat]) -> Vec<String> {
+    let mut lines = vec![format!(
+        "{:<16} {:<9} {:<32} {:>7} {:>6} {:>10} {:>8} {:>8} {:>6}",
+        "plugin", "kind", "key", "calls", "errors", "total ms", "p50 ms", "p99 ms", "cmds"
+    )];
+    for s in stats {
+        lines.push(format!(
+            "{:<16} {:<9} {:<32} {:>7} {:>6} {:>10.1} {:>8.2} {:>8.2} {:>6}",
+            shorten(&s.plugin, 16),
+            s.kind.as_str(),
+            shorten(&s.key, 32),
+            s.calls,
+            s.errors,
+            s.total_ms,
+            s.p50_ms,
+            s.p99_ms,
+            s.commands
+        ));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(n: u64) -> Duration {
+        Duration::from_millis(n)
+    }
+
+    #[test]
+    fn counts_errors_commands_and_percentiles() {
+        let m = MetricsRegistry::default();
+        for i in 1..=100 {
+            m.record("shop", Kind::Trigger, "!buy", millis(i), i != 7, 2);
+        }
+        m.record("shop", Kind::Cron, "0 * * * * *", millis(1000), true, 0);
+        m.record("motd", Kind::Join, "", millis(1), true, 1);
+
+        let stats = m.snapshot(Some("shop"));
+        assert_eq!(stats.len(), 2);
+        // Slowest total first: 5050 ms of triggers before the 1000 ms cron job.
+        let t = &stats[0];
+        assert_eq!((t.kind, t.key.as_str()), (Kind::Trigger, "!buy"));
+        assert_eq!((t.calls, t.errors, t.commands), (100, 1, 200));
+        assert_eq!(t.total_ms, 5050.0);
+        assert_eq!(t.p50_ms, 50.0);
+        assert_eq!(t.p99_ms, 99.0);
+        assert_eq!(m.snapshot(None).len(), 3);
+    }
+
+    #[test]
+    fn percentiles_cover_recent_samples_only() {
+        let m = MetricsRegistry::default();
+        for _ in 0..SAMPLES {
+            m.record("a", Kind::Cron, "x", millis(100), true, 0);
+        }
+        for _ in 0..SAMPLES {
+            m.record("a", Kind::Cron, "x", millis(1), true, 0);
+        }
+        let s = &m.snapshot(None)[0];
+        assert_eq!(s.calls, 2 * SAMPLES as u64);
+        assert_eq!(s.p99_ms, 1.0);
+    }
+
+    #[test]
+    fn record_call_reads_the_result() {
+        let m = MetricsRegistry::default();
+        let started = Instant::now();
+        m.record_call(
+            "a",
+            Kind::Trigger,
+            "x",
+            started,
+            &Ok(Some(vec!["say 1".into(), "say 2".into()])),
+        );
+        m.record_call(
+            "a",
+            Kind::Trigger,
+            "x",
+            started,
+            &Err(mlua::Error::external("boom")),
+        );
+        let s = &m.snapshot(None)[0];
+        assert_eq!((s.calls, s.errors, s.commands), (2, 1, 2));
+    }
+
+    #[test]
+    fn reset_one_plugin_or_all() {
+        let m = MetricsRegistry::default();
+        m.record("a", Kind::Stop, "", millis(1), true, 0);
+        m.record("b", Kind::Stop, "", millis(1), true, 0);
+        assert_eq!(m.reset(Some("a")), 1);
+        assert_eq!(m.snapshot(None)[0].plugin, "b");
+        assert_eq!(m.reset(None), 1);
+        assert!(m.snapshot(None).is_empty());
+
+        m.record("a", Kind::Stop, "", millis(1), true, 0);
+        m.record("b", Kind::Stop, "", millis(1), true, 0);
+        assert_eq!(m.take(Some("a"))[0].plugin, "a");
+        assert_eq!(m.snapshot(None).len(), 1);
+        assert_eq!(m.take(None).len(), 1);
+        assert!(m.snapshot(None).is_empty());
+    }
+
+    #[test]
+    fn profile_table_lines_up() {
+        let m = MetricsRegistry::default();
+        m.record("shop", Kind::Trigger, &"x".repeat(40), millis(3), true, 1);
+        let lines = format_profile(&m.snapshot(None));
+        assert_eq!(lines.len(), 2);
+        // Columns are fixed width, so a shortened row is exactly as wide as the header.
+        assert_eq!(lines[0].chars().count(), lines[1].chars().count());
+        assert!(lines[1].contains('…'));
+    }
+}
@@ -0,0 +1,630 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! `mcrstw pack`, `mcrstw install` and `mcrstw uninstall` — plugin packages.
+//!
+//! A `.mcrwpkg` is a gzip-compressed tar archive:
+//!
+//! ```text
+//! mcrwpkg.toml          manifest: plugin directory name, SHA-256 of every file
+//! <plugin>/meta.toml
+//! <plugin>/init.lua
+//! <plugin>/...          every other .lua and .py file
+//! ```
+//!
+//! `config.json` is never packed, so installing over an existing plugin keeps
+//! the operator's settings. The manifest is kept as `.mcrwpkg.toml` in the
+//! installed plugin, which lets an upgrade delete files the new version no
+//! longer ships.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::libs::{LIBS_DIR, check_lib_requirements};
+use crate::lua_ctx::{
+    PluginMeta, check_mcrw_version, loaded_dependents, read_plugin_meta, resolve_plugin_name,
+};
+use crate::teprintln;
+
+const MANIFEST: &str = "mcrwpkg.toml";
+const INSTALLED_MANIFEST: &str = ".mcrwpkg.toml";
+const FORMAT: u32 = 1;
+// Refuse archives that unpack to more than this (a gzip bomb, or not a plugin).
+const MAX_UNPACKED_BYTES: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Manifest {
+    format: u32,
+    plugin: String,
+    // Path inside the plugin directory ('/'-separated) → SHA-256, hex.
+    files: BTreeMap<String, String>,
+}
+
+/// Reasons `pack`, `install` or `uninstall` can fail.
+#[derive(Debug)]
+pub enum PackError {
+    /// No such plugin directory, installed plugin or package file.
+    NotFound(String),
+    /// The archive is not a well-formed package (manifest, checksums…).
+    InvalidPackage(String),
+    /// An archive entry would land outside the plugin directory.
+    UnsafePath(String),
+    /// A valid package or plugin that cannot be packed/installed/removed here.
+    Rejected(String),
+    /// An underlying filesystem operation failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackError::NotFound(what) => write!(f, "not found: {what}"),
+            PackError::InvalidPackage(why) => write!(f, "invalid package: {why}"),
+            PackError::UnsafePath(path) => {
+                write!(
+                    f,
+                    "refusing package entry outside the plugin directory: {path}"
+                )
+            }
+            PackError::Rejected(why) => f.write_str(why),
+            PackError::Io(e) => write!(f, "filesystem error: {e}"),
+        }
+    }
+}
+
+impl From<io::Error> for PackError {
+    fn from(e: io::Error) -> Self {
+        PackError::Io(e)
+    }
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+// Components of an archive path, or `None` if it is absolute or climbs out
+// with `..`.
+fn safe_components(path: &Path) -> Option<Vec<String>> {
+    let mut out = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::Normal(s) => out.push(s.to_str()?.to_string()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!out.is_empty()).then_some(out)
+}
+
+// Same rule the loader applies to `!load <dir>`.
+fn valid_dirname(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\', '.'])
+}
+
+// Files that go into a package: meta.toml and Lua/Python sources. Hidden
+// directories and `__pycache__` are skipped by the caller.
+fn packable(rel: &Path) -> bool {
+    if rel == Path::new("meta.toml") {
+        return true;
+    }
+    matches!(rel.extension().and_then(|e| e.to_str()), Some("lua" | "py"))
+}
+
+/// Pack `<base_dir>/lua_plugins/<plugin>/` into
+/// `<out_dir>/<plugin>-<version>.mcrwpkg` and return the archive's path.
+pub fn pack(base_dir: &Path, plugin: &str, out_dir: &Path) -> Result<PathBuf, PackError> {
+    let plugin_dir = base_dir.join("lua_plugins").join(plugin);
+    if !valid_dirname(plugin) || !plugin_dir.is_dir() {
+        return Err(PackError::NotFound(format!(
+            "plugin directory {}",
+            plugin_dir.display()
+        )));
+    }
+    let meta = read_plugin_meta(&plugin_dir).map_err(PackError::Rejected)?;
+    if !plugin_dir.join("init.lua").exists() {
+        return Err(PackError::Rejected(format!(
+            "{} has no init.lua",
+            plugin_dir.display()
+        )));
+    }
+
+    let mut files = BTreeMap::new();
+    let walker = walkdir::WalkDir::new(&plugin_dir)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| {
+            let name = e.file_name().to_string_lossy();
+            e.depth() == 0 || !(name.starts_with('.') || name == "__pycache__")
+        });
+    for entry in walker {
+        let entry = entry.map_err(io::Error::other)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let rel = entry
+            .path()
+            .strip_prefix(&plugin_dir)
+            .unwrap_or(entry.path());
+        if !packable(rel) {
+            continue;
+        }
+        let key = safe_components(rel)
+            .ok_or_else(|| PackError::UnsafePath(rel.display().to_string()))?
+            .join("/");
+        files.insert(key, fs::read(entry.path())?);
+    }
+
+    let manifest = Manifest {
+        format: FORMAT,
+        plugin: plugin.to_string(),
+        files: files
+            .iter()
+            .map(|(k, v)| (k.clone(), sha256_hex(v)))
+            .collect(),
+    };
+    let manifest_text = toml::to_string(&manifest).map_err(io::Error::other)?;
+
+    let out = out_dir.join(format!("{plugin}-{}.mcrwpkg", meta.version));
+    let mut builder =
+        tar::Builder::new(GzEncoder::new(File::create(&out)?, Compression::default()));
+    append_file(&mut builder, MANIFEST, manifest_text.as_bytes())?;
+    for (rel, data) in &files {
+        append_file(&mut builder, &format!("{plugin}/{rel}"), data)?;
+    }
+    builder.into_inner()?.finish()?;
+    Ok(out)
+}
+
+fn append_file<W: io::Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    builder.append_data(&mut header, path, data)
+}
+
+// A package read into memory and checked against its manifest.
+struct Package {
+    manifest: Manifest,
+    meta: PluginMeta,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+fn read_package(path: &Path) -> Result<Package, PackError> {
+    let file =
+        File::open(path).map_err(|e| PackError::NotFound(format!("{}: {e}", path.display())))?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut manifest_text = None;
+    let mut entries = Vec::new();
+    let mut total = 0u64;
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let entry_path = entry.path()?.into_owned();
+        let shown = entry_path.display().to_string();
+        let comps =
+            safe_components(&entry_path).ok_or_else(|| PackError::UnsafePath(shown.clone()))?;
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            continue;
+        }
+        if !kind.is_file() {
+            return Err(PackError::UnsafePath(format!(
+                "{shown} (not a regular file)"
+            )));
+        }
+        total += entry.size();
+        if total > MAX_UNPACKED_BYTES {
+            return Err(PackError::InvalidPackage(
+                "more than 64 MiB unpacked".into(),
+            ));
+        }
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data)?;
+        if comps == [MANIFEST] {
+            manifest_text = Some(data);
+        } else {
+            entries.push((comps, shown, data));
+        }
+    }
+
+    let manifest_text =
+        manifest_text.ok_or_else(|| PackError::InvalidPackage(format!("no {MANIFEST}")))?;
+    let manifest: Manifest = std::str::from_utf8(&manifest_text)
+        .map_err(|e| e.to_string())
+        .and_then(|s| toml::from_str(s).map_err(|e| e.to_string()))
+        .map_err(|e| PackError::InvalidPackage(format!("{MANIFEST}: {e}")))?;
+    if manifest.format != FORMAT {
+        return Err(PackError::InvalidPackage(format!(
+            "package format {} is not supported (expected {FORMAT})",
+            manifest.format
+        )));
+    }
+    if !valid_dirname(&manifest.plugin) {
+        return Err(PackError::UnsafePath(manifest.plugin.clone()));
+    }
+
+    let mut files = BTreeMap::new();
+    for (comps, shown, data) in entries {
+        if comps.len() < 2 || comps[0] != manifest.plugin {
+            return Err(PackError::UnsafePath(shown));
+        }
+        let rel = comps[1..].join("/");
+        match manifest.files.get(&rel) {
+            None => {
+                return Err(PackError::InvalidPackage(format!(
+                    "{rel} is not in the manifest"
+                )));
+            }
+            Some(sum) if *sum != sha256_hex(&data) => {
+                return Err(PackError::InvalidPackage(format!(
+                    "checksum mismatch for {rel}"
+                )));
+            }
+            Some(_) => {}
+        }
+        files.insert(rel, data);
+    }
+    if let Some(missing) = manifest.files.keys().find(|k| !files.contains_key(*k)) {
+        return Err(PackError::InvalidPackage(format!(
+            "{missing} is listed but missing"
+        )));
+    }
+    if !files.contains_key("init.lua") {
+        return Err(PackError::InvalidPackage("no init.lua".into()));
+    }
+    let meta = files
+        .get("meta.toml")
+        .ok_or_else(|| PackError::InvalidPackage("no meta.toml".into()))
+        .and_then(|m| {
+            std::str::from_utf8(m)
+                .map_err(|e| e.to_string())
+                .and_then(|s| toml::from_str::<PluginMeta>(s).map_err(|e| e.to_string()))
+                .map_err(|e| PackError::InvalidPackage(format!("meta.toml: {e}")))
+        })?;
+    Ok(Package {
+        manifest,
+        meta,
+        files,
+    })
+}
+
+// Plugins in `plugins_dir` with a readable meta.toml, keyed by directory.
+fn installed_plugins(plugins_dir: &Path) -> HashMap<String, PluginMeta> {
+    let Ok(entries) = fs::read_dir(plugins_dir) else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().join("init.lua").exists())
+        .filter_map(|e| {
+            let meta = read_plugin_meta(&e.path()).ok()?;
+            Some((e.file_name().to_str()?.to_string(), meta))
+        })
+        .collect()
+}
+
+/// What `install` did.
+#[derive(Debug)]
+pub struct Installed {
+    pub plugin: String,
+    pub meta: PluginMeta,
+    /// The version that was replaced, when this was an upgrade.
+    pub upgraded_from: Option<String>,
+    /// An existing `config.json` was left in place.
+    pub kept_config: bool,
+}
+
+/// Install the package at `pkg` into `<base_dir>/lua_plugins/`. Nothing is
+/// written unless the package is intact, compatible and its dependencies and
+/// libraries are installed.
+pub fn install(
+    base_dir: &Path,
+    pkg: &Path,
+    force_incompatible: bool,
+) -> Result<Installed, PackError> {
+    let package = read_package(pkg)?;
+    let plugin = package.manifest.plugin.clone();
+    let meta = package.meta;
+
+    if let Err(e) = check_mcrw_version(&meta.mcrw_version) {
+        if !force_incompatible {
+            return Err(PackError::Rejected(format!(
+                "{e} (use --force-incompatible to install it anyway)"
+            )));
+        }
+        teprintln!("[MCRW] [WARNING] installing incompatible plugin '{plugin}': {e}");
+    }
+
+    let plugins_dir = base_dir.join("lua_plugins");
+    let mut installed = installed_plugins(&plugins_dir);
+    let previous = installed.remove(&plugin);
+    if let Some(old) = &previous {
+        if old.name != meta.name {
+            return Err(PackError::Rejected(format!(
+                "lua_plugins/{plugin} holds a different plugin ('{}')",
+                old.name
+            )));
+        }
+    }
+    let missing: Vec<&str> = meta
+        .dependencies
+        .iter()
+        .filter(|d| resolve_plugin_name(&installed, d).is_none())
+        .map(String::as_str)
+        .collect();
+    if !missing.is_empty() {
+        return Err(PackError::Rejected(format!(
+            "missing dependencies: {} (install them first)",
+            missing.join(", ")
+        )));
+    }
+    check_lib_requirements(&base_dir.join(LIBS_DIR), &meta.libs).map_err(PackError::Rejected)?;
+
+    let target = plugins_dir.join(&plugin);
+    let old_manifest: Option<Manifest> = fs::read_to_string(target.join(INSTALLED_MANIFEST))
+        .ok()
+        .and_then(|s| toml::from_str(&s).ok());
+    let kept_config = target.join("config.json").exists();
+    for (rel, data) in &package.files {
+        if rel == "config.json" && kept_config {
+            continue;
+        }
+        let dest = target.join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(dest, data)?;
+    }
+    // Files the previous package shipped that this one does not.
+    for rel in old_manifest.iter().flat_map(|m| m.files.keys()) {
+        if package.files.contains_key(rel) {
+            continue;
+        }
+        let Some(comps) = safe_components(Path::new(rel)) else {
+            continue;
+        };
+        match fs::remove_file(target.join(comps.join("/"))) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+    }
+    let manifest_text = toml::to_string(&package.manifest).map_err(io::Error::other)?;
+    fs::write(target.join(INSTALLED_MANIFEST), manifest_text)?;
+
+    Ok(Installed {
+        plugin,
+        meta,
+        upgraded_from: previous.map(|m| m.version),
+        kept_config,
+    })
+}
+
+/// Remove an installed plugin (by directory or meta.toml `name`), including
+/// its config.json. Refused while another installed plugin depends on it.
+/// Returns the directory name.
+pub fn uninstall(base_dir: &Path, name: &str) -> Result<String, PackError> {
+    let plugins_dir = base_dir.join("lua_plugins");
+    let installed = installed_plugins(&plugins_dir);
+    let dirname = resolve_plugin_name(&installed, name)
+        .ok_or_else(|| PackError::NotFound(format!("installed plugin '{name}'")))?;
+    let dependents = loaded_dependents(&installed, &dirname);
+    if !dependents.is_empty() {
+        return Err(PackError::Rejected(format!(
+            "{} depend on '{dirname}'; uninstall them first",
+            dependents.join(", ")
+        )));
+    }
+    fs::remove_dir_all(plugins_dir.join(&dirname))?;
+    Ok(dirname)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcrw_pack_{tag}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write(path: PathBuf, text: &str) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, text).unwrap();
+    }
+
+    fn make_plugin(base: &Path, dir: &str, meta: &str) {
+        let p = base.join("lua_plugins").join(dir);
+        write(p.join("meta.toml"), meta);
+        write(p.join("init.lua"), "return nil");
+    }
+
+    // An archive written entry by entry, bypassing the tar crate's own path
+    // checks, to test what `install` does with hostile input.
+    fn raw_package(path: &Path, entries: &[(&str, &[u8])]) {
+        let mut b = tar::Builder::new(GzEncoder::new(
+            File::create(path).unwrap(),
+            Compression::default(),
+        ));
+        for (name, data) in entries {
+            let mut h = tar::Header::new_gnu();
+            h.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+            h.set_entry_type(tar::EntryType::Regular);
+            h.set_size(data.len() as u64);
+            h.set_mode(0o644);
+            h.set_cksum();
+            b.append(&h, *data).unwrap();
+        }
+        b.into_inner().unwrap().finish().unwrap();
+    }
+
+    fn manifest(plugin: &str, files: &[(&str, &[u8])]) -> Vec<u8> {
+        let m = Manifest {
+            format: FORMAT,
+            plugin: plugin.into(),
+            files: files
+                .iter()
+                .map(|(k, v)| (k.to_string(), sha256_hex(v)))
+                .collect(),
+        };
+        toml::to_string(&m).unwrap().into_bytes()
+    }
+
+    #[test]
+    fn pack_then_install_and_upgrade() {
+        let src = temp_dir("src");
+        let dst = temp_dir("dst");
+        let plugin = src.join("lua_plugins/shop");
+        make_plugin(&src, "shop", "name = \"Shop\"\nversion = \"1.0.0\"");
+        write(plugin.join("lib/util.lua"), "return {}");
+        write(plugin.join("scripts/job.py"), "print(1)");
+        write(plugin.join("config.json"), "{}");
+        write(plugin.join("notes.txt"), "");
+        write(plugin.join(".git/hook.lua"), "");
+
+        let pkg = pack(&src, "shop", &src).unwrap();
+        assert_eq!(pkg.file_name().unwrap(), "shop-1.0.0.mcrwpkg");
+        let files: Vec<String> = read_package(&pkg).unwrap().files.into_keys().collect();
+        assert_eq!(
+            files,
+            ["init.lua", "lib/util.lua", "meta.toml", "scripts/job.py"]
+        );
+
+        let done = install(&dst, &pkg, false).unwrap();
+        assert_eq!(done.plugin, "shop");
+        assert!(done.upgraded_from.is_none());
+        let installed = dst.join("lua_plugins/shop");
+        assert!(installed.join("lib/util.lua").exists());
+        assert!(!installed.join("config.json").exists());
+
+        // Upgrade: the operator's config survives, dropped files go away.
+        write(installed.join("config.json"), "{\"price\": 5}");
+        fs::remove_file(plugin.join("lib/util.lua")).unwrap();
+        write(
+            plugin.join("meta.toml"),
+            "name = \"Shop\"\nversion = \"1.1.0\"",
+        );
+        let pkg = pack(&src, "shop", &src).unwrap();
+        let done = install(&dst, &pkg, false).unwrap();
+        assert_eq!(done.upgraded_from.as_deref(), Some("1.0.0"));
+        assert!(done.kept_config);
+        assert!(!installed.join("lib/util.lua").exists());
+        assert_eq!(
+            fs::read_to_string(installed.join("config.json")).unwrap(),
+            "{\"price\": 5}"
+        );
+
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dst);
+    }
+
+    #[test]
+    fn rejects_paths_outside_the_plugin() {
+        let base = temp_dir("escape");
+        let meta: &[u8] = b"name = \"x\"\nversion = \"1\"";
+        let init: &[u8] = b"return nil";
+        let evil: &[u8] = b"os.execute('rm -rf ~')";
+        for bad in ["x/../../evil.lua", "other/evil.lua", "/tmp/evil.lua"] {
+            let pkg = base.join("bad.mcrwpkg");
+            let rel = bad.trim_start_matches("x/");
+            raw_package(
+                &pkg,
+                &[
+                    (
+                        MANIFEST,
+                        &manifest("x", &[("meta.toml", meta), ("init.lua", init), (rel, evil)]),
+                    ),
+                    ("x/meta.toml", meta),
+                    ("x/init.lua", init),
+                    (bad, evil),
+                ],
+            );
+            let err = install(&base, &pkg, false).unwrap_err();
+            assert!(matches!(err, PackError::UnsafePath(_)), "{bad}: {err}");
+        }
+        assert!(!base.join("lua_plugins").exists());
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn rejects_tampered_files() {
+        let base = temp_dir("tamper");
+        let meta: &[u8] = b"name = \"x\"\nversion = \"1\"";
+        let pkg = base.join("x.mcrwpkg");
+        raw_package(
+            &pkg,
+            &[
+                (
+                    MANIFEST,
+                    &manifest("x", &[("meta.toml", meta), ("init.lua", b"return 1")]),
+                ),
+                ("x/meta.toml", meta),
+                ("x/init.lua", b"return 2"),
+            ],
+        );
+        let err = install(&base, &pkg, false).unwrap_err().to_string();
+        assert!(err.contains("checksum mismatch for init.lua"), "{err}");
+        let _ = fs::remove_dir_all(&base);
+    }
+
+    #[test]
+    fn dependencies_gate_install_and_uninstall() {
+        let src = temp_dir("deps_src");
+        let dst = temp_dir("deps_dst");
+        make_plugin(
+            &src,
+            "shop",
+            "name = \"Shop\"\nversion = \"1\"\ndependencies = [\"Economy\"]",
+        );
+        let pkg = pack(&src, "shop", &src).unwrap();
+
+        let err = install(&dst, &pkg, false).unwrap_err().to_string();
+        assert!(err.contains("missing dependencies: Economy"), "{err}");
+
+        make_plugin(&dst, "economy", "name = \"Economy\"\nversion = \"1\"");
+        install(&dst, &pkg, false).unwrap();
+        let err = uninstall(&dst, "economy").unwrap_err().to_string();
+        assert!(err.contains("shop depend on 'economy'"), "{err}");
+
+        assert_eq!(uninstall(&dst, "Shop").unwrap(), "shop");
+        assert!(!dst.join("lua_plugins/shop").exists());
+        assert_eq!(uninstall(&dst, "economy").unwrap(), "economy");
+        assert!(matches!(
+            uninstall(&dst, "economy"),
+            Err(PackError::NotFound(_))
+        ));
+
+        let _ = fs::remove_dir_all(&src);
+        let _ = fs::remove_dir_all(&dst);
+    }
+}
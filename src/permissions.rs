@@ -0,0 +1,544 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Capability permissions for plugins.
+//!
+//! A plugin lists what it needs in `meta.toml`:
+//!
+//! ```toml
+//! permissions = ["http", "store:shared:economy"]
+//! ```
+//!
+//! and the operator approves it per plugin directory in `mcrw.toml`:
+//!
+//! ```toml
+//! [permissions]
+//! enforce = true
+//! [permissions.grants]
+//! shop = ["http", "store:shared:economy"]
+//! ```
+//!
+//! With `enforce` off (the default) every plugin keeps the full API, as
+//! before. With it on, a capability is usable only if the plugin declares it
+//! *and* the operator grants it. The `wrapper` methods check their capability
+//! on each call, and plugin modules are loaded into a per-plugin global
+//! environment without `debug`, `package`, `dofile`/`loadfile`, and without
+//! `io` and most of `os` unless "fs" is held.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use mlua::{ChunkMode, Function, IntoLuaMulti, Lua, MultiValue, Table};
+
+use crate::lua_ctx::{PermissionsConfig, PluginRegistry, SharedMcrwConfig, module_owner};
+use crate::teprintln;
+
+// Capabilities a `permissions` entry may name; `store:shared:<name>` (or
+// `store:shared:*`) is matched separately.
+const CAPABILITIES: &[&str] = &["http", "python", "rcon", "command", "fs", "metrics"];
+
+// Named registry table: plugin dirname → its global environment. Entries are
+// taken and restored with the plugin's modules (see lua_ctx::take_plugins).
+pub const PLUGIN_ENVS: &str = "mcrw.plugin_envs";
+
+// Named registry table: the one environment shared by every module outside
+// lua_plugins/ (lua_libs/, `./?.lua`…) while permissions are enforced.
+const LIB_ENV: &str = "mcrw.lib_env";
+
+// Named registry table: `package.loaded` key → owning plugin dirname, for
+// modules of a plugin that were required under another name than
+// `lua_plugins.<dir>.…` (e.g. `require("<dir>.helper")`), so take_plugins can
+// still find them.
+pub const PLUGIN_MODULES: &str = "mcrw.plugin_modules";
+
+/// What one plugin may use, resolved when its context is created.
+#[derive(Debug, Clone, Default)]
+pub struct Permissions {
+    dirname: String,
+    enforced: bool,
+    // Declared in meta.toml and granted in mcrw.toml.
+    granted: Vec<String>,
+}
+
+// "store:shared:*" covers every shared namespace; anything else is exact.
+fn covers(entry: &str, cap: &str) -> bool {
+    match entry.strip_suffix('*') {
+        Some(prefix) => prefix.starts_with("store:shared:") && cap.starts_with(prefix),
+        None => entry == cap,
+    }
+}
+
+fn is_known(entry: &str) -> bool {
+    CAPABILITIES.contains(&entry)
+        || entry
+            .strip_prefix("store:shared:")
+            .is_some_and(|n| !n.is_empty())
+}
+
+impl Permissions {
+    pub fn resolve(dirname: &str, declared: &[String], cfg: &PermissionsConfig) -> Self {
+        let grants = cfg.grants.get(dirname).map(Vec::as_slice).unwrap_or(&[]);
+        let granted = declared
+            .iter()
+            .filter(|d| grants.iter().any(|g| covers(g, d)))
+            .cloned()
+            .collect();
+        Permissions {
+            dirname: dirname.to_string(),
+            enforced: cfg.enforce,
+            granted,
+        }
+    }
+
+    pub fn allows(&self, cap: &str) -> bool {
+        !self.enforced || self.granted.iter().any(|g| covers(g, cap))
+    }
+
+    /// `Err` for the calling `wrapper` method when `cap` is not held.
+    pub fn check(&self, method: &str, cap: &str) -> mlua::Result<()> {
+        if self.allows(cap) {
+            return Ok(());
+        }
+        Err(mlua::Error::external(format!(
+            "wrapper:{method}: plugin '{}' lacks the \"{cap}\" permission (declare it in meta.toml and grant it under [permissions.grants] in mcrw.toml)",
+            self.dirname
+        )))
+    }
+}
+
+// Build a plugin's global environment. Runs with the real globals, so the
+// closures below keep the unrestricted `load`, `require` and `Server` out of
+// the plugin's reach.
+const SANDBOX_LUA: &str = r#"
+local dirname, fs = ...
+local function copy(t)
+  local c = {}
+  for k, v in pairs(t) do c[k] = v end
+  return c
+end
+local env = copy(_G)
+env._G = env
+env.debug, env.package, env.dofile, env.loadfile = nil, nil, nil, nil
+-- Library tables are per environment, so a plugin patching string.format
+-- or table.concat changes them for itself only.
+for _, lib in ipairs({ "string", "table", "math", "coroutine", "utf8", "io", "os" }) do
+  if _G[lib] then env[lib] = copy(_G[lib]) end
+end
+if not fs then
+  env.io = nil
+  env.os = { clock = os.clock, date = os.date, difftime = os.difftime, time = os.time }
+end
+
+-- Method calls on strings go through the string metatable, shared by the
+-- whole Lua state: index a read-only view of the real library and keep the
+-- metatable itself out of reach.
+local strings = getmetatable("")
+if type(strings) == "table" then
+  strings.__index = setmetatable({}, {
+    __index = string,
+    __newindex = function() error("the string library is read-only", 2) end,
+    __metatable = false,
+  })
+  strings.__metatable = false
+end
+
+local load, require, Server = load, require, Server
+
+-- Text chunks only, and the plugin's environment unless one is given.
+function env.load(chunk, name, _, e)
+  return load(chunk, name, "t", e or env)
+end
+
+-- package.loaded still holds the real libraries.
+local stdlib = {
+  io = true, os = true, debug = true, package = true, _G = true,
+  string = true, table = true, math = true, coroutine = true, utf8 = true,
+}
+local who = dirname and "plugin '" .. dirname .. "'" or "libraries"
+function env.require(name)
+  if stdlib[name] then
+    if env[name] == nil then
+      error("module '" .. name .. "' is not permitted for " .. who, 2)
+    end
+    return env[name]
+  end
+  return require(name)
+end
+
+-- Libraries get no Server at all.
+if not dirname then
+  env.Server = nil
+  return env
+end
+
+-- Another plugin's context would carry that plugin's permissions.
+local own = "lua_plugins." .. dirname
+env.Server = {
+  get_context = function(_, path)
+    if (tostring(path):gsub("%.$", "")) ~= own then
+      error("Server:get_context: plugin '" .. dirname .. "' can only get its own context", 2)
+    end
+    return Server:get_context(path)
+  end,
+}
+return env
+"#;
+
+/// A plugin's environment, or with `dirname` `None` the libraries' one: no
+/// `Server`, and `io`/`os` as for a plugin without `fs`.
+pub fn build_env(lua: &Lua, dirname: Option<&str>, fs: bool) -> mlua::Result<Table> {
+    lua.load(SANDBOX_LUA)
+        .set_name("=mcrw_sandbox")
+        .call((dirname, fs))
+}
+
+// The plugin's environment, created on its first module load. Logs what was
+// declared but not granted, once per load.
+fn plugin_env(
+    lua: &Lua,
+    dirname: &str,
+    declared: &[String],
+    cfg: &PermissionsConfig,
+) -> mlua::Result<Table> {
+    let envs: Table = match lua.named_registry_value::<Option<Table>>(PLUGIN_ENVS)? {
+        Some(t) => t,
+        None => {
+            let t = lua.create_table()?;
+            lua.set_named_registry_value(PLUGIN_ENVS, &t)?;
+            t
+        }
+    };
+    if let Some(env) = envs.get::<Option<Table>>(dirname)? {
+        return Ok(env);
+    }
+
+    let unknown: Vec<&str> = declared
+        .iter()
+        .map(String::as_str)
+        .filter(|d| !is_known(d))
+        .collect();
+    if !unknown.is_empty() {
+        teprintln!(
+            "[MCRW] [WARNING] plugin '{}' declares unknown permissions: {}",
+            dirname,
+            unknown.join(", ")
+        );
+    }
+    let perms = Permissions::resolve(dirname, declared, cfg);
+    let missing: Vec<&str> = declared
+        .iter()
+        .map(String::as_str)
+        .filter(|d| is_known(d) && !perms.granted.iter().any(|g| g == d))
+        .collect();
+    if !missing.is_empty() {
+        teprintln!(
+            "[MCRW] [WARNING] plugin '{}' requests permissions not granted in mcrw.toml: {} (add them under [permissions.grants])",
+            dirname,
+            missing.join(", ")
+        );
+    }
+
+    let env = build_env(lua, Some(dirname), perms.allows("fs"))?;
+    envs.set(dirname, &env)?;
+    Ok(env)
+}
+
+// The first path component under `root` of the file `file`, judged by where
+// the file really is: `./lua_plugins/shop/../shop/x.lua` and a symlink into
+// `lua_plugins/shop/` both give "shop". None for a file outside `root`.
+fn file_owner(root: &Path, file: &str) -> Option<String> {
+    let root = fs::canonicalize(root).ok()?;
+    let file = fs::canonicalize(file).ok()?;
+    let first = file.strip_prefix(&root).ok()?.components().next()?;
+    first.as_os_str().to_str().map(str::to_string)
+}
+
+fn library_env(lua: &Lua) -> mlua::Result<Table> {
+    if let Some(env) = lua.named_registry_value::<Option<Table>>(LIB_ENV)? {
+        return Ok(env);
+    }
+    let env = build_env(lua, None, false)?;
+    lua.set_named_registry_value(LIB_ENV, &env)?;
+    Ok(env)
+}
+
+// The searcher's result for `file`: its text compiled into `env`.
+fn sandboxed_chunk(lua: &Lua, file: &str, env: Table) -> mlua::Result<MultiValue> {
+    let code = fs::read(file).map_err(|e| mlua::Error::external(format!("read {file}: {e}")))?;
+    let chunk = lua
+        .load(code)
+        .set_name(format!("@{file}"))
+        .set_mode(ChunkMode::Text)
+        .set_environment(env)
+        .into_function()?;
+    (chunk, file).into_lua_multi(lua)
+}
+
+/// Put a searcher in front of the standard Lua file searcher. With
+/// `[permissions] enforce`, it loads every module file under `lua_plugins/<dir>/`
+/// into that plugin's environment, whatever name it was required by, refuses
+/// files there that belong to no loaded plugin, and runs every other Lua
+/// module file (libraries) in a shared environment without `io`, `debug` or
+/// the rest of `os`; otherwise it steps aside.
+pub fn install_plugin_searcher(
+    lua: &Lua,
+    plugins: PluginRegistry,
+    mcrw_config: SharedMcrwConfig,
+) -> mlua::Result<()> {
+    install_searcher_under(lua, plugins, mcrw_config, PathBuf::from("lua_plugins"))
+}
+
+fn install_searcher_under(
+    lua: &Lua,
+    plugins: PluginRegistry,
+    mcrw_config: SharedMcrwConfig,
+    root: PathBuf,
+) -> mlua::Result<()> {
+    let searcher = lua.create_function(move |lua: &Lua, name: String| {
+        let cfg = mcrw_config.lock().unwrap().clone();
+        if !cfg.permissions.enforce {
+            return Ok(MultiValue::new());
+        }
+
+        let package: Table = lua.globals().get("package")?;
+        let searchpath: Function = package.get("searchpath")?;
+        let (file, err): (Option<String>, Option<String>) =
+            searchpath.call((name.as_str(), package.get::<String>("path")?))?;
+        let by_name = module_owner(&name);
+        let Some(file) = file else {
+            return if by_name.is_some() {
+                err.into_lua_multi(lua)
+            } else {
+                Ok(MultiValue::new())
+            };
+        };
+        // Ownership follows the file: package.path also reaches plugin files
+        // as `<dir>.x` (lua_plugins/?.lua) and `lua_plugins/<dir>/x` (./?.lua),
+        // and those must not run with the real globals either.
+        let Some(dirname) = file_owner(&root, &file).or_else(|| by_name.map(str::to_string)) else {
+            // A library: plugins lacking fs must not reach io/os through it.
+            return sandboxed_chunk(lua, &file, library_env(lua)?);
+        };
+        let Some(meta) = plugins.lock().unwrap().get(&dirname).cloned() else {
+            return Err(mlua::Error::external(format!(
+                "module '{name}' ({file}) belongs to no loaded plugin"
+            )));
+        };
+        if by_name != Some(dirname.as_str()) {
+            let modules: Table = match lua.named_registry_value::<Option<Table>>(PLUGIN_MODULES)? {
+                Some(t) => t,
+                None => {
+                    let t = lua.create_table()?;
+                    lua.set_named_registry_value(PLUGIN_MODULES, &t)?;
+                    t
+                }
+            };
+            modules.set(name.as_str(), dirname.as_str())?;
+        }
+
+        let env = plugin_env(lua, &dirname, &meta.permissions, &cfg.permissions)?;
+        sandboxed_chunk(lua, &file, env)
+    })?;
+
+    let package: Table = lua.globals().get("package")?;
+    let searchers: Table = package.get("searchers")?;
+    // After package.preload, before the file searchers.
+    searchers.raw_insert(2, searcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lua_ctx::McrwConfig;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    fn cfg(enforce: bool, grants: &[(&str, &[&str])]) -> PermissionsConfig {
+        PermissionsConfig {
+            enforce,
+            grants: grants
+                .iter()
+                .map(|(d, g)| (d.to_string(), g.iter().map(|s| s.to_string()).collect()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    fn declared(caps: &[&str]) -> Vec<String> {
+        caps.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn needs_both_declaration_and_grant() {
+        let c = cfg(true, &[("shop", &["http", "store:shared:*", "python"])]);
+        let p = Permissions::resolve(
+            "shop",
+            &declared(&["http", "store:shared:bank", "rcon"]),
+            &c,
+        );
+        assert!(p.allows("http"));
+        assert!(p.allows("store:shared:bank"));
+        // Granted but not declared.
+        assert!(!p.allows("python"));
+        // Declared but not granted.
+        assert!(!p.allows("rcon"));
+        assert!(!p.allows("store:shared:other"));
+        assert!(p.check("rcon_command", "rcon").is_err());
+
+        // Another plugin's grants do not apply.
+        let other = Permissions::resolve("motd", &declared(&["http"]), &c);
+        assert!(!other.allows("http"));
+    }
+
+    #[test]
+    fn not_enforced_allows_everything() {
+        let p = Permissions::resolve("shop", &[], &cfg(false, &[]));
+        assert!(p.allows("fs"));
+        assert!(p.check("run_python", "python").is_ok());
+    }
+
+    #[test]
+    fn known_permission_names() {
+        assert!(is_known("fs"));
+        assert!(is_known("metrics"));
+        assert!(is_known("store:shared:economy"));
+        assert!(!is_known("store:shared:"));
+        assert!(!is_known("network"));
+    }
+
+    #[test]
+    fn sandbox_strips_stdlib_without_fs() {
+        let lua = Lua::new();
+        let env = build_env(&lua, Some("shop"), false).unwrap();
+        let run = |code: &str| -> mlua::Result<bool> {
+            lua.load(code).set_environment(env.clone()).eval()
+        };
+        assert!(run("return io == nil and debug == nil and package == nil").unwrap());
+        assert!(run("return os.execute == nil and os.time ~= nil").unwrap());
+        assert!(run("return _G == _ENV").unwrap());
+        // load() and require() cannot reach the real globals.
+        assert!(run("return load('return io')() == nil").unwrap());
+        assert!(run("return load(string.dump(function() end)) == nil").unwrap());
+        assert!(run("return require('io')").is_err());
+        assert!(run("return require('string') == string").unwrap());
+
+        let full = build_env(&lua, Some("shop"), true).unwrap();
+        let io_ok: bool = lua
+            .load("return io ~= nil and os.execute ~= nil and debug == nil")
+            .set_environment(full)
+            .eval()
+            .unwrap();
+        assert!(io_ok);
+    }
+
+    // Each plugin has its own library tables, and the string methods every
+    // plugin reaches through the shared metatable cannot be patched.
+    #[test]
+    fn sandboxes_do_not_share_library_tables() {
+        let lua = Lua::new();
+        let a = build_env(&lua, Some("a"), false).unwrap();
+        let b = build_env(&lua, Some("b"), false).unwrap();
+        let run = |env: &Table, code: &str| lua.load(code).set_environment(env.clone()).exec();
+        run(
+            &a,
+            "string.format = function() return 'leak' end\ntable.concat = nil",
+        )
+        .unwrap();
+        let eval = |env: &Table, code: &str| -> String {
+            lua.load(code).set_environment(env.clone()).eval().unwrap()
+        };
+        assert_eq!(eval(&a, "return string.format('%d', 1)"), "leak");
+        assert_eq!(eval(&b, "return string.format('%d', 1)"), "1");
+        assert_eq!(eval(&b, "return table.concat({ 'x', 'y' })"), "xy");
+        assert_eq!(eval(&a, "return ('%d'):format(2)"), "2");
+        assert!(run(&a, "getmetatable('').__index.format = print").is_err());
+        assert!(run(&a, "('').format = print").is_err());
+        assert_eq!(eval(&b, "return ('%d'):format(3)"), "3");
+    }
+
+    // A plugin's own files land in its environment under every name
+    // package.path resolves them by, not just `lua_plugins.<dir>.…`; other
+    // module files run in the restricted library environment.
+    #[test]
+    fn plugin_files_load_sandboxed_under_any_name() {
+        let root = std::env::temp_dir().join("mcrw_permissions_searcher");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("lua_plugins/shop")).unwrap();
+        fs::create_dir_all(root.join("lua_plugins/gone")).unwrap();
+        fs::write(
+            root.join("lua_plugins/shop/helper.lua"),
+            "return io == nil and debug == nil",
+        )
+        .unwrap();
+        fs::write(root.join("lua_plugins/gone/x.lua"), "return true").unwrap();
+        fs::create_dir_all(root.join("lua_libs")).unwrap();
+        fs::write(
+            root.join("lua_libs/passthrough.lua"),
+            "return io == nil and debug == nil and os.execute == nil and Server == nil",
+        )
+        .unwrap();
+
+        let lua = Lua::new();
+        let r = root.display();
+        let package: Table = lua.globals().get("package").unwrap();
+        package
+            .set(
+                "path",
+                format!("{r}/lua_plugins/?.lua;{r}/lua_libs/?.lua;{r}/?.lua"),
+            )
+            .unwrap();
+        let meta = toml::from_str("name = 'shop'\nversion = '1.0.0'").unwrap();
+        let plugins: PluginRegistry =
+            Arc::new(Mutex::new(HashMap::from([("shop".to_string(), meta)])));
+        let mut config = McrwConfig::default();
+        config.permissions.enforce = true;
+        let shared: SharedMcrwConfig = Arc::new(Mutex::new(Arc::new(config)));
+        install_searcher_under(&lua, plugins, shared, root.join("lua_plugins")).unwrap();
+
+        let require = |name: &str| lua.load(format!("return require('{name}')")).eval::<bool>();
+        assert!(require("lua_plugins.shop.helper").unwrap());
+        assert!(require("shop.helper").unwrap());
+        assert!(require("lua_plugins/shop/helper").unwrap());
+        let modules: Table = lua.named_registry_value(PLUGIN_MODULES).unwrap();
+        assert_eq!(modules.get::<String>("shop.helper").unwrap(), "shop");
+        assert!(
+            modules
+                .get::<Option<String>>("lua_plugins.shop.helper")
+                .unwrap()
+                .is_none()
+        );
+        // Libraries cannot hand a plugin what its own environment lacks.
+        assert!(require("passthrough").unwrap());
+        // Not a loaded plugin: refused rather than run with the real globals.
+        let err = require("gone.x").unwrap_err();
+        assert!(
+            err.to_string().contains("belongs to no loaded plugin"),
+            "{err}"
+        );
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn sandbox_only_hands_out_own_context() {
+        let lua = Lua::new();
+        let env = build_env(&lua, Some("shop"), false).unwrap();
+        let err = lua
+            .load("return Server:get_context('lua_plugins.other.')")
+            .set_environment(env)
+            .exec()
+            .unwrap_err();
+        assert!(err.to_string().contains("only get its own context"));
+    }
+}
@@ -31,30 +31,61 @@
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
 use regex::Regex;
 use serde::{Deserialize, Serialize};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::mpsc;
 
-use crate::teprintln;
+use crate::flavor::Flavor;
+use crate::log_line::LogRecord;
 use crate::lua_ctx::PlayersConfig;
 use crate::rcon::RconHandle;
+use crate::teprintln;
+use crate::waiters::Waiters;
+
+// Built-in default patterns. Grounded in real server logs; see the design
+// spec §6 table. They match the message after the log prefix (log_line.rs),
+// so they hold for every server flavor; anchoring at the start keeps chat
+// such as "<bob> Steve joined the game" from matching. The pos/dim patterns
+// are unanchored so the same regex parses both a stdout message and a bare
+// RCON response body.
+struct Presets {
+    join: &'static str,
+    leave: &'static str,
+    login: &'static str,
+    uuid: &'static str,
+    pos: &'static str,
+    dim: &'static str,
+}
 
-// Built-in vanilla default patterns. Grounded in real server logs; see the
-// design spec §6 table. The pos/dim patterns are intentionally prefix-agnostic
-// (no `[Server thread/INFO]:` tag) so the same regex parses both a prefixed
-// stdout line and a bare RCON response body.
-const DEFAULT_JOIN: &str = r"\[Server thread/INFO\]: (\w{3,16}) joined the game";
-const DEFAULT_LEAVE: &str = r"\[Server thread/INFO\]: (\w{3,16}) left the game";
-const DEFAULT_LOGIN: &str =
-    r"\[Server thread/INFO\]: (\w{3,16})\[/([\d.]+):\d+\] logged in with entity id";
-const DEFAULT_UUID: &str =
-    r"\[User Authenticator #\d+/INFO\]: UUID of player (\w{3,16}) is ([0-9a-fA-F-]{36})";
-const DEFAULT_POS: &str =
-    r"(\w{3,16}) has the following entity data: \[([-0-9.eE]+)d, ([-0-9.eE]+)d, ([-0-9.eE]+)d\]";
-const DEFAULT_DIM: &str = r#"(\w{3,16}) has the following entity data: "([^"]+)""#;
+const VANILLA: Presets = Presets {
+    join: r"^(\w{3,16}) joined the game$",
+    leave: r"^(\w{3,16}) left the game$",
+    login: r"^(\w{3,16})\[/([\d.]+):\d+\] logged in with entity id",
+    uuid: r"^UUID of player (\w{3,16}) is ([0-9a-fA-F-]{36})$",
+    pos: r"(\w{3,16}) has the following entity data: \[([-0-9.eE]+)d, ([-0-9.eE]+)d, ([-0-9.eE]+)d\]",
+    dim: r#"(\w{3,16}) has the following entity data: "([^"]+)""#,
+};
+
+// Velocity logs proxy connections; one line serves as both login and join.
+// It has no UUID line or `data` command, so those keep the vanilla patterns.
+const VELOCITY: Presets = Presets {
+    join: r"^\[connected player\] (\w{3,16}) \(/[\d.]+:\d+\) has connected$",
+    leave: r"^\[connected player\] (\w{3,16}) \(/[\d.]+:\d+\) has disconnected$",
+    login: r"^\[connected player\] (\w{3,16}) \(/([\d.]+):\d+\) has connected$",
+    ..VANILLA
+};
+
+// Paper, Fabric and Forge print the vanilla messages behind their own
+// prefix, which the log parser strips.
+fn presets(flavor: Flavor) -> &'static Presets {
+    match flavor {
+        Flavor::Velocity => &VELOCITY,
+        _ => &VANILLA,
+    }
+}
 
 /// A live player position, as returned by `data get entity <name> Pos`.
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -64,7 +95,7 @@ pub struct Pos {
     pub z: f64,
 }
 
-/// Emitted by [`PlayerRegistry::observe_line`] so the dispatch loop can fire the
+/// Emitted by [`PlayerRegistry::observe`] so the dispatch loop can fire the
 /// corresponding Lua `register_on_join` / `register_on_leave` callbacks.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PlayerEvent {
@@ -109,21 +140,22 @@ struct Patterns {
 }
 
 impl Patterns {
-    fn compile(cfg: &PlayersConfig) -> Self {
+    fn compile(cfg: &PlayersConfig, flavor: Flavor) -> Self {
+        let d = presets(flavor);
         Self {
-            join: compile_or_default("join", &cfg.join_pattern, DEFAULT_JOIN),
-            leave: compile_or_default("leave", &cfg.leave_pattern, DEFAULT_LEAVE),
-            login: compile_or_default("login", &cfg.login_pattern, DEFAULT_LOGIN),
-            uuid: compile_or_default("uuid", &cfg.uuid_pattern, DEFAULT_UUID),
-            pos: compile_or_default("pos", &cfg.pos_pattern, DEFAULT_POS),
-            dim: compile_or_default("dim", &cfg.dim_pattern, DEFAULT_DIM),
+            join: compile_or_default("join", &cfg.join_pattern, d.join),
+            leave: compile_or_default("leave", &cfg.leave_pattern, d.leave),
+            login: compile_or_default("login", &cfg.login_pattern, d.login),
+            uuid: compile_or_default("uuid", &cfg.uuid_pattern, d.uuid),
+            pos: compile_or_default("pos", &cfg.pos_pattern, d.pos),
+            dim: compile_or_default("dim", &cfg.dim_pattern, d.dim),
         }
     }
 }
 
-// Compile a user override if present, else the built-in default. A malformed
-// override is a loud error, not a silent swallow — we fall back to the default
-// for that field.
+// Compile a user override if present, else the flavor's built-in default. A
+// malformed override is a loud error, not a silent swallow — we fall back to the
+// default for that field.
 fn compile_or_default(name: &str, over: &Option<String>, default: &str) -> Regex {
     if let Some(p) = over {
         match Regex::new(p) {
@@ -147,31 +179,41 @@ struct Inner {
 /// so a single shared handle serves every reader and writer.
 pub struct PlayerRegistry {
     enabled: bool,
-    patterns: Patterns,
+    // Swapped as a whole when mcrw.toml changes or the flavor is detected
+    // (see apply_config / set_flavor).
+    patterns: Mutex<Arc<Patterns>>,
+    flavor: Mutex<Flavor>,
     inner: Mutex<Inner>,
-    pending_pos: Mutex<HashMap<String, Vec<oneshot::Sender<Pos>>>>,
-    pending_dim: Mutex<HashMap<String, Vec<oneshot::Sender<String>>>>,
+    // Live-query waiters keyed by player name.
+    pending_pos: Waiters<String, Pos>,
+    pending_dim: Waiters<String, String>,
     cmd_tx: mpsc::Sender<String>,
-    pos_timeout: Duration,
+    pos_timeout: Mutex<Duration>,
     json_path: PathBuf,
     rcon: Option<RconHandle>,
 }
 
 impl PlayerRegistry {
-    pub fn new(cfg: &PlayersConfig, cmd_tx: mpsc::Sender<String>, json_path: PathBuf) -> Self {
+    pub fn new(
+        cfg: &PlayersConfig,
+        flavor: Flavor,
+        cmd_tx: mpsc::Sender<String>,
+        json_path: PathBuf,
+    ) -> Self {
         let records = load_records(&json_path);
         Self {
             enabled: cfg.enabled,
-            patterns: Patterns::compile(cfg),
+            patterns: Mutex::new(Arc::new(Patterns::compile(cfg, flavor))),
+            flavor: Mutex::new(flavor),
             inner: Mutex::new(Inner {
                 records,
                 dirty: false,
                 last_write: None,
             }),
-            pending_pos: Mutex::new(HashMap::new()),
-            pending_dim: Mutex::new(HashMap::new()),
+            pending_pos: Waiters::default(),
+            pending_dim: Waiters::default(),
             cmd_tx,
-            pos_timeout: Duration::from_millis(cfg.pos_timeout_ms),
+            pos_timeout: Mutex::new(Duration::from_millis(cfg.pos_timeout_ms)),
             json_path,
             rcon: None,
         }
@@ -182,22 +224,44 @@ impl PlayerRegistry {
         self.rcon = Some(handle);
     }
 
+    /// Apply the live-reloadable `[players]` settings: the log patterns and
+    /// the query timeout. `enabled` only takes effect at startup.
+    pub fn apply_config(&self, cfg: &PlayersConfig) {
+        let flavor = *self.flavor.lock().unwrap();
+        *self.patterns.lock().unwrap() = Arc::new(Patterns::compile(cfg, flavor));
+        *self.pos_timeout.lock().unwrap() = Duration::from_millis(cfg.pos_timeout_ms);
+    }
+
+    /// Switch to the presets of a detected flavor; `cfg`'s overrides stay.
+    pub fn set_flavor(&self, flavor: Flavor, cfg: &PlayersConfig) {
+        *self.flavor.lock().unwrap() = flavor;
+        *self.patterns.lock().unwrap() = Arc::new(Patterns::compile(cfg, flavor));
+    }
+
+    fn patterns(&self) -> Arc<Patterns> {
+        self.patterns.lock().unwrap().clone()
+    }
+
     /// Feed every stdout line through here. Updates cached records and resolves
     /// any pending live-query waiters; returns join/leave events for the caller
     /// to dispatch to Lua callbacks. Does not touch mlua.
-    pub fn observe_line(&self, line: &str) -> Vec<PlayerEvent> {
+    pub fn observe(&self, rec: &LogRecord) -> Vec<PlayerEvent> {
         if !self.enabled {
             return Vec::new();
         }
+        let patterns = self.patterns();
+        // Patterns match the message; an override written for the whole line,
+        // prefix included, still matches through the raw line.
+        let find = |re: &Regex| re.captures(&rec.message).or_else(|| re.captures(&rec.raw));
 
         // Live-query responses are terminal — they never carry a join/leave.
-        if let Some(c) = self.patterns.pos.captures(line) {
+        if let Some(c) = find(&patterns.pos) {
             if let (Ok(x), Ok(y), Ok(z)) = (c[2].parse(), c[3].parse(), c[4].parse()) {
                 self.resolve_pos(&c[1], Pos { x, y, z });
             }
             return Vec::new();
         }
-        if let Some(c) = self.patterns.dim.captures(line) {
+        if let Some(c) = find(&patterns.dim) {
             let (name, dim) = (c[1].to_string(), c[2].to_string());
             self.resolve_dim(&name, dim);
             return Vec::new();
@@ -206,16 +270,16 @@ impl PlayerRegistry {
         let mut events = Vec::new();
 
         // login carries the IP and precedes "joined the game".
-        if let Some(c) = self.patterns.login.captures(line) {
+        if let Some(c) = find(&patterns.login) {
             let ip = c[2].to_string();
             self.upsert(&c[1], |r| r.ip = Some(ip.clone()));
         }
         // UUID is logged by the User Authenticator thread, also before join.
-        if let Some(c) = self.patterns.uuid.captures(line) {
+        if let Some(c) = find(&patterns.uuid) {
             let uuid = c[2].to_string();
             self.upsert(&c[1], |r| r.uuid = Some(uuid.clone()));
         }
-        if let Some(c) = self.patterns.join.captures(line) {
+        if let Some(c) = find(&patterns.join) {
             let name = c[1].to_string();
             self.upsert(&name, |r| {
                 let now = now_ts();
@@ -228,7 +292,7 @@ impl PlayerRegistry {
             });
             events.push(PlayerEvent::Joined(name));
         }
-        if let Some(c) = self.patterns.leave.captures(line) {
+        if let Some(c) = find(&patterns.leave) {
             let name = c[1].to_string();
             self.upsert(&name, |r| {
                 r.online = false;
@@ -236,8 +300,8 @@ impl PlayerRegistry {
                 r.last_seen = now_ts();
             });
             // Drop any dangling live-query waiters for the departed player.
-            self.pending_pos.lock().unwrap().remove(&name);
-            self.pending_dim.lock().unwrap().remove(&name);
+            self.pending_pos.cancel_where(|n| *n == name);
+            self.pending_dim.cancel_where(|n| *n == name);
             events.push(PlayerEvent::Left(name));
         }
 
@@ -256,28 +320,14 @@ impl PlayerRegistry {
         if let Some(rcon) = &self.rcon {
             if rcon.is_connected() {
                 let body = rcon.command(&format!("data get entity {name} Pos")).await?;
-                return parse_pos(&self.patterns.pos, &body);
+                return parse_pos(&self.patterns().pos, &body);
             }
         }
-        let (tx, rx) = oneshot::channel();
+        let timeout = *self.pos_timeout.lock().unwrap();
+        let cmd = format!("data get entity {name} Pos");
         self.pending_pos
-            .lock()
-            .unwrap()
-            .entry(name.to_string())
-            .or_default()
-            .push(tx);
-        if self
-            .cmd_tx
-            .send(format!("data get entity {name} Pos\n"))
+            .request(name.to_string(), &self.cmd_tx, Some(&cmd), timeout)
             .await
-            .is_err()
-        {
-            return None;
-        }
-        match tokio::time::timeout(self.pos_timeout, rx).await {
-            Ok(Ok(pos)) => Some(pos),
-            _ => None,
-        }
     }
 
     /// Live dimension for an online player (e.g. `"minecraft:overworld"`), or
@@ -291,28 +341,14 @@ impl PlayerRegistry {
                 let body = rcon
                     .command(&format!("data get entity {name} Dimension"))
                     .await?;
-                return parse_dim(&self.patterns.dim, &body);
+                return parse_dim(&self.patterns().dim, &body);
             }
         }
-        let (tx, rx) = oneshot::channel();
+        let timeout = *self.pos_timeout.lock().unwrap();
+        let cmd = format!("data get entity {name} Dimension");
         self.pending_dim
-            .lock()
-            .unwrap()
-            .entry(name.to_string())
-            .or_default()
-            .push(tx);
-        if self
-            .cmd_tx
-            .send(format!("data get entity {name} Dimension\n"))
+            .request(name.to_string(), &self.cmd_tx, Some(&cmd), timeout)
             .await
-            .is_err()
-        {
-            return None;
-        }
-        match tokio::time::timeout(self.pos_timeout, rx).await {
-            Ok(Ok(dim)) => Some(dim),
-            _ => None,
-        }
     }
 
     /// Names of all currently-online players (for `wrapper:players()`).
@@ -347,8 +383,8 @@ impl PlayerRegistry {
             }
             inner.dirty = true;
         }
-        self.pending_pos.lock().unwrap().clear();
-        self.pending_dim.lock().unwrap().clear();
+        self.pending_pos.clear();
+        self.pending_dim.clear();
     }
 
     /// Force a synchronous persist (e.g. on shutdown), bypassing the debounce.
@@ -396,19 +432,12 @@ impl PlayerRegistry {
     }
 
     fn resolve_pos(&self, name: &str, pos: Pos) {
-        if let Some(waiters) = self.pending_pos.lock().unwrap().remove(name) {
-            for w in waiters {
-                let _ = w.send(pos);
-            }
-        }
+        self.pending_pos.resolve(|n| (n == name).then_some(pos));
     }
 
     fn resolve_dim(&self, name: &str, dim: String) {
-        if let Some(waiters) = self.pending_dim.lock().unwrap().remove(name) {
-            for w in waiters {
-                let _ = w.send(dim.clone());
-            }
-        }
+        self.pending_dim
+            .resolve(|n| (n == name).then(|| dim.clone()));
     }
 
     // Debounced persist: write at most once per ~5s; the tail is covered by
@@ -517,6 +546,13 @@ fn write_players_json(path: &Path, json: &str) -> std::io::Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::log_line::LogParser;
+
+    impl PlayerRegistry {
+        fn observe_line(&self, line: &str) -> Vec<PlayerEvent> {
+            self.observe(&LogParser::default().parse(line))
+        }
+    }
 
     fn cfg() -> PlayersConfig {
         PlayersConfig::default()
@@ -530,7 +566,10 @@ mod tests {
 
     fn registry(tag: &str) -> (PlayerRegistry, mpsc::Receiver<String>) {
         let (tx, rx) = mpsc::channel(16);
-        (PlayerRegistry::new(&cfg(), tx, temp_path(tag)), rx)
+        (
+            PlayerRegistry::new(&cfg(), Flavor::Auto, tx, temp_path(tag)),
+            rx,
+        )
     }
 
     #[test]
@@ -578,7 +617,7 @@ mod tests {
         reg.observe_line("[12:00:00] [Server thread/INFO]: Steve joined the game");
         reg.resolve_pos("Steve", Pos { x: 0.0, y: 0.0, z: 0.0 }); // no-op, just exercises path
         let caps = reg
-            .patterns
+            .patterns()
             .pos
             .captures("Steve has the following entity data: [1.5d, 64.0d, -2.5d]")
             .unwrap();
@@ -588,7 +627,7 @@ mod tests {
 
         // bare RCON-body form (no server-thread prefix)
         let caps = reg
-            .patterns
+            .patterns()
             .pos
             .captures("Alex has the following entity data: [10.0d, 70.0d, 20.0d]")
             .unwrap();
@@ -622,7 +661,7 @@ mod tests {
         let mut c = cfg();
         c.pos_timeout_ms = 150;
         let (tx, _rx) = mpsc::channel(16);
-        let reg = PlayerRegistry::new(&c, tx, temp_path("timeout"));
+        let reg = PlayerRegistry::new(&c, Flavor::Auto, tx, temp_path("timeout"));
         reg.observe_line("[12:00:00] [Server thread/INFO]: Steve joined the game");
         assert!(reg.query_pos("Steve").await.is_none());
     }
@@ -641,9 +680,69 @@ mod tests {
         let mut c = cfg();
         c.join_pattern = Some(r"CUSTOM JOIN (\w+)".to_string());
         let (tx, _rx) = mpsc::channel(16);
-        let reg = PlayerRegistry::new(&c, tx, temp_path("override"));
+        let reg = PlayerRegistry::new(&c, Flavor::Auto, tx, temp_path("override"));
+        let ev = reg.observe_line("CUSTOM JOIN Bob");
+        assert_eq!(ev, vec![PlayerEvent::Joined("Bob".into())]);
+    }
+
+    #[test]
+    fn apply_config_swaps_patterns() {
+        let (tx, _rx) = mpsc::channel(16);
+        let reg = PlayerRegistry::new(&cfg(), Flavor::Auto, tx, temp_path("apply"));
+        assert!(reg.observe_line("CUSTOM JOIN Bob").is_empty());
+        let mut c = cfg();
+        c.join_pattern = Some(r"CUSTOM JOIN (\w+)".to_string());
+        c.pos_timeout_ms = 42;
+        reg.apply_config(&c);
         let ev = reg.observe_line("CUSTOM JOIN Bob");
         assert_eq!(ev, vec![PlayerEvent::Joined("Bob".into())]);
+        assert_eq!(*reg.pos_timeout.lock().unwrap(), Duration::from_millis(42));
+    }
+
+    #[test]
+    fn defaults_match_every_flavor_but_not_chat() {
+        let (reg, _rx) = registry("flavors");
+        let ev = reg.observe_line("[12:00:00 INFO]: Alex joined the game");
+        assert_eq!(ev, vec![PlayerEvent::Joined("Alex".into())]);
+        let ev = reg.observe_line("[12:00:00] [Server thread/INFO] (Minecraft) Alex left the game");
+        assert_eq!(ev, vec![PlayerEvent::Left("Alex".into())]);
+        assert!(
+            reg.observe_line("[12:00:00] [Server thread/INFO]: <bob> Steve joined the game")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn velocity_presets_follow_the_flavor() {
+        let (reg, _rx) = registry("velocity");
+        let join = "[12:00:00 INFO]: [connected player] Alex (/10.0.0.5:51234) has connected";
+        assert!(reg.observe_line(join).is_empty());
+        reg.set_flavor(Flavor::Velocity, &cfg());
+        assert_eq!(
+            reg.observe_line(join),
+            vec![PlayerEvent::Joined("Alex".into())]
+        );
+        assert_eq!(
+            reg.snapshot("Alex").unwrap().ip.as_deref(),
+            Some("10.0.0.5")
+        );
+        let ev = reg.observe_line(
+            "[12:00:05 INFO]: [connected player] Alex (/10.0.0.5:51234) has disconnected",
+        );
+        assert_eq!(ev, vec![PlayerEvent::Left("Alex".into())]);
+
+        // A hot config change keeps the detected flavor; overrides still win.
+        let mut c = cfg();
+        c.leave_pattern = Some(r"^(\w+) is gone$".to_string());
+        reg.apply_config(&c);
+        assert_eq!(
+            reg.observe_line(join),
+            vec![PlayerEvent::Joined("Alex".into())]
+        );
+        assert_eq!(
+            reg.observe_line("Alex is gone"),
+            vec![PlayerEvent::Left("Alex".into())]
+        );
     }
 
     #[test]
@@ -651,7 +750,7 @@ mod tests {
         let mut c = cfg();
         c.join_pattern = Some("(".to_string()); // invalid regex
         let (tx, _rx) = mpsc::channel(16);
-        let reg = PlayerRegistry::new(&c, tx, temp_path("badregex"));
+        let reg = PlayerRegistry::new(&c, Flavor::Auto, tx, temp_path("badregex"));
         // default vanilla pattern still works
         let ev = reg.observe_line("[12:00:00] [Server thread/INFO]: Bob joined the game");
         assert_eq!(ev, vec![PlayerEvent::Joined("Bob".into())]);
@@ -662,12 +761,12 @@ mod tests {
         let path = temp_path("persist");
         let (tx, _rx) = mpsc::channel(16);
         {
-            let reg = PlayerRegistry::new(&cfg(), tx.clone(), path.clone());
+            let reg = PlayerRegistry::new(&cfg(), Flavor::Auto, tx.clone(), path.clone());
             reg.observe_line("[12:00:00] [Server thread/INFO]: Persisted joined the game");
             reg.flush();
         }
         // reload from disk: first_join survives, online resets to false
-        let reg2 = PlayerRegistry::new(&cfg(), tx, path.clone());
+        let reg2 = PlayerRegistry::new(&cfg(), Flavor::Auto, tx, path.clone());
         let rec = reg2.snapshot("Persisted").unwrap();
         assert!(rec.first_join.is_some());
         assert!(!rec.online);
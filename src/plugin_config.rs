@@ -0,0 +1,527 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Per-plugin `config.json` handling behind `wrapper:load_config`.
+//!
+//! The file is deep-merged over the plugin's defaults: objects merge key by
+//! key, anything else (strings, numbers, arrays) is taken from the file as-is.
+//! Keys a plugin update introduced are written back to the file, so operators
+//! see them, without touching any value they set. Keys that exist only in the
+//! file are kept, and so is the order the file lists them in; new keys follow,
+//! sorted.
+//!
+//! After the plugin loaded it, the file is watched: an edit is merged the same
+//! way again and handed to the plugin's `register_on_config_change` callbacks,
+//! or rejected (the previous config stays) if it no longer parses or validates.
+//!
+//! An optional schema, written as a table of field specs, validates the merged
+//! result; every violation is reported with its JSON path:
+//!
+//! ```lua
+//! {
+//!     interval = { type = "integer", min = 1, max = 3600 },
+//!     mode     = { type = "string", enum = { "chat", "title" } },
+//!     rewards  = { type = "table", fields = { amount = "number" } },
+//!     admins   = { type = "array", items = "string", optional = true },
+//! }
+//! ```
+
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::de::{Deserialize, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use serde_json::{Map, Value as JsonValue};
+
+// What `load_plugin_config` did to the file on disk.
+#[derive(Debug, PartialEq)]
+pub enum ConfigWrite {
+    Unchanged,
+    Created,
+    // JSON paths of the default keys that were missing from the file.
+    Added(Vec<String>),
+}
+
+/// Load `path`, merge it over `defaults`, validate against `schema` and write
+/// back what changed. On a validation error nothing is written.
+pub fn load_plugin_config(
+    path: &Path,
+    defaults: JsonValue,
+    schema: Option<&JsonValue>,
+) -> Result<(JsonValue, ConfigWrite), String> {
+    let (config, write, order) = if path.exists() {
+        let content = fs::read_to_string(path).map_err(|e| format!("read failed: {e}"))?;
+        let mut config: JsonValue =
+            serde_json::from_str(&content).map_err(|e| format!("JSON syntax error: {e}"))?;
+        let order = serde_json::from_str(&content).unwrap_or_default();
+        let mut added = Vec::new();
+        merge_defaults(&mut config, &defaults, "$", &mut added);
+        let write = if added.is_empty() {
+            ConfigWrite::Unchanged
+        } else {
+            ConfigWrite::Added(added)
+        };
+        (config, write, order)
+    } else {
+        (defaults, ConfigWrite::Created, KeyOrder::default())
+    };
+
+    if let Some(schema) = schema {
+        let mut errors = Vec::new();
+        validate_fields(&config, schema, "$", &mut errors);
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+    }
+
+    if write != ConfigWrite::Unchanged {
+        let json =
+            serde_json::to_string_pretty(&InOrder(&config, &order)).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("write failed: {e}"))?;
+    }
+    Ok((config, write))
+}
+
+/// What `wrapper:load_config` was called with and what it returned, kept so a
+/// change to `config.json` can be merged and validated the same way again.
+pub struct LoadedConfig {
+    pub defaults: JsonValue,
+    pub schema: Option<JsonValue>,
+    pub current: JsonValue,
+}
+
+impl LoadedConfig {
+    /// Re-run the merge against the file at `path`. Returns the previous
+    /// config if the result differs; on error `current` is left untouched.
+    pub fn reread(&mut self, path: &Path) -> Result<Option<JsonValue>, String> {
+        let (config, _) = load_plugin_config(path, self.defaults.clone(), self.schema.as_ref())?;
+        if config == self.current {
+            return Ok(None);
+        }
+        Ok(Some(std::mem::replace(&mut self.current, config)))
+    }
+}
+
+// Fill in every key of `defaults` that `value` lacks, recursing into objects
+// present on both sides. The path of each inserted key goes into `added`.
+fn merge_defaults(
+    value: &mut JsonValue,
+    defaults: &JsonValue,
+    path: &str,
+    added: &mut Vec<String>,
+) {
+    let (JsonValue::Object(map), JsonValue::Object(defaults)) = (value, defaults) else {
+        return;
+    };
+    for (key, default) in defaults {
+        let child = child_path(path, key);
+        match map.get_mut(key) {
+            Some(existing) => merge_defaults(existing, default, &child, added),
+            None => {
+                map.insert(key.clone(), default.clone());
+                added.push(child);
+            }
+        }
+    }
+}
+
+// The key order of every object in a JSON document, as the file lists them.
+// serde_json's map sorts its keys, so a write-back walks this instead to keep
+// the operator's layout.
+#[derive(Default)]
+struct KeyOrder {
+    keys: Vec<(String, KeyOrder)>,
+    items: Vec<KeyOrder>,
+}
+
+static NO_ORDER: KeyOrder = KeyOrder {
+    keys: Vec::new(),
+    items: Vec::new(),
+};
+
+impl<'de> Deserialize<'de> for KeyOrder {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(KeyOrderVisitor)
+    }
+}
+
+struct KeyOrderVisitor;
+
+impl<'de> Visitor<'de> for KeyOrderVisitor {
+    type Value = KeyOrder;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any JSON value")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<KeyOrder, A::Error> {
+        let mut order = KeyOrder::default();
+        while let Some((key, child)) = map.next_entry::<String, KeyOrder>()? {
+            // A repeated key keeps its first position, like the parsed value.
+            match order.keys.iter_mut().find(|(k, _)| *k == key) {
+                Some(entry) => entry.1 = child,
+                None => order.keys.push((key, child)),
+            }
+        }
+        Ok(order)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<KeyOrder, A::Error> {
+        let mut order = KeyOrder::default();
+        while let Some(item) = seq.next_element()? {
+            order.items.push(item);
+        }
+        Ok(order)
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+
+    fn visit_unit<E>(self) -> Result<KeyOrder, E> {
+        Ok(KeyOrder::default())
+    }
+}
+
+// Serializes a value with its objects' keys in `KeyOrder`'s order; keys it
+// does not list (the defaults just merged in) follow in sorted order.
+struct InOrder<'a>(&'a JsonValue, &'a KeyOrder);
+
+impl Serialize for InOrder<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let InOrder(value, order) = *self;
+        match value {
+            JsonValue::Object(map) => {
+                let mut out = serializer.serialize_map(Some(map.len()))?;
+                for (key, child) in &order.keys {
+                    if let Some(v) = map.get(key) {
+                        out.serialize_entry(key, &InOrder(v, child))?;
+                    }
+                }
+                for (key, v) in map {
+                    if !order.keys.iter().any(|(k, _)| k == key) {
+                        out.serialize_entry(key, &InOrder(v, &NO_ORDER))?;
+                    }
+                }
+                out.end()
+            }
+            JsonValue::Array(items) => {
+                let mut out = serializer.serialize_seq(Some(items.len()))?;
+                for (i, v) in items.iter().enumerate() {
+                    out.serialize_element(&InOrder(v, order.items.get(i).unwrap_or(&NO_ORDER)))?;
+                }
+                out.end()
+            }
+            other => other.serialize(serializer),
+        }
+    }
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && !key.starts_with(|c: char| c.is_ascii_digit())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        format!("{path}.{key}")
+    } else {
+        format!("{path}[{}]", JsonValue::String(key.to_string()))
+    }
+}
+
+// `fields` maps key → spec; `value` must be an object holding them.
+fn validate_fields(value: &JsonValue, fields: &JsonValue, path: &str, errors: &mut Vec<String>) {
+    let Some(fields) = fields.as_object() else {
+        errors.push(format!("{path}: schema 'fields' must be a table"));
+        return;
+    };
+    let Some(map) = value.as_object() else {
+        errors.push(format!("{path}: expected table, got {}", type_name(value)));
+        return;
+    };
+    for (key, spec) in fields {
+        let child = child_path(path, key);
+        match map.get(key) {
+            Some(v) => validate(v, spec, &child, errors),
+            None if is_optional(spec) => {}
+            None => errors.push(format!("{child}: required key is missing")),
+        }
+    }
+}
+
+fn is_optional(spec: &JsonValue) -> bool {
+    spec.get("optional")
+        .and_then(JsonValue::as_bool)
+        .unwrap_or(false)
+}
+
+// A spec is a type name ("integer") or a table { type = ..., enum, min, max,
+// fields, items, optional }.
+fn validate(value: &JsonValue, spec: &JsonValue, path: &str, errors: &mut Vec<String>) {
+    let empty = Map::new();
+    let (ty, opts) = match spec {
+        JsonValue::String(ty) => (ty.as_str(), &empty),
+        JsonValue::Object(opts) => match opts.get("type").and_then(JsonValue::as_str) {
+            Some(ty) => (ty, opts),
+            None => {
+                errors.push(format!("{path}: schema entry has no 'type'"));
+                return;
+            }
+        },
+        _ => {
+            errors.push(format!(
+                "{path}: schema entry must be a type name or a table"
+            ));
+            return;
+        }
+    };
+
+    let type_ok = match ty {
+        "any" => true,
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => {
+            value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|f| f.fract() == 0.0)
+        }
+        "boolean" => value.is_boolean(),
+        // An empty Lua table arrives as `{}`, so it counts as an empty array too.
+        "array" => value.is_array() || value.as_object().is_some_and(Map::is_empty),
+        "table" => value.is_object(),
+        other => {
+            errors.push(format!("{path}: unknown schema type '{other}'"));
+            return;
+        }
+    };
+    if !type_ok {
+        errors.push(format!("{path}: expected {ty}, got {}", type_name(value)));
+        return;
+    }
+
+    if let Some(allowed) = opts.get("enum").and_then(JsonValue::as_array) {
+        if !allowed.iter().any(|a| json_eq(a, value)) {
+            let list: Vec<String> = allowed.iter().map(JsonValue::to_string).collect();
+            errors.push(format!("{path}: {value} is not one of {}", list.join(", ")));
+        }
+    }
+
+    // min/max bound numbers by value, strings and arrays by length.
+    let measured = match value {
+        JsonValue::Number(n) => n.as_f64().map(|n| (n, "")),
+        JsonValue::String(s) => Some((s.chars().count() as f64, "length ")),
+        JsonValue::Array(a) => Some((a.len() as f64, "length ")),
+        _ => None,
+    };
+    if let Some((n, what)) = measured {
+        if let Some(min) = opts.get("min").and_then(JsonValue::as_f64) {
+            if n < min {
+                errors.push(format!("{path}: {what}{n} is below the minimum {min}"));
+            }
+        }
+        if let Some(max) = opts.get("max").and_then(JsonValue::as_f64) {
+            if n > max {
+                errors.push(format!("{path}: {what}{n} is above the maximum {max}"));
+            }
+        }
+    }
+
+    if let Some(fields) = opts.get("fields") {
+        validate_fields(value, fields, path, errors);
+    }
+    if let (Some(items), Some(array)) = (opts.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            validate(item, items, &format!("{path}[{i}]"), errors);
+        }
+    }
+}
+
+// Lua integers and floats both end up as JSON numbers; compare them by value.
+fn json_eq(a: &JsonValue, b: &JsonValue) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+fn type_name(value: &JsonValue) -> &'static str {
+    match value {
+        JsonValue::Null => "null",
+        JsonValue::Bool(_) => "boolean",
+        JsonValue::Number(_) => "number",
+        JsonValue::String(_) => "string",
+        JsonValue::Array(_) => "array",
+        JsonValue::Object(_) => "table",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn temp_path(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcrw_plugin_config_{tag}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("config.json")
+    }
+
+    #[test]
+    fn merge_keeps_user_values_and_adds_new_keys() {
+        let path = temp_path("merge");
+        fs::write(
+            &path,
+            r#"{"greeting":"hey","limits":{"max":5},"extra":true}"#,
+        )
+        .unwrap();
+        let defaults = json!({
+            "greeting": "hello",
+            "limits": { "max": 10, "min": 1 },
+            "mode": "chat"
+        });
+        let (cfg, write) = load_plugin_config(&path, defaults.clone(), None).unwrap();
+        assert_eq!(cfg["greeting"], "hey");
+        assert_eq!(cfg["limits"], json!({ "max": 5, "min": 1 }));
+        assert_eq!(cfg["mode"], "chat");
+        assert_eq!(cfg["extra"], true);
+        assert_eq!(
+            write,
+            ConfigWrite::Added(vec!["$.limits.min".into(), "$.mode".into()])
+        );
+
+        // Written back: a second load adds nothing and keeps the user's order.
+        let on_disk = fs::read_to_string(&path).unwrap();
+        let at = |key: &str| on_disk.find(&format!("\"{key}\"")).unwrap();
+        assert!(
+            at("greeting") < at("limits") && at("limits") < at("extra") && at("extra") < at("mode")
+        );
+        assert!(at("max") < at("min"));
+        let (_, write) = load_plugin_config(&path, defaults, None).unwrap();
+        assert_eq!(write, ConfigWrite::Unchanged);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn missing_file_is_created_from_defaults() {
+        let path = temp_path("create");
+        let (cfg, write) = load_plugin_config(&path, json!({ "a": 1 }), None).unwrap();
+        assert_eq!(write, ConfigWrite::Created);
+        assert_eq!(cfg, json!({ "a": 1 }));
+        assert!(path.exists());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn schema_errors_carry_json_paths() {
+        let schema = json!({
+            "interval": { "type": "integer", "min": 1, "max": 3600 },
+            "mode": { "type": "string", "enum": ["chat", "title"] },
+            "rewards": { "type": "table", "fields": { "amount": "number" } },
+            "admins": { "type": "array", "items": "string" },
+            "motd": { "type": "string", "optional": true },
+            "name": "string"
+        });
+        let config = json!({
+            "interval": 0,
+            "mode": "bossbar",
+            "rewards": { "amount": "ten" },
+            "admins": ["alice", 7]
+        });
+        let mut errors = Vec::new();
+        validate_fields(&config, &schema, "$", &mut errors);
+        // Reported in key order; a schema from Lua has no other stable order.
+        assert_eq!(
+            errors,
+            [
+                "$.admins[1]: expected string, got number",
+                "$.interval: 0 is below the minimum 1",
+                "$.mode: \"bossbar\" is not one of \"chat\", \"title\"",
+                "$.name: required key is missing",
+                "$.rewards.amount: expected number, got string",
+            ]
+        );
+
+        let ok = json!({
+            "interval": 60.0,
+            "mode": "title",
+            "rewards": { "amount": 2.5 },
+            "admins": {},
+            "name": "x"
+        });
+        let mut errors = Vec::new();
+        validate_fields(&ok, &schema, "$", &mut errors);
+        assert!(errors.is_empty(), "{errors:?}");
+    }
+
+    #[test]
+    fn invalid_config_is_not_written() {
+        let path = temp_path("invalid");
+        fs::write(&path, r#"{"port":"x"}"#).unwrap();
+        let schema = json!({ "port": "integer", "host": "string" });
+        let err = load_plugin_config(&path, json!({ "port": 1, "host": "h" }), Some(&schema))
+            .unwrap_err();
+        assert_eq!(err, "$.port: expected integer, got string");
+        assert_eq!(fs::read_to_string(&path).unwrap(), r#"{"port":"x"}"#);
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn reread_reports_changes_and_keeps_config_on_error() {
+        let path = temp_path("reread");
+        let schema = json!({ "n": { "type": "integer", "min": 1 } });
+        let (current, _) = load_plugin_config(&path, json!({ "n": 1 }), Some(&schema)).unwrap();
+        let mut loaded = LoadedConfig {
+            defaults: json!({ "n": 1 }),
+            schema: Some(schema),
+            current,
+        };
+        assert_eq!(loaded.reread(&path).unwrap(), None);
+
+        fs::write(&path, r#"{"n": 5}"#).unwrap();
+        assert_eq!(loaded.reread(&path).unwrap(), Some(json!({ "n": 1 })));
+        assert_eq!(loaded.current, json!({ "n": 5 }));
+
+        fs::write(&path, r#"{"n": 0}"#).unwrap();
+        assert!(loaded.reread(&path).is_err());
+        fs::write(&path, r#"{"n": "#).unwrap();
+        assert!(loaded.reread(&path).is_err());
+        assert_eq!(loaded.current, json!({ "n": 5 }));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn odd_keys_are_quoted_in_paths() {
+        assert_eq!(child_path("$", "a_b1"), "$.a_b1");
+        assert_eq!(child_path("$", "has space"), "$[\"has space\"]");
+        assert_eq!(child_path("$.x", "1st"), "$.x[\"1st\"]");
+    }
+}
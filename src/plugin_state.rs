@@ -0,0 +1,163 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Operator-managed plugin state, persisted in `.mcrw/plugins.toml`.
+//!
+//! Today this is only the list of disabled plugins (by directory name), so a
+//! plugin can be switched off without renaming its `init.lua` or moving its
+//! folder. The loader reads the file on every load/reload; the `!plugin
+//! enable|disable <dir>` console commands reload with the edited state and
+//! write it once that reload has committed.
+//!
+//! ```toml
+//! disabled = ["old_motd", "debug_tools"]
+//! ```
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::teprintln;
+
+pub const PLUGIN_STATE_PATH: &str = ".mcrw/plugins.toml";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PluginState {
+    #[serde(default)]
+    pub disabled: Vec<String>,
+}
+
+impl PluginState {
+    pub fn is_disabled(&self, dirname: &str) -> bool {
+        self.disabled.iter().any(|d| d == dirname)
+    }
+}
+
+/// Read the state file. Missing → empty; unparseable → empty, logged (every
+/// plugin loads rather than the wrapper refusing to start).
+pub fn load_plugin_state(path: &Path) -> PluginState {
+    read_plugin_state(path).unwrap_or_else(|e| {
+        teprintln!("[MCRW] [ERROR] {e}; treating every plugin as enabled");
+        PluginState::default()
+    })
+}
+
+/// Read the state file, failing on anything but a missing file. The write
+/// path uses this so a file it cannot parse is never replaced by an empty one.
+fn read_plugin_state(path: &Path) -> Result<PluginState, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(t) => t,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(PluginState::default()),
+        Err(e) => return Err(format!("read {}: {e}", path.display())),
+    };
+    toml::from_str(&text).map_err(|e| format!("parse {}: {e}", path.display()))
+}
+
+/// The state at `path` with `dirname` enabled or disabled, not yet written.
+/// `None` when the plugin already is in the requested state. Fails when the
+/// existing file cannot be read or parsed, so it is never replaced blindly.
+pub fn toggled_plugin_state(
+    path: &Path,
+    dirname: &str,
+    enabled: bool,
+) -> Result<Option<PluginState>, String> {
+    let mut state = read_plugin_state(path)
+        .map_err(|e| format!("{e}; fix or remove the file, it was left unchanged"))?;
+    if state.is_disabled(dirname) != enabled {
+        return Ok(None);
+    }
+    if enabled {
+        state.disabled.retain(|d| d != dirname);
+    } else {
+        state.disabled.push(dirname.to_string());
+        state.disabled.sort();
+    }
+    Ok(Some(state))
+}
+
+pub fn save_plugin_state(path: &Path, state: &PluginState) -> Result<(), String> {
+    let text = toml::to_string(state).map_err(|e| e.to_string())?;
+    let write = |path: &Path| -> std::io::Result<()> {
+        if let Some(parent) = path.parent() {
+            if !parent.as_os_str().is_empty() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+        fs::write(path, text)
+    };
+    write(path).map_err(|e| format!("write {}: {e}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_path(tag: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mcrw_plugin_state_{tag}"));
+        let _ = fs::remove_dir_all(&dir);
+        dir.join("plugins.toml")
+    }
+
+    fn set_plugin_enabled(path: &Path, dirname: &str, enabled: bool) -> Result<bool, String> {
+        let Some(state) = toggled_plugin_state(path, dirname, enabled)? else {
+            return Ok(false);
+        };
+        save_plugin_state(path, &state)?;
+        Ok(true)
+    }
+
+    #[test]
+    fn missing_and_corrupt_file_enable_everything() {
+        let path = temp_path("missing");
+        assert!(load_plugin_state(&path).disabled.is_empty());
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "disabled = [").unwrap();
+        assert!(load_plugin_state(&path).disabled.is_empty());
+    }
+
+    #[test]
+    fn disable_then_enable_roundtrips() {
+        let path = temp_path("roundtrip");
+        assert!(set_plugin_enabled(&path, "motd", false).unwrap());
+        assert!(set_plugin_enabled(&path, "afk", false).unwrap());
+        // Already disabled: no change reported.
+        assert!(!set_plugin_enabled(&path, "motd", false).unwrap());
+        let state = load_plugin_state(&path);
+        assert_eq!(state.disabled, ["afk", "motd"]);
+
+        assert!(set_plugin_enabled(&path, "motd", true).unwrap());
+        assert!(!set_plugin_enabled(&path, "motd", true).unwrap());
+        assert!(!load_plugin_state(&path).is_disabled("motd"));
+        assert!(load_plugin_state(&path).is_disabled("afk"));
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn corrupt_file_is_not_overwritten() {
+        let path = temp_path("corrupt_write");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "disabled = [\"afk\", \"motd\"\n").unwrap();
+        let err = toggled_plugin_state(&path, "afk", true).unwrap_err();
+        assert!(err.contains("left unchanged"), "{err}");
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "disabled = [\"afk\", \"motd\"\n"
+        );
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
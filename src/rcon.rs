@@ -226,7 +226,7 @@ pub fn resolve_settings(cfg: &RconConfig) -> Option<RconConnectInfo> {
     })
 }
 
-fn read_server_properties() -> HashMap<String, String> {
+pub fn read_server_properties() -> HashMap<String, String> {
     let mut map = HashMap::new();
     if let Ok(content) = fs::read_to_string("server.properties") {
         for line in content.lines() {
@@ -15,8 +15,21 @@
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
 //! `mcrstw init <name>` — generate the skeleton of a new Lua plugin.
+//!
+//! Without `--template` this writes the hello-world trio (meta.toml,
+//! init.lua, config.json). A template adds a worked example, a `tests/`
+//! directory runnable with plain `lua`, and a `.luarc.json` pointing the Lua
+//! Language Server at the API definitions, which are generated (as for
+//! `tools/lua-types/mcrw.lua`) into `.mcrw/lua-types/`. Templates are built in
+//! or live in `~/.config/mcrw/templates/<template>/`; a user template with a
+//! built-in's name replaces it.
+//!
+//! Template files may use `{{name}}`, `{{description}}`, `{{authors}}` and
+//! `{{mcrw_version}}`, substituted when the plugin is created.
 
 use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
 use std::path::{Path, PathBuf};
 
 /// Reasons `run_init` can refuse to scaffold a plugin.
@@ -26,6 +39,11 @@ pub enum ScaffoldError {
     InvalidName(String),
     /// A plugin directory with this name already exists; we never overwrite.
     AlreadyExists(PathBuf),
+    /// No built-in or user template has this name.
+    UnknownTemplate {
+        name: String,
+        available: Vec<String>,
+    },
     /// An underlying filesystem operation failed.
     Io(std::io::Error),
 }
@@ -41,6 +59,11 @@ impl fmt::Display for ScaffoldError {
             ScaffoldError::AlreadyExists(path) => {
                 write!(f, "plugin directory already exists: {}", path.display())
             }
+            ScaffoldError::UnknownTemplate { name, available } => write!(
+                f,
+                "unknown template {name:?}; available: {}",
+                available.join(", ")
+            ),
             ScaffoldError::Io(e) => write!(f, "filesystem error: {e}"),
         }
     }
@@ -64,12 +87,156 @@ fn valid_name(name: &str) -> bool {
     chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
+/// A template shipped in the binary.
+struct Template {
+    name: &'static str,
+    /// Written to meta.toml's `permissions` (see `[permissions]`).
+    permissions: &'static [&'static str],
+    /// Path inside the plugin directory → contents.
+    files: &'static [(&'static str, &'static str)],
+}
+
+const HARNESS_LUA: &str = include_str!("scaffold/templates/harness.lua");
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        name: "chat-command",
+        permissions: &[],
+        files: &[
+            (
+                "init.lua",
+                include_str!("scaffold/templates/chat-command/init.lua"),
+            ),
+            (
+                "config.json",
+                include_str!("scaffold/templates/chat-command/config.json"),
+            ),
+            (
+                "tests/init_spec.lua",
+                include_str!("scaffold/templates/chat-command/init_spec.lua"),
+            ),
+        ],
+    },
+    Template {
+        name: "cron-job",
+        permissions: &[],
+        files: &[
+            (
+                "init.lua",
+                include_str!("scaffold/templates/cron-job/init.lua"),
+            ),
+            (
+                "config.json",
+                include_str!("scaffold/templates/cron-job/config.json"),
+            ),
+            (
+                "tests/init_spec.lua",
+                include_str!("scaffold/templates/cron-job/init_spec.lua"),
+            ),
+        ],
+    },
+    Template {
+        name: "http-webhook",
+        permissions: &["http"],
+        files: &[
+            (
+                "init.lua",
+                include_str!("scaffold/templates/http-webhook/init.lua"),
+            ),
+            (
+                "config.json",
+                include_str!("scaffold/templates/http-webhook/config.json"),
+            ),
+            (
+                "tests/init_spec.lua",
+                include_str!("scaffold/templates/http-webhook/init_spec.lua"),
+            ),
+        ],
+    },
+    Template {
+        name: "python-bridge",
+        permissions: &["python"],
+        files: &[
+            (
+                "init.lua",
+                include_str!("scaffold/templates/python-bridge/init.lua"),
+            ),
+            (
+                "scripts/bridge.py",
+                include_str!("scaffold/templates/python-bridge/bridge.py"),
+            ),
+            (
+                "tests/init_spec.lua",
+                include_str!("scaffold/templates/python-bridge/init_spec.lua"),
+            ),
+        ],
+    },
+    Template {
+        name: "store-demo",
+        permissions: &[],
+        files: &[
+            (
+                "init.lua",
+                include_str!("scaffold/templates/store-demo/init.lua"),
+            ),
+            (
+                "tests/init_spec.lua",
+                include_str!("scaffold/templates/store-demo/init_spec.lua"),
+            ),
+        ],
+    },
+];
+
+/// Where the editor definitions are copied, relative to the server directory.
+const TYPES_DIR: &str = ".mcrw/lua-types";
+
+/// How `run_init` fills in a new plugin.
+#[derive(Debug, Default)]
+pub struct InitOptions {
+    /// `--template`; `None` writes the hello-world trio.
+    pub template: Option<String>,
+    /// meta.toml `description`; a TODO placeholder if `None`.
+    pub description: Option<String>,
+    pub authors: Vec<String>,
+    /// Directory of user templates; `None` means built-ins only.
+    pub user_templates: Option<PathBuf>,
+}
+
+/// `$XDG_CONFIG_HOME/mcrw/templates`, falling back to `~/.config/mcrw/templates`.
+pub fn user_templates_dir() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|v| !v.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))?;
+    Some(config.join("mcrw").join("templates"))
+}
+
+/// Built-in template names plus the directories in `user_dir`, sorted.
+pub fn template_names(user_dir: Option<&Path>) -> Vec<String> {
+    let mut names: Vec<String> = TEMPLATES.iter().map(|t| t.name.to_string()).collect();
+    if let Some(Ok(entries)) = user_dir.map(fs::read_dir) {
+        names.extend(
+            entries
+                .filter_map(|e| e.ok())
+                .filter(|e| e.path().is_dir())
+                .filter_map(|e| e.file_name().to_str().map(String::from)),
+        );
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
 /// Scaffold a new plugin under `<base_dir>/lua_plugins/<name>/`.
 ///
 /// `base_dir` is the directory the wrapper runs in (the server directory);
 /// taking it as a parameter keeps the function testable against a tempdir.
 /// On success returns the paths of every file created, in a stable order.
-pub fn run_init(base_dir: &Path, name: &str) -> Result<Vec<PathBuf>, ScaffoldError> {
+pub fn run_init(
+    base_dir: &Path,
+    name: &str,
+    opts: &InitOptions,
+) -> Result<Vec<PathBuf>, ScaffoldError> {
     if !valid_name(name) {
         return Err(ScaffoldError::InvalidName(name.to_string()));
     }
@@ -78,35 +245,225 @@ pub fn run_init(base_dir: &Path, name: &str) -> Result<Vec<PathBuf>, ScaffoldErr
     if plugin_dir.exists() {
         return Err(ScaffoldError::AlreadyExists(plugin_dir));
     }
-    std::fs::create_dir_all(&plugin_dir)?;
 
-    let files = [
-        ("meta.toml", meta_toml(name)),
-        ("init.lua", init_lua(name)),
-        ("config.json", config_json()),
+    let Some(template) = &opts.template else {
+        fs::create_dir_all(&plugin_dir)?;
+        let files = [
+            ("meta.toml", meta_toml(name, opts, &[])),
+            ("init.lua", init_lua(name)),
+            ("config.json", config_json()),
+        ];
+        let mut created = Vec::with_capacity(files.len());
+        for (filename, contents) in files {
+            let path = plugin_dir.join(filename);
+            fs::write(&path, contents)?;
+            created.push(path);
+        }
+        return Ok(created);
+    };
+
+    // Resolve the template before creating anything. Names are looked up as
+    // directories, so keep them to plain words.
+    if template.is_empty()
+        || !template
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(ScaffoldError::UnknownTemplate {
+            name: template.clone(),
+            available: template_names(opts.user_templates.as_deref()),
+        });
+    }
+    let user_dir = opts
+        .user_templates
+        .as_deref()
+        .map(|d| d.join(template))
+        .filter(|d| d.is_dir());
+    let builtin = TEMPLATES.iter().find(|t| t.name == *template);
+    let (mut files, permissions) = match (user_dir, builtin) {
+        (Some(dir), _) => (user_template_files(&dir)?, &[][..]),
+        (None, Some(t)) => (
+            t.files
+                .iter()
+                .map(|(p, c)| (p.to_string(), c.as_bytes().to_vec()))
+                .collect(),
+            t.permissions,
+        ),
+        (None, None) => {
+            return Err(ScaffoldError::UnknownTemplate {
+                name: template.clone(),
+                available: template_names(opts.user_templates.as_deref()),
+            });
+        }
+    };
+
+    // Placeholders are filled in before the defaults are added, which hold
+    // their final values. Binary files in user templates are copied as they
+    // are.
+    for (rel, contents) in &mut files {
+        if let Ok(text) = std::str::from_utf8(contents) {
+            let vars = template_vars(name, opts, rel.ends_with(".toml"));
+            *contents = render(text, &vars).into_bytes();
+        }
+    }
+
+    // Anything the template does not provide itself.
+    let defaults = [
+        ("meta.toml", meta_toml(name, opts, permissions)),
+        (".luarc.json", luarc_json()),
+        (
+            "tests/harness.lua",
+            render(HARNESS_LUA, &template_vars(name, opts, false)),
+        ),
     ];
+    for (path, contents) in defaults {
+        if !files.iter().any(|(p, _)| p == path) {
+            files.push((path.to_string(), contents.into_bytes()));
+        }
+    }
+    files.sort_by(|a, b| a.0.cmp(&b.0));
 
-    let mut created = Vec::with_capacity(files.len());
-    for (filename, contents) in files {
-        let path = plugin_dir.join(filename);
-        std::fs::write(&path, contents)?;
+    let mut created = Vec::with_capacity(files.len() + 1);
+    for (rel, contents) in files {
+        let path = plugin_dir.join(&rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&path, contents)?;
         created.push(path);
     }
+
+    // Refreshed on every templated init, so it matches this wrapper.
+    let types = base_dir.join(TYPES_DIR).join("mcrw.lua");
+    fs::create_dir_all(base_dir.join(TYPES_DIR))?;
+    fs::write(&types, crate::api_spec::lua_stubs())?;
+    created.push(types);
     Ok(created)
 }
 
-fn meta_toml(name: &str) -> String {
-    format!(
+// Every file under a user template directory, keyed by '/'-separated path.
+fn user_template_files(dir: &Path) -> io::Result<Vec<(String, Vec<u8>)>> {
+    let mut files = Vec::new();
+    for entry in walkdir::WalkDir::new(dir).sort_by_file_name() {
+        let entry = entry.map_err(io::Error::other)?;
+        if !entry.path().is_file() {
+            continue;
+        }
+        let rel = entry.path().strip_prefix(dir).unwrap_or(entry.path());
+        let key: Vec<&str> = rel
+            .components()
+            .filter_map(|c| c.as_os_str().to_str())
+            .collect();
+        files.push((key.join("/"), fs::read(entry.path())?));
+    }
+    Ok(files)
+}
+
+// Placeholder values. In `.toml` files they are TOML values, quoted and
+// escaped (`{{authors}}` an array), so prompt answers cannot break the file;
+// elsewhere they are the plain text.
+fn template_vars(name: &str, opts: &InitOptions, toml: bool) -> [(&'static str, String); 4] {
+    let description = description(name, opts);
+    let mcrw_version = format!(">={}", env!("CARGO_PKG_VERSION"));
+    if toml {
+        return [
+            ("{{name}}", toml_str(name)),
+            ("{{description}}", toml_str(&description)),
+            ("{{authors}}", toml_list(&opts.authors)),
+            ("{{mcrw_version}}", toml_str(&mcrw_version)),
+        ];
+    }
+    [
+        ("{{name}}", name.to_string()),
+        ("{{description}}", description),
+        ("{{authors}}", opts.authors.join(", ")),
+        ("{{mcrw_version}}", mcrw_version),
+    ]
+}
+
+fn render(text: &str, vars: &[(&str, String)]) -> String {
+    vars.iter().fold(text.to_string(), |acc, (key, value)| {
+        acc.replace(key, value)
+    })
+}
+
+fn description(name: &str, opts: &InitOptions) -> String {
+    opts.description
+        .clone()
+        .unwrap_or_else(|| format!("TODO: describe {name}"))
+}
+
+// A TOML string literal, escaped.
+fn toml_str(s: &str) -> String {
+    toml::Value::String(s.to_string()).to_string()
+}
+
+fn toml_list<S: AsRef<str>>(items: &[S]) -> String {
+    let items: Vec<String> = items.iter().map(|s| toml_str(s.as_ref())).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn meta_toml(name: &str, opts: &InitOptions, permissions: &[&str]) -> String {
+    let mut meta = format!(
         "name = \"{name}\"\n\
          version = \"0.1.0\"\n\
-         description = \"TODO: describe {name}\"\n\
-         authors = []\n\
+         description = {}\n\
+         authors = {}\n\
          dependencies = []\n\
          mcrw_version = \">={}\"\n",
+        toml_str(&description(name, opts)),
+        toml_list(&opts.authors),
         env!("CARGO_PKG_VERSION")
+    );
+    if !permissions.is_empty() {
+        meta.push_str(&format!("permissions = {}\n", toml_list(permissions)));
+    }
+    meta
+}
+
+// The plugin directory is the editor workspace, so the library path is
+// relative to it.
+fn luarc_json() -> String {
+    let luarc = serde_json::json!({
+        "$schema": "https://raw.githubusercontent.com/LuaLS/vscode-lua/master/setting/schema.json",
+        "runtime.version": "Lua 5.4",
+        "runtime.path": ["?.lua", "?/init.lua"],
+        "workspace.library": [format!("../../{TYPES_DIR}")],
+        "workspace.checkThirdParty": false,
+        "diagnostics.globals": ["Server"],
+    });
+    format!(
+        "{}\n",
+        serde_json::to_string_pretty(&luarc).unwrap_or_default()
     )
 }
 
+/// Ask for a description and authors on `output`, reading answers from
+/// `input`. An empty answer keeps the default (a TODO description, no
+/// authors).
+pub fn prompt_details<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    name: &str,
+) -> io::Result<(Option<String>, Vec<String>)> {
+    let mut ask = |question: &str| -> io::Result<String> {
+        write!(output, "{question}")?;
+        output.flush()?;
+        let mut line = String::new();
+        input.read_line(&mut line)?;
+        Ok(line.trim().to_string())
+    };
+    let description = ask(&format!("Description of {name} (optional): "))?;
+    let authors = ask("Authors, comma-separated (optional): ")?;
+    let authors = authors
+        .split(',')
+        .map(str::trim)
+        .filter(|a| !a.is_empty())
+        .map(String::from)
+        .collect();
+    Ok(((!description.is_empty()).then_some(description), authors))
+}
+
 fn config_json() -> String {
     "{\n  \"enabled\": true\n}\n".to_string()
 }
@@ -148,10 +505,14 @@ mod tests {
         dir
     }
 
+    fn run_init_default(base: &Path, name: &str) -> Result<Vec<PathBuf>, ScaffoldError> {
+        run_init(base, name, &InitOptions::default())
+    }
+
     #[test]
     fn creates_three_files_in_plugin_dir() {
         let base = tmpdir("creates_three");
-        let created = run_init(&base, "myplugin").expect("should scaffold");
+        let created = run_init_default(&base, "myplugin").expect("should scaffold");
 
         let plugin_dir = base.join("lua_plugins").join("myplugin");
         assert!(plugin_dir.join("meta.toml").is_file());
@@ -165,7 +526,7 @@ mod tests {
     #[test]
     fn meta_toml_parses_and_carries_name_and_version() {
         let base = tmpdir("meta_parses");
-        run_init(&base, "coolplugin").unwrap();
+        run_init_default(&base, "coolplugin").unwrap();
 
         let meta_str =
             fs::read_to_string(base.join("lua_plugins/coolplugin/meta.toml")).unwrap();
@@ -186,7 +547,7 @@ mod tests {
     #[test]
     fn config_json_is_valid_json() {
         let base = tmpdir("config_json");
-        run_init(&base, "p").unwrap();
+        run_init_default(&base, "p").unwrap();
 
         let cfg = fs::read_to_string(base.join("lua_plugins/p/config.json")).unwrap();
         let parsed: serde_json::Value = serde_json::from_str(&cfg).unwrap();
@@ -198,7 +559,7 @@ mod tests {
     #[test]
     fn init_lua_mentions_plugin_name() {
         let base = tmpdir("init_name");
-        run_init(&base, "greeter").unwrap();
+        run_init_default(&base, "greeter").unwrap();
 
         let lua = fs::read_to_string(base.join("lua_plugins/greeter/init.lua")).unwrap();
         assert!(lua.contains("greeter"), "init.lua should mention the name");
@@ -217,7 +578,7 @@ mod tests {
         fs::create_dir_all(&plugin_dir).unwrap();
         fs::write(plugin_dir.join("init.lua"), "-- user's own code\n").unwrap();
 
-        let err = run_init(&base, "dup").expect_err("must refuse");
+        let err = run_init_default(&base, "dup").expect_err("must refuse");
         assert!(matches!(err, ScaffoldError::AlreadyExists(_)));
         // Existing content untouched.
         assert_eq!(
@@ -231,7 +592,7 @@ mod tests {
     #[test]
     fn rejects_empty_name() {
         let base = tmpdir("empty_name");
-        let err = run_init(&base, "").expect_err("must reject");
+        let err = run_init_default(&base, "").expect_err("must reject");
         assert!(matches!(err, ScaffoldError::InvalidName(_)));
         fs::remove_dir_all(&base).unwrap();
     }
@@ -240,7 +601,7 @@ mod tests {
     fn rejects_name_with_illegal_chars() {
         let base = tmpdir("illegal_name");
         for bad in ["my-plugin", "my plugin", "../escape", "a/b", "naïve"] {
-            let err = run_init(&base, bad).expect_err("must reject");
+            let err = run_init_default(&base, bad).expect_err("must reject");
             assert!(
                 matches!(err, ScaffoldError::InvalidName(_)),
                 "{bad:?} should be rejected"
@@ -252,7 +613,7 @@ mod tests {
     #[test]
     fn rejects_name_starting_with_digit() {
         let base = tmpdir("digit_name");
-        let err = run_init(&base, "1plugin").expect_err("must reject");
+        let err = run_init_default(&base, "1plugin").expect_err("must reject");
         assert!(matches!(err, ScaffoldError::InvalidName(_)));
         fs::remove_dir_all(&base).unwrap();
     }
@@ -260,8 +621,177 @@ mod tests {
     #[test]
     fn accepts_underscores_and_digits_after_first() {
         let base = tmpdir("good_name");
-        run_init(&base, "my_plugin_2").expect("should accept");
+        run_init_default(&base, "my_plugin_2").expect("should accept");
         assert!(base.join("lua_plugins/my_plugin_2/init.lua").is_file());
         fs::remove_dir_all(&base).unwrap();
     }
+
+    fn with_template(template: &str) -> InitOptions {
+        InitOptions {
+            template: Some(template.to_string()),
+            ..Default::default()
+        }
+    }
+
+    // Run the generated tests/init_spec.lua the way `lua` would from the
+    // plugin directory, without changing this process's working directory.
+    fn run_spec(plugin_dir: &Path) {
+        let lua = mlua::Lua::new();
+        let dir = plugin_dir.to_str().unwrap();
+        lua.load(format!(
+            "package.path = {:?} .. '/tests/?.lua'
+             local root = {:?}
+             function dofile(path) return assert(loadfile(root .. '/' .. path))() end",
+            dir, dir
+        ))
+        .exec()
+        .unwrap();
+        let spec = fs::read_to_string(plugin_dir.join("tests/init_spec.lua")).unwrap();
+        lua.load(spec).set_name("init_spec.lua").exec().unwrap();
+    }
+
+    #[test]
+    fn builtin_templates_scaffold_and_pass_their_tests() {
+        for t in TEMPLATES {
+            let base = tmpdir(&format!("template_{}", t.name));
+            let created = run_init(&base, "demo", &with_template(t.name)).unwrap();
+            let plugin_dir = base.join("lua_plugins/demo");
+            for file in [
+                "init.lua",
+                "meta.toml",
+                ".luarc.json",
+                "tests/harness.lua",
+                "tests/init_spec.lua",
+            ] {
+                assert!(
+                    created.contains(&plugin_dir.join(file)),
+                    "{}: {file}",
+                    t.name
+                );
+            }
+            for path in &created {
+                let text = fs::read_to_string(path).unwrap();
+                assert!(
+                    !text.contains("{{"),
+                    "{}: unrendered placeholder in {}",
+                    t.name,
+                    path.display()
+                );
+            }
+            assert!(base.join(".mcrw/lua-types/mcrw.lua").is_file());
+
+            let meta: crate::lua_ctx::PluginMeta =
+                toml::from_str(&fs::read_to_string(plugin_dir.join("meta.toml")).unwrap()).unwrap();
+            assert_eq!(meta.permissions, t.permissions);
+            let luarc: serde_json::Value =
+                serde_json::from_str(&fs::read_to_string(plugin_dir.join(".luarc.json")).unwrap())
+                    .unwrap();
+            assert_eq!(luarc["workspace.library"][0], "../../.mcrw/lua-types");
+
+            run_spec(&plugin_dir);
+            fs::remove_dir_all(&base).unwrap();
+        }
+    }
+
+    #[test]
+    fn unknown_template_lists_the_available_ones() {
+        let base = tmpdir("unknown_template");
+        for bad in ["nope", "../chat-command", ""] {
+            let err = run_init(&base, "demo", &with_template(bad)).unwrap_err();
+            assert!(
+                matches!(err, ScaffoldError::UnknownTemplate { .. }),
+                "{bad:?}"
+            );
+            assert!(err.to_string().contains("chat-command, cron-job"), "{err}");
+        }
+        assert!(!base.join("lua_plugins/demo").exists());
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn user_templates_are_rendered_and_completed() {
+        let base = tmpdir("user_template");
+        let templates = base.join("templates");
+        fs::create_dir_all(templates.join("mine/lib")).unwrap();
+        fs::write(
+            templates.join("mine/init.lua"),
+            "-- {{name}} by {{authors}}\n",
+        )
+        .unwrap();
+        fs::write(
+            templates.join("mine/lib/util.lua"),
+            "return '{{description}}'\n",
+        )
+        .unwrap();
+        fs::write(
+            templates.join("mine/meta.toml"),
+            "name = {{name}}\nversion = \"0.1.0\"\ndescription = {{description}}\n\
+             authors = {{authors}}\nmcrw_version = {{mcrw_version}}\n",
+        )
+        .unwrap();
+        // Overrides the built-in of the same name.
+        fs::create_dir_all(templates.join("cron-job")).unwrap();
+        fs::write(templates.join("cron-job/init.lua"), "-- custom\n").unwrap();
+
+        let opts = InitOptions {
+            template: Some("mine".into()),
+            description: Some("Says \"hi\"".into()),
+            authors: vec!["alice".into(), "bob".into()],
+            user_templates: Some(templates.clone()),
+        };
+        run_init(&base, "demo", &opts).unwrap();
+        let plugin_dir = base.join("lua_plugins/demo");
+        assert_eq!(
+            fs::read_to_string(plugin_dir.join("init.lua")).unwrap(),
+            "-- demo by alice, bob\n"
+        );
+        assert_eq!(
+            fs::read_to_string(plugin_dir.join("lib/util.lua")).unwrap(),
+            "return 'Says \"hi\"'\n"
+        );
+        let meta: crate::lua_ctx::PluginMeta =
+            toml::from_str(&fs::read_to_string(plugin_dir.join("meta.toml")).unwrap()).unwrap();
+        assert_eq!(meta.description, "Says \"hi\"");
+        assert_eq!(meta.authors, ["alice", "bob"]);
+        assert!(plugin_dir.join(".luarc.json").is_file());
+
+        run_init(
+            &base,
+            "other",
+            &InitOptions {
+                template: Some("cron-job".into()),
+                ..opts
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(base.join("lua_plugins/other/init.lua")).unwrap(),
+            "-- custom\n"
+        );
+        assert!(template_names(Some(&templates)).contains(&"mine".to_string()));
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn prompt_reads_description_and_authors() {
+        let mut out = Vec::new();
+        let (desc, authors) = prompt_details(
+            &mut io::Cursor::new("A shop\n alice , ,bob\n"),
+            &mut out,
+            "shop",
+        )
+        .unwrap();
+        assert_eq!(desc.as_deref(), Some("A shop"));
+        assert_eq!(authors, ["alice", "bob"]);
+        assert!(
+            String::from_utf8(out)
+                .unwrap()
+                .contains("Description of shop")
+        );
+
+        let (desc, authors) =
+            prompt_details(&mut io::Cursor::new(""), &mut Vec::new(), "shop").unwrap();
+        assert!(desc.is_none() && authors.is_empty());
+    }
 }
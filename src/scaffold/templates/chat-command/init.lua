@@ -0,0 +1,15 @@
+-- {{name}}: answers `!{{name}}` in chat.
+local wrapper = Server:get_context(...)
+
+local config = wrapper:load_config({
+    reply = "Hello, %s!",
+})
+
+-- A vanilla chat line: "[12:00:00] [Server thread/INFO]: <alice> !{{name}}".
+-- The capture group (the player) becomes the second callback argument.
+wrapper:register(
+    "\\[.*\\] \\[Server thread/INFO\\]: <(\\w+)> !{{name}}$",
+    function(line, player)
+        return { "say " .. string.format(config.reply, player) }
+    end
+)
@@ -0,0 +1,13 @@
+package.path = "tests/?.lua;" .. package.path
+local harness = require("harness")
+
+local w = harness.load()
+assert(#w.triggers == 1, "registers one chat trigger")
+local out = w.triggers[1].callback("[12:00:00] [Server thread/INFO]: <alice> !{{name}}", "alice")
+assert(out[1] == "say Hello, alice!", out[1])
+
+w = harness.load({ reply = "Hi %s" })
+out = w.triggers[1].callback("", "bob")
+assert(out[1] == "say Hi bob", out[1])
+
+print("{{name}}: ok")
@@ -0,0 +1,19 @@
+-- {{name}}: broadcasts a rotating message on a schedule.
+local wrapper = Server:get_context(...)
+
+local config = wrapper:load_config({
+    -- sec min hour day-of-month month day-of-week
+    schedule = "0 */10 * * * *",
+    messages = { "Welcome to the server!" },
+})
+
+local next_index = 1
+
+wrapper:register_cron(config.schedule, function(fire_time)
+    if #config.messages == 0 then
+        return
+    end
+    local msg = config.messages[next_index]
+    next_index = next_index % #config.messages + 1
+    return { "say " .. msg }
+end)
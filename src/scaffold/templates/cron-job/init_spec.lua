@@ -0,0 +1,15 @@
+package.path = "tests/?.lua;" .. package.path
+local harness = require("harness")
+
+local w = harness.load({ messages = { "one", "two" } })
+assert(#w.cron == 1, "registers one cron job")
+assert(w.cron[1].expr == "0 */10 * * * *", w.cron[1].expr)
+local fire = w.cron[1].callback
+assert(fire(0)[1] == "say one")
+assert(fire(0)[1] == "say two")
+assert(fire(0)[1] == "say one", "wraps around")
+
+w = harness.load({ messages = {} })
+assert(w.cron[1].callback(0) == nil, "nothing to say")
+
+print("{{name}}: ok")
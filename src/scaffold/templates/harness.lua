@@ -0,0 +1,81 @@
+-- Stand-in for the MCRW `wrapper` handle, for testing {{name}} without a
+-- server. Run a spec from the plugin directory:
+--
+--     lua tests/init_spec.lua
+--
+-- `harness.load(config)` runs init.lua against a fake wrapper and returns
+-- what it registered and did. Callbacks are called directly: trigger
+-- patterns are Rust regexes, which Lua cannot match.
+local harness = {}
+
+-- Like config.json over the defaults: objects merge key by key, anything
+-- else (arrays included) is replaced.
+local function is_object(t)
+    return type(t) == "table" and next(t) ~= nil and t[1] == nil
+end
+
+local function merge(base, over)
+    for k, v in pairs(over or {}) do
+        if is_object(v) and is_object(base[k]) then
+            merge(base[k], v)
+        else
+            base[k] = v
+        end
+    end
+    return base
+end
+
+function harness.load(config)
+    local w = {
+        triggers = {},  -- { pattern, callback }
+        cron = {},      -- { expr, callback }
+        joins = {},     -- callbacks
+        commands = {},  -- wrapper:command() calls
+        logs = {},
+        http = {},      -- request tables passed to wrapper:http_request
+        python = {},    -- { script, args } passed to wrapper:run_python
+        data = {},      -- wrapper:store() contents
+        -- Set these before firing a callback to change what the fakes return.
+        http_response = { status = 200, ok = true, headers = {}, body = "" },
+        python_result = { stdout = nil, stderr = "", code = 0 },
+    }
+
+    local wrapper = {}
+    function wrapper:log(msg) table.insert(w.logs, msg) end
+    function wrapper:load_config(defaults) return merge(defaults, config) end
+    function wrapper:register(pattern, callback)
+        table.insert(w.triggers, { pattern = pattern, callback = callback })
+    end
+    function wrapper:register_cron(expr, callback)
+        table.insert(w.cron, { expr = expr, callback = callback })
+    end
+    function wrapper:register_on_join(callback) table.insert(w.joins, callback) end
+    function wrapper:command(cmd) table.insert(w.commands, cmd) end
+    function wrapper:http_request(opts)
+        table.insert(w.http, opts)
+        return w.http_response
+    end
+    function wrapper:run_python(script, args)
+        table.insert(w.python, { script = script, args = args or {} })
+        return w.python_result
+    end
+    function wrapper:store()
+        local db = {}
+        function db:get(key) return w.data[key] end
+        function db:set(key, value) w.data[key] = value end
+        function db:delete(key) w.data[key] = nil end
+        function db:keys()
+            local keys = {}
+            for k in pairs(w.data) do table.insert(keys, k) end
+            table.sort(keys)
+            return keys
+        end
+        return db
+    end
+
+    Server = { get_context = function() return wrapper end }
+    dofile("init.lua")
+    return w
+end
+
+return harness
@@ -0,0 +1,20 @@
+-- {{name}}: posts player joins to a webhook.
+local wrapper = Server:get_context(...)
+
+local config = wrapper:load_config({
+    url = "",
+})
+
+wrapper:register_on_join(function(p)
+    if config.url == "" then
+        return
+    end
+    local resp = wrapper:http_request({
+        url = config.url,
+        method = "POST",
+        json = { event = "join", player = p.name },
+    })
+    if not resp.ok then
+        wrapper:log("webhook returned HTTP " .. resp.status)
+    end
+end)
@@ -0,0 +1,18 @@
+package.path = "tests/?.lua;" .. package.path
+local harness = require("harness")
+
+local w = harness.load()
+w.joins[1]({ name = "alice" })
+assert(#w.http == 0, "no request without a url")
+
+w = harness.load({ url = "https://example.com/hook" })
+w.joins[1]({ name = "alice" })
+assert(#w.http == 1)
+assert(w.http[1].method == "POST")
+assert(w.http[1].json.player == "alice")
+
+w.http_response = { status = 500, ok = false, headers = {}, body = "" }
+w.joins[1]({ name = "bob" })
+assert(w.logs[#w.logs] == "webhook returned HTTP 500", w.logs[#w.logs])
+
+print("{{name}}: ok")
@@ -0,0 +1,17 @@
+"""Called by {{name}}/init.lua as: bridge.py <player> <text>
+
+Anything written to stderr shows up in the wrapper's log; the last line of
+stdout must be one JSON value, returned to Lua as `result.stdout`.
+"""
+import json
+import sys
+
+
+def main():
+    player, text = sys.argv[1], sys.argv[2]
+    print(json.dumps({"reply": f"{player} said {text!r}"}))
+    return 0
+
+
+if __name__ == "__main__":
+    sys.exit(main())
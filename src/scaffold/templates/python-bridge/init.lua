@@ -0,0 +1,14 @@
+-- {{name}}: hands `!{{name}} <text>` to scripts/bridge.py.
+local wrapper = Server:get_context(...)
+
+wrapper:register(
+    "\\[.*\\] \\[Server thread/INFO\\]: <(\\w+)> !{{name}} (.+)$",
+    function(line, player, text)
+        local result = wrapper:run_python("scripts/bridge.py", { player, text })
+        if result.code ~= 0 or type(result.stdout) ~= "table" then
+            wrapper:log("bridge.py failed: " .. result.stderr)
+            return
+        end
+        return { "say " .. result.stdout.reply }
+    end
+)
@@ -0,0 +1,15 @@
+package.path = "tests/?.lua;" .. package.path
+local harness = require("harness")
+
+local w = harness.load()
+w.python_result = { stdout = { reply = "hi" }, stderr = "", code = 0 }
+local out = w.triggers[1].callback("", "alice", "hello")
+assert(w.python[1].script == "scripts/bridge.py")
+assert(w.python[1].args[1] == "alice" and w.python[1].args[2] == "hello")
+assert(out[1] == "say hi", out[1])
+
+w.python_result = { stdout = nil, stderr = "boom", code = 1 }
+assert(w.triggers[1].callback("", "alice", "hello") == nil)
+assert(w.logs[#w.logs] == "bridge.py failed: boom")
+
+print("{{name}}: ok")
@@ -0,0 +1,14 @@
+-- {{name}}: counts how often each player typed `!{{name}}`, across restarts.
+local wrapper = Server:get_context(...)
+
+local db = wrapper:store()
+
+wrapper:register(
+    "\\[.*\\] \\[Server thread/INFO\\]: <(\\w+)> !{{name}}$",
+    function(line, player)
+        local key = "count." .. player
+        local count = (db:get(key) or 0) + 1
+        db:set(key, count)
+        return { "say " .. player .. " has used !{{name}} " .. count .. " time(s)" }
+    end
+)
@@ -0,0 +1,12 @@
+package.path = "tests/?.lua;" .. package.path
+local harness = require("harness")
+
+local w = harness.load()
+local fire = w.triggers[1].callback
+fire("", "alice")
+local out = fire("", "alice")
+assert(out[1] == "say alice has used !{{name}} 2 time(s)", out[1])
+fire("", "bob")
+assert(w.data["count.alice"] == 2 and w.data["count.bob"] == 1)
+
+print("{{name}}: ok")
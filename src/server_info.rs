@@ -0,0 +1,254 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! What the wrapper knows about the server it runs, for
+//! `wrapper:server_info()` and `!status`.
+//!
+//! server.properties gives the configured values; the startup lines
+//! ("Starting minecraft server version …", "Preparing level …", "Starting
+//! Minecraft server on *:25565", "Done (3.0s)!") replace them with what the
+//! server actually did. The flavor is the configured or detected one
+//! (flavor.rs).
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+
+use regex::{Captures, Regex};
+use serde::Serialize;
+
+use crate::flavor::Flavor;
+use crate::log_line::LogRecord;
+
+/// Unknown fields are `None` (nil in Lua).
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ServerInfo {
+    /// Minecraft version, e.g. "1.20.4".
+    pub version: Option<String>,
+    pub flavor: Flavor,
+    pub level_name: Option<String>,
+    pub port: Option<u16>,
+    /// Only while RCON / the query protocol is enabled in server.properties.
+    pub rcon_port: Option<u16>,
+    pub query_port: Option<u16>,
+    pub max_players: Option<u32>,
+    pub online_mode: Option<bool>,
+    /// Startup time the server reports on its "Done" line.
+    pub startup_secs: Option<f64>,
+}
+
+pub type SharedServerInfo = Arc<Mutex<ServerInfo>>;
+
+type Apply = fn(&mut ServerInfo, &Captures);
+
+// Matched against the message. Velocity reports its listener differently.
+static STARTUP_LINES: LazyLock<Vec<(Regex, Apply)>> = LazyLock::new(|| {
+    let lines: [(&str, Apply); 5] = [
+        (r"^Starting minecraft server version (\S+)$", |i, c| {
+            i.version = Some(c[1].to_string())
+        }),
+        (r#"^Preparing level "([^"]*)"$"#, |i, c| {
+            i.level_name = Some(c[1].to_string())
+        }),
+        (r"^Starting Minecraft server on \S*:(\d+)$", |i, c| {
+            i.port = c[1].parse().ok()
+        }),
+        (r"^Listening on /\S*:(\d+)$", |i, c| {
+            i.port = c[1].parse().ok()
+        }),
+        (r"^Done \(([0-9.]+)s\)!", |i, c| {
+            i.startup_secs = c[1].parse().ok()
+        }),
+    ];
+    lines
+        .into_iter()
+        .map(|(p, f)| {
+            (
+                Regex::new(p).expect("built-in startup pattern must compile"),
+                f,
+            )
+        })
+        .collect()
+});
+
+impl ServerInfo {
+    pub fn new(flavor: Flavor, props: &HashMap<String, String>) -> Self {
+        let mut info = Self {
+            flavor,
+            ..Self::default()
+        };
+        info.fill_from_properties(props);
+        info
+    }
+
+    /// Fill the fields still unknown from server.properties. What the
+    /// startup lines reported is kept.
+    pub fn fill_from_properties(&mut self, props: &HashMap<String, String>) {
+        let get = |k: &str| props.get(k).map(String::as_str);
+        let enabled = |k: &str| get(k) == Some("true");
+        self.level_name = self
+            .level_name
+            .take()
+            .or_else(|| get("level-name").map(str::to_string));
+        self.port = self.port.or_else(|| get("server-port")?.parse().ok());
+        if enabled("enable-rcon") {
+            self.rcon_port = self.rcon_port.or_else(|| get("rcon.port")?.parse().ok());
+        }
+        if enabled("enable-query") {
+            self.query_port = self.query_port.or_else(|| get("query.port")?.parse().ok());
+        }
+        self.max_players = self
+            .max_players
+            .or_else(|| get("max-players")?.parse().ok());
+        self.online_mode = self
+            .online_mode
+            .or_else(|| get("online-mode").map(|v| v == "true"));
+    }
+
+    /// Take what a startup line reports. Returns true on the "Done" line,
+    /// after which server.properties is worth reading again: a first run
+    /// writes it during startup.
+    pub fn observe(&mut self, rec: &LogRecord) -> bool {
+        let found = STARTUP_LINES
+            .iter()
+            .find_map(|(re, apply)| re.captures(&rec.message).map(|c| (c, apply)));
+        let Some((c, apply)) = found else {
+            return false;
+        };
+        apply(self, &c);
+        rec.message.starts_with("Done (")
+    }
+
+    /// The `!status` report.
+    pub fn status_lines(&self, online: usize) -> Vec<String> {
+        let unknown = || "?".to_string();
+        let mut server = format!(
+            "Server: {} {}",
+            self.flavor,
+            self.version.clone().unwrap_or_else(unknown)
+        );
+        if let Some(level) = &self.level_name {
+            server.push_str(&format!(", level \"{level}\""));
+        }
+        let mut ports = vec![self.port.map_or_else(unknown, |p| p.to_string())];
+        ports.extend(self.rcon_port.map(|p| format!("rcon {p}")));
+        ports.extend(self.query_port.map(|p| format!("query {p}")));
+        server.push_str(&format!(", port {}", ports.join(", ")));
+
+        let mut players = format!(
+            "Players: {online}/{} online",
+            self.max_players.map_or_else(unknown, |m| m.to_string())
+        );
+        match self.online_mode {
+            Some(true) => players.push_str(", online mode"),
+            Some(false) => players.push_str(", offline mode"),
+            None => {}
+        }
+        let started = match self.startup_secs {
+            Some(s) => format!("Started in {s}s"),
+            None => "Still starting".to_string(),
+        };
+        vec![server, players, started]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log_line::LogParser;
+
+    fn props(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn startup_lines_override_properties() {
+        let p = props(&[
+            ("level-name", "world"),
+            ("server-port", "25565"),
+            ("enable-rcon", "true"),
+            ("rcon.port", "25575"),
+            ("enable-query", "false"),
+            ("query.port", "25565"),
+            ("max-players", "20"),
+            ("online-mode", "true"),
+        ]);
+        let mut info = ServerInfo::new(Flavor::Vanilla, &p);
+        let parser = LogParser::default();
+        let mut observe = |line: &str| info.observe(&parser.parse(line));
+        assert!(!observe(
+            "[12:00:00] [Server thread/INFO]: Starting minecraft server version 1.20.4"
+        ));
+        assert!(!observe(
+            "[12:00:00] [Server thread/INFO]: Starting Minecraft server on *:25570"
+        ));
+        assert!(!observe(
+            "[12:00:00] [Server thread/INFO]: Preparing level \"survival\""
+        ));
+        assert!(observe(
+            "[12:00:03] [Server thread/INFO]: Done (3.021s)! For help, type \"help\""
+        ));
+        assert_eq!(
+            info,
+            ServerInfo {
+                version: Some("1.20.4".into()),
+                flavor: Flavor::Vanilla,
+                level_name: Some("survival".into()),
+                port: Some(25570),
+                rcon_port: Some(25575),
+                query_port: None,
+                max_players: Some(20),
+                online_mode: Some(true),
+                startup_secs: Some(3.021),
+            }
+        );
+        // A later read of server.properties does not undo the startup lines.
+        info.fill_from_properties(&p);
+        assert_eq!(
+            (info.port, info.level_name.as_deref()),
+            (Some(25570), Some("survival"))
+        );
+        assert_eq!(
+            info.status_lines(3),
+            [
+                "Server: vanilla 1.20.4, level \"survival\", port 25570, rcon 25575",
+                "Players: 3/20 online, online mode",
+                "Started in 3.021s",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_fields_stay_empty() {
+        let mut info = ServerInfo::new(Flavor::Auto, &HashMap::new());
+        assert!(
+            !info.observe(
+                &LogParser::default().parse("[12:00:00 INFO]: Listening on /0.0.0.0:25577")
+            )
+        );
+        assert_eq!(info.port, Some(25577));
+        assert_eq!(
+            info.status_lines(0),
+            [
+                "Server: auto ?, port 25577",
+                "Players: 0/? online",
+                "Still starting"
+            ]
+        );
+    }
+}
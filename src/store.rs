@@ -41,6 +41,7 @@ use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use std::time::{Duration, Instant};
 
+use crate::api_spec::{Registrar, STORE};
 use crate::teprintln;
 use mlua::LuaSerdeExt;
 use mlua::{Lua, UserData, UserDataMethods, Value};
@@ -190,8 +191,9 @@ impl StoreHandle {
 
 impl UserData for StoreHandle {
     fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        let mut methods = Registrar::methods(&STORE, methods);
         // get(key) -> value | nil
-        methods.add_method("get", |lua: &Lua, this: &Self, key: String| {
+        methods.method("get", |lua: &Lua, this: &Self, key: String| {
             match this.registry.get(&this.namespace, &key) {
                 Some(jv) => lua.to_value(&jv),
                 None => Ok(Value::Nil),
@@ -200,7 +202,7 @@ impl UserData for StoreHandle {
 
         // set(key, value). A nil value deletes the key (so set/delete share one
         // mental model and storing `nil` can never resurrect as JSON null).
-        methods.add_method(
+        methods.method(
             "set",
             |lua: &Lua, this: &Self, (key, val): (String, Value)| {
                 if val == Value::Nil {
@@ -214,13 +216,13 @@ impl UserData for StoreHandle {
         );
 
         // delete(key)
-        methods.add_method("delete", |_lua: &Lua, this: &Self, key: String| {
+        methods.method("delete", |_lua: &Lua, this: &Self, key: String| {
             this.registry.delete(&this.namespace, &key);
             Ok(())
         });
 
         // keys() -> { string }
-        methods.add_method("keys", |lua: &Lua, this: &Self, ()| {
+        methods.method("keys", |lua: &Lua, this: &Self, ()| {
             let t = lua.create_table()?;
             for (i, k) in this.registry.keys(&this.namespace).into_iter().enumerate() {
                 t.set(i + 1, k)?;
@@ -229,10 +231,11 @@ impl UserData for StoreHandle {
         });
 
         // flush() — force an immediate durable write.
-        methods.add_method("flush", |_lua: &Lua, this: &Self, ()| {
+        methods.method("flush", |_lua: &Lua, this: &Self, ()| {
             this.registry.flush();
             Ok(())
         });
+        methods.finish();
     }
 }
 
@@ -0,0 +1,268 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! Plugin timers: `wrapper:after`, `wrapper:every` and the handles they return.
+//!
+//! All timers live in one [`TimerWheel`] driven by a `select!` arm of the main
+//! loop, which sleeps until the earliest deadline and wakes early whenever a
+//! timer is added. A timer belongs to the plugin that created it and is taken
+//! out with the plugin's other registrations on reload and unload, so a
+//! reloaded plugin starts with none.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use mlua::{Function, Lua, RegistryKey, UserData, UserDataMethods};
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+use crate::api_spec::{Registrar, TIMER};
+use crate::teprintln;
+
+pub struct Timer {
+    id: u64,
+    pub plugin: String,
+    callback: RegistryKey,
+    due: Instant,
+    // Period of a `wrapper:every` timer; None for `wrapper:after`.
+    every: Option<Duration>,
+}
+
+impl Timer {
+    // Metrics key: timers have no pattern, so report how they were created.
+    fn key(&self) -> String {
+        match self.every {
+            Some(period) => format!("every {}ms", period.as_millis()),
+            None => "after".to_string(),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct TimerWheel {
+    timers: Mutex<Vec<Timer>>,
+    next_id: AtomicU64,
+    changed: Notify,
+}
+
+pub type TimerList = Arc<TimerWheel>;
+
+impl TimerWheel {
+    pub fn add(
+        &self,
+        plugin: &str,
+        callback: RegistryKey,
+        delay: Duration,
+        every: Option<Duration>,
+    ) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.timers.lock().unwrap().push(Timer {
+            id,
+            plugin: plugin.to_string(),
+            callback,
+            due: Instant::now() + delay,
+            every,
+        });
+        // notify_one keeps a permit if the driver is not waiting right now, so
+        // a timer added between next_due() and changed() is not missed.
+        self.changed.notify_one();
+        id
+    }
+
+    /// Returns false if the timer already fired (one-shot), was cancelled, or
+    /// was dropped by a reload.
+    pub fn cancel(&self, id: u64) -> bool {
+        let mut g = self.timers.lock().unwrap();
+        let before = g.len();
+        g.retain(|t| t.id != id);
+        g.len() != before
+    }
+
+    pub fn is_pending(&self, id: u64) -> bool {
+        self.timers.lock().unwrap().iter().any(|t| t.id == id)
+    }
+
+    pub fn next_due(&self) -> Option<Instant> {
+        self.timers.lock().unwrap().iter().map(|t| t.due).min()
+    }
+
+    /// Resolves when a timer was added since the last call.
+    pub async fn changed(&self) {
+        self.changed.notified().await
+    }
+
+    pub fn take_where(&self, owned: impl Fn(&str) -> bool) -> Vec<Timer> {
+        let mut g = self.timers.lock().unwrap();
+        let (taken, kept) = std::mem::take(&mut *g)
+            .into_iter()
+            .partition(|t| owned(&t.plugin));
+        *g = kept;
+        taken
+    }
+
+    pub fn restore(&self, timers: Vec<Timer>) {
+        self.timers.lock().unwrap().extend(timers);
+        self.changed.notify_one();
+    }
+
+    /// Every timer due at `now`, earliest first, as (callback, plugin, key).
+    /// One-shot timers are removed; periodic ones move to their next period,
+    /// skipping periods the driver missed rather than firing them in a burst.
+    pub fn drain_due(
+        &self,
+        now: Instant,
+        lookup: impl Fn(&str, &RegistryKey) -> mlua::Result<Function>,
+    ) -> Vec<(Function, String, String)> {
+        let mut due = Vec::new();
+        self.timers.lock().unwrap().retain_mut(|t| {
+            if t.due > now {
+                return true;
+            }
+            match lookup(&t.plugin, &t.callback) {
+                Ok(f) => due.push((t.due, f, t.plugin.clone(), t.key())),
+                Err(e) => teprintln!("[MCRW] [ERROR] timer registry lookup ({}): {e}", t.plugin),
+            }
+            match t.every {
+                Some(period) => {
+                    t.due += period;
+                    if t.due <= now {
+                        t.due = now + period;
+                    }
+                    true
+                }
+                None => false,
+            }
+        });
+        due.sort_by_key(|(at, ..)| *at);
+        due.into_iter()
+            .map(|(_, f, plugin, key)| (f, plugin, key))
+            .collect()
+    }
+}
+
+// Returned by wrapper:after / wrapper:every.
+#[derive(Clone)]
+pub struct TimerHandle {
+    timers: TimerList,
+    id: u64,
+}
+
+impl TimerHandle {
+    pub fn new(timers: TimerList, id: u64) -> Self {
+        Self { timers, id }
+    }
+}
+
+impl UserData for TimerHandle {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        let mut methods = Registrar::methods(&TIMER, methods);
+        methods.method("cancel", |_lua: &Lua, this: &Self, ()| {
+            Ok(this.timers.cancel(this.id))
+        });
+        methods.method("active", |_lua: &Lua, this: &Self, ()| {
+            Ok(this.timers.is_pending(this.id))
+        });
+        methods.finish();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wheel_with(lua: &Lua, timers: &[(&str, u64, Option<u64>)]) -> (TimerWheel, Vec<u64>) {
+        let wheel = TimerWheel::default();
+        let ids = timers
+            .iter()
+            .map(|(plugin, delay, every)| {
+                let f = lua.create_function(|_, ()| Ok(())).unwrap();
+                let key = lua.create_registry_value(f).unwrap();
+                wheel.add(
+                    plugin,
+                    key,
+                    Duration::from_millis(*delay),
+                    every.map(Duration::from_millis),
+                )
+            })
+            .collect();
+        (wheel, ids)
+    }
+
+    fn drain(lua: &Lua, wheel: &TimerWheel, now: Instant) -> Vec<(String, String)> {
+        wheel
+            .drain_due(now, |_, key| lua.registry_value(key))
+            .into_iter()
+            .map(|(_, plugin, key)| (plugin, key))
+            .collect()
+    }
+
+    #[test]
+    fn fires_due_timers_earliest_first() {
+        let lua = Lua::new();
+        let (wheel, _) = wheel_with(
+            &lua,
+            &[("a", 200, None), ("b", 100, Some(100)), ("c", 10_000, None)],
+        );
+        let start = Instant::now();
+        assert!(drain(&lua, &wheel, start).is_empty());
+        let fired = drain(&lua, &wheel, start + Duration::from_millis(250));
+        assert_eq!(
+            fired,
+            [
+                ("b".to_string(), "every 100ms".to_string()),
+                ("a".to_string(), "after".to_string())
+            ]
+        );
+        // The one-shot is gone; the periodic one is rescheduled, not replayed
+        // for the period it missed.
+        let fired = drain(&lua, &wheel, start + Duration::from_millis(300));
+        assert!(fired.is_empty());
+        let fired = drain(&lua, &wheel, start + Duration::from_millis(360));
+        assert_eq!(fired.len(), 1);
+    }
+
+    #[test]
+    fn cancel_and_take_by_plugin() {
+        let lua = Lua::new();
+        let (wheel, ids) = wheel_with(&lua, &[("a", 0, Some(50)), ("b", 0, None), ("a", 0, None)]);
+        assert!(wheel.is_pending(ids[0]));
+        assert!(wheel.cancel(ids[0]));
+        assert!(!wheel.cancel(ids[0]));
+        assert!(!wheel.is_pending(ids[0]));
+
+        let taken = wheel.take_where(|p| p == "a");
+        assert_eq!(taken.len(), 1);
+        assert!(!wheel.is_pending(ids[2]) && wheel.is_pending(ids[1]));
+        wheel.restore(taken);
+        assert!(wheel.is_pending(ids[2]));
+    }
+
+    #[tokio::test]
+    async fn adding_a_timer_wakes_the_driver() {
+        let lua = Lua::new();
+        let (wheel, _) = wheel_with(&lua, &[("a", 0, None)]);
+        // The permit left by add() resolves the first wait immediately.
+        tokio::time::timeout(Duration::from_secs(1), wheel.changed())
+            .await
+            .unwrap();
+        assert!(
+            tokio::time::timeout(Duration::from_millis(20), wheel.changed())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,247 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! One-shot waiters for server output.
+//!
+//! A task that sends a command and needs the line it prints registers a
+//! matcher first, then sends the command, then awaits the receiver with a
+//! timeout. Whoever reads stdout resolves every waiter whose matcher accepts
+//! the line. Registering before sending is what makes this race-free: the
+//! response cannot be printed before anyone is listening for it.
+//!
+//! [`PlayerRegistry`](crate::players::PlayerRegistry) uses this keyed by
+//! player name for `pos()`/`dimension()`; `wrapper:wait_for` and the stdout
+//! path of `wrapper:query` use it with a plugin's regex through
+//! [`LineWaiters`].
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use regex::Regex;
+use tokio::sync::{OwnedMutexGuard, mpsc, oneshot};
+
+pub struct Waiters<M, V> {
+    pending: Mutex<Vec<(M, oneshot::Sender<V>)>>,
+}
+
+impl<M, V> Default for Waiters<M, V> {
+    fn default() -> Self {
+        Self {
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<M, V> Waiters<M, V> {
+    pub fn register(&self, matcher: M) -> oneshot::Receiver<V> {
+        let (tx, rx) = oneshot::channel();
+        self.pending.lock().unwrap().push((matcher, tx));
+        rx
+    }
+
+    /// Hand `value(matcher)` to every waiter it is `Some` for and remove them;
+    /// returns how many were resolved. Waiters that already gave up (timed
+    /// out) are dropped on the way.
+    pub fn resolve(&self, mut value: impl FnMut(&M) -> Option<V>) -> usize {
+        let mut g = self.pending.lock().unwrap();
+        let mut resolved = 0;
+        for (m, tx) in std::mem::take(&mut *g) {
+            if tx.is_closed() {
+                continue;
+            }
+            match value(&m) {
+                Some(v) => {
+                    let _ = tx.send(v);
+                    resolved += 1;
+                }
+                None => g.push((m, tx)),
+            }
+        }
+        resolved
+    }
+
+    /// Drop the waiters `pred` selects; their receivers see the sender gone.
+    pub fn cancel_where(&self, pred: impl Fn(&M) -> bool) {
+        self.pending.lock().unwrap().retain(|(m, _)| !pred(m));
+    }
+
+    pub fn clear(&self) {
+        self.pending.lock().unwrap().clear();
+    }
+
+    /// Register `matcher`, then queue `command` (if any), then wait up to
+    /// `timeout` for a line to resolve it. `None` on timeout, cancellation,
+    /// or a closed command queue.
+    pub async fn request(
+        &self,
+        matcher: M,
+        cmd_tx: &mpsc::Sender<String>,
+        command: Option<&str>,
+        timeout: Duration,
+    ) -> Option<V> {
+        let rx = self.register(matcher);
+        if let Some(cmd) = command {
+            cmd_tx.send(format!("{cmd}\n")).await.ok()?;
+        }
+        match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(v)) => Some(v),
+            _ => None,
+        }
+    }
+}
+
+/// A `wrapper:wait_for` matcher.
+pub struct LineMatch {
+    pub plugin: String,
+    pub regex: Regex,
+}
+
+/// Resolved with the line followed by its capture groups, like trigger
+/// callback arguments (an unmatched optional group is "").
+pub type LineWaiters = Arc<Waiters<LineMatch, Vec<String>>>;
+
+/// `text` followed by the capture groups of `regex` in it, or `None` if it
+/// does not match.
+pub fn captures(regex: &Regex, text: &str) -> Option<Vec<String>> {
+    let caps = regex.captures(text)?;
+    let mut args = Vec::with_capacity(caps.len());
+    args.push(text.to_string());
+    for i in 1..caps.len() {
+        args.push(caps.get(i).map_or("", |c| c.as_str()).to_string());
+    }
+    Some(args)
+}
+
+/// Feed one stdout line to the `wrapper:wait_for` / `wrapper:query` waiters.
+pub fn observe_line(waiters: &LineWaiters, line: &str) -> usize {
+    waiters.resolve(|m| captures(&m.regex, line))
+}
+
+/// One lock per response pattern for `wrapper:query` over stdout. Replies on
+/// stdout carry no request id, so two queries waiting for the same pattern
+/// would both take the first reply; holding the pattern's lock from sending
+/// until the reply (or timeout) makes them take turns instead.
+#[derive(Default)]
+pub struct QueryLocks {
+    locks: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl QueryLocks {
+    pub async fn acquire(&self, pattern: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut g = self.locks.lock().unwrap();
+            // Drop locks nobody holds or waits for.
+            g.retain(|_, l| Arc::strong_count(l) > 1);
+            g.entry(pattern.to_string()).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_match(plugin: &str, pattern: &str) -> LineMatch {
+        LineMatch {
+            plugin: plugin.to_string(),
+            regex: Regex::new(pattern).unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn resolves_matching_waiters_once() {
+        let w = Waiters::<String, u32>::default();
+        let a1 = w.register("a".into());
+        let a2 = w.register("a".into());
+        let b = w.register("b".into());
+        assert_eq!(w.resolve(|k| (k == "a").then_some(7)), 2);
+        assert_eq!((a1.await.unwrap(), a2.await.unwrap()), (7, 7));
+        // Resolved waiters are gone; the other key is still pending.
+        assert_eq!(w.resolve(|k| (k == "a").then_some(8)), 0);
+        assert_eq!(w.pending.lock().unwrap().len(), 1);
+        w.cancel_where(|k| k == "b");
+        assert!(b.await.is_err());
+    }
+
+    #[tokio::test]
+    async fn request_registers_before_sending() {
+        let w: LineWaiters = Arc::default();
+        let (tx, mut rx) = mpsc::channel::<String>(4);
+        let server = {
+            let w = w.clone();
+            tokio::spawn(async move {
+                // Answer the command the moment it arrives: the waiter must
+                // already be registered by then.
+                let cmd = rx.recv().await.unwrap();
+                assert_eq!(cmd, "list\n");
+                observe_line(&w, "There are 2 of a max of 20 players online: a, b");
+            })
+        };
+        let caps = w
+            .request(
+                line_match("p", r"There are (\d+) of a max of (\d+)( x)?"),
+                &tx,
+                Some("list"),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+        server.await.unwrap();
+        assert_eq!(caps[1..], ["2", "20", ""]);
+        assert!(caps[0].starts_with("There are 2"));
+    }
+
+    #[tokio::test]
+    async fn query_locks_serialize_per_pattern() {
+        let locks = Arc::new(QueryLocks::default());
+        let first = locks.acquire("a").await;
+        // A different pattern is independent.
+        drop(locks.acquire("b").await);
+        let waiting = {
+            let locks = locks.clone();
+            tokio::spawn(async move { drop(locks.acquire("a").await) })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert!(!waiting.is_finished());
+        drop(first);
+        tokio::time::timeout(Duration::from_secs(1), waiting)
+            .await
+            .unwrap()
+            .unwrap();
+        drop(locks.acquire("c").await);
+        assert_eq!(locks.locks.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn request_times_out_and_is_pruned() {
+        let w: LineWaiters = Arc::default();
+        let (tx, _rx) = mpsc::channel::<String>(4);
+        let got = w
+            .request(
+                line_match("p", "never"),
+                &tx,
+                None,
+                Duration::from_millis(20),
+            )
+            .await;
+        assert_eq!(got, None);
+        // The dead entry goes with the next line observed.
+        assert_eq!(observe_line(&w, "anything"), 0);
+        assert!(w.pending.lock().unwrap().is_empty());
+    }
+}
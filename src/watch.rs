@@ -0,0 +1,355 @@
+// MCRW is a extendable management framework for minecraft
+// Copyright (C) 2026  YUHAN LI
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+//! File watcher for configuration and (with `[dev] watch = true`) plugin code.
+//!
+//! Always on: an edit to `lua_plugins/<dir>/config.json` becomes
+//! [`ControlMsg::ConfigChanged`] for that plugin, and an edit to `mcrw.toml`
+//! becomes [`ControlMsg::McrwConfigChanged`].
+//!
+//! With `[dev] watch`, code edits also turn into the same control messages an
+//! operator would type: a change to a `.lua` file or `meta.toml` inside
+//! `lua_plugins/<dir>/` becomes [`ControlMsg::PluginChanged`] for that plugin,
+//! and a change to `trigger_config.toml` or to a library in `lua_libs/` becomes
+//! a full [`ControlMsg::Reload`].
+//!
+//! Editors save in bursts (temp file, rename, chmod…), so events are collected
+//! until no new relevant event has arrived for `debounce_ms`, then flushed as
+//! one message per changed plugin. Runs on a plain thread: notify delivers on
+//! a std channel, and `blocking_send` hands the result to the main loop.
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{Receiver, RecvTimeoutError, channel};
+use std::time::{Duration, Instant};
+
+use notify::{EventKind, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::libs::LIBS_DIR;
+use crate::lua_ctx::ControlMsg;
+use crate::{teprintln, tprintln};
+
+// What a changed path asks for.
+#[derive(Debug, PartialEq)]
+enum Change {
+    Plugin(String),
+    Config(String),
+    All,
+    Mcrw,
+}
+
+impl Change {
+    // Code changes, only acted on with `[dev] watch`.
+    fn is_code(&self) -> bool {
+        matches!(self, Change::Plugin(_) | Change::All)
+    }
+}
+
+// Map a changed path (relative to the server directory) to what must be
+// reloaded; `None` for files the wrapper does not read (editor swap files,
+// python scripts, plugin data…).
+fn classify(rel: &Path) -> Option<Change> {
+    if rel == Path::new("trigger_config.toml") {
+        return Some(Change::All);
+    }
+    if rel == Path::new("mcrw.toml") {
+        return Some(Change::Mcrw);
+    }
+    // Any plugin may use a library, so a library edit reloads them all.
+    if rel.starts_with(LIBS_DIR) {
+        let file = rel.file_name()?.to_str()?;
+        return (file.ends_with(".lua") || file == "meta.toml").then_some(Change::All);
+    }
+    let mut parts = rel.strip_prefix("lua_plugins").ok()?.components();
+    let dir = parts.next()?.as_os_str().to_str()?.to_string();
+    // Files directly in lua_plugins/ are not plugins.
+    parts.next()?;
+    let file = rel.file_name()?.to_str()?;
+    // Only the plugin's own config.json, not one in a subdirectory.
+    if file == "config.json" && parts.next().is_none() {
+        return Some(Change::Config(dir));
+    }
+    let relevant = file.ends_with(".lua") || file == "meta.toml";
+    relevant.then_some(Change::Plugin(dir))
+}
+
+// Changes collected during one debounce window.
+#[derive(Default)]
+struct Pending {
+    plugins: BTreeSet<String>,
+    configs: BTreeSet<String>,
+    all: bool,
+    mcrw: bool,
+}
+
+impl Pending {
+    fn add(&mut self, change: Change) {
+        match change {
+            Change::Plugin(dir) => {
+                self.plugins.insert(dir);
+            }
+            Change::Config(dir) => {
+                self.configs.insert(dir);
+            }
+            Change::All => self.all = true,
+            Change::Mcrw => self.mcrw = true,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.all && !self.mcrw && self.plugins.is_empty() && self.configs.is_empty()
+    }
+
+    // mcrw.toml goes first, so a reload already sees its new settings. A full
+    // reload covers every plugin, and reloading a plugin re-runs its
+    // load_config, so both absorb config-only changes.
+    fn take(&mut self) -> Vec<ControlMsg> {
+        let pending = std::mem::take(self);
+        let mut msgs = Vec::new();
+        if pending.mcrw {
+            msgs.push(ControlMsg::McrwConfigChanged);
+        }
+        if pending.all {
+            msgs.push(ControlMsg::Reload);
+            return msgs;
+        }
+        let configs: Vec<String> = pending
+            .configs
+            .into_iter()
+            .filter(|d| !pending.plugins.contains(d))
+            .collect();
+        msgs.extend(pending.plugins.into_iter().map(ControlMsg::PluginChanged));
+        msgs.extend(configs.into_iter().map(ControlMsg::ConfigChanged));
+        msgs
+    }
+}
+
+// Collect raw events into `pending` and flush once `debounce` has passed
+// since the last relevant one; ignored events do not extend the window.
+// Returns when the watcher or the main loop goes away.
+fn debounce_events(
+    raw_rx: Receiver<notify::Result<notify::Event>>,
+    base: &Path,
+    debounce: Duration,
+    watch_code: bool,
+    ctl_tx: &mpsc::Sender<ControlMsg>,
+) {
+    let mut pending = Pending::default();
+    let mut deadline = Instant::now();
+    loop {
+        let event = if pending.is_empty() {
+            match raw_rx.recv() {
+                Ok(ev) => ev,
+                Err(_) => return,
+            }
+        } else {
+            match raw_rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(ev) => ev,
+                Err(RecvTimeoutError::Timeout) => {
+                    for msg in pending.take() {
+                        if ctl_tx.blocking_send(msg).is_err() {
+                            return; // main loop gone → wrapper shutting down
+                        }
+                    }
+                    continue;
+                }
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        };
+        let event = match event {
+            Ok(ev) => ev,
+            Err(e) => {
+                teprintln!("[MCRW] [ERROR] file watcher: {e}");
+                continue;
+            }
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            continue;
+        }
+        for path in &event.paths {
+            if let Some(change) = path.strip_prefix(base).ok().and_then(classify) {
+                if watch_code || !change.is_code() {
+                    pending.add(change);
+                    deadline = Instant::now() + debounce;
+                }
+            }
+        }
+    }
+}
+
+/// Start watching `base` (the server directory); `watch_code` is `[dev] watch`.
+/// Errors (e.g. the inotify watch limit) are returned so the caller can log
+/// them and carry on without hot reload.
+pub fn spawn_file_watcher(
+    base: PathBuf,
+    debounce: Duration,
+    watch_code: bool,
+    ctl_tx: mpsc::Sender<ControlMsg>,
+) -> notify::Result<()> {
+    let (raw_tx, raw_rx) = channel::<notify::Result<notify::Event>>();
+    let mut watcher = notify::recommended_watcher(raw_tx)?;
+    watcher.watch(&base.join("lua_plugins"), RecursiveMode::Recursive)?;
+    // trigger_config.toml may not exist yet, so watch its directory (only
+    // direct children; the world folders below are never reported). mcrw.toml
+    // lives there too.
+    watcher.watch(&base, RecursiveMode::NonRecursive)?;
+    // lua_libs/ is optional; one created later is only picked up on restart.
+    if base.join(LIBS_DIR).is_dir() {
+        watcher.watch(&base.join(LIBS_DIR), RecursiveMode::Recursive)?;
+    }
+
+    std::thread::spawn(move || {
+        // Dropping the watcher stops the OS watch; keep it for the thread's life.
+        let _watcher = watcher;
+        debounce_events(raw_rx, &base, debounce, watch_code, &ctl_tx);
+    });
+    if watch_code {
+        tprintln!("[MCRW] Watching lua_plugins/, lua_libs/ and trigger_config.toml for changes.");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_plugin_files() {
+        let plugin = |dir: &str| Some(Change::Plugin(dir.to_string()));
+        assert_eq!(
+            classify(Path::new("lua_plugins/motd/init.lua")),
+            plugin("motd")
+        );
+        assert_eq!(
+            classify(Path::new("lua_plugins/motd/lib/util.lua")),
+            plugin("motd")
+        );
+        assert_eq!(
+            classify(Path::new("lua_plugins/motd/meta.toml")),
+            plugin("motd")
+        );
+        assert_eq!(
+            classify(Path::new("lua_plugins/motd/data/config.json")),
+            None
+        );
+        assert_eq!(
+            classify(Path::new("trigger_config.toml")),
+            Some(Change::All)
+        );
+        assert_eq!(
+            classify(Path::new("lua_libs/strings.lua")),
+            Some(Change::All)
+        );
+        assert_eq!(
+            classify(Path::new("lua_libs/tellraw/meta.toml")),
+            Some(Change::All)
+        );
+        assert_eq!(classify(Path::new("lua_libs/tellraw/README.md")), None);
+    }
+
+    #[test]
+    fn classifies_config_files() {
+        assert_eq!(
+            classify(Path::new("lua_plugins/motd/config.json")),
+            Some(Change::Config("motd".into()))
+        );
+        assert_eq!(classify(Path::new("mcrw.toml")), Some(Change::Mcrw));
+    }
+
+    #[test]
+    fn ignores_unrelated_files() {
+        assert_eq!(classify(Path::new("lua_plugins/motd/.init.lua.swp")), None);
+        assert_eq!(classify(Path::new("lua_plugins/motd/job.py")), None);
+        assert_eq!(classify(Path::new("lua_plugins/README.lua")), None);
+        assert_eq!(classify(Path::new("lua_plugins/motd")), None);
+        assert_eq!(classify(Path::new("server.properties")), None);
+        assert_eq!(classify(Path::new("world/level.dat")), None);
+    }
+
+    #[test]
+    fn pending_dedups_and_full_reload_wins() {
+        let mut p = Pending::default();
+        assert!(p.is_empty());
+        p.add(Change::Plugin("b".into()));
+        p.add(Change::Plugin("a".into()));
+        p.add(Change::Plugin("b".into()));
+        assert_eq!(
+            p.take(),
+            [
+                ControlMsg::PluginChanged("a".into()),
+                ControlMsg::PluginChanged("b".into())
+            ]
+        );
+        assert!(p.is_empty());
+
+        p.add(Change::Plugin("a".into()));
+        p.add(Change::All);
+        assert_eq!(p.take(), [ControlMsg::Reload]);
+    }
+
+    #[test]
+    fn config_changes_yield_to_code_reloads() {
+        let mut p = Pending::default();
+        p.add(Change::Config("a".into()));
+        p.add(Change::Config("b".into()));
+        p.add(Change::Plugin("b".into()));
+        p.add(Change::Mcrw);
+        assert_eq!(
+            p.take(),
+            [
+                ControlMsg::McrwConfigChanged,
+                ControlMsg::PluginChanged("b".into()),
+                ControlMsg::ConfigChanged("a".into()),
+            ]
+        );
+
+        p.add(Change::Config("a".into()));
+        p.add(Change::All);
+        assert_eq!(p.take(), [ControlMsg::Reload]);
+    }
+
+    // A plugin rewriting its own data file keeps producing events; they must
+    // not hold back the flush of a code change made meanwhile.
+    #[test]
+    fn irrelevant_events_do_not_extend_the_window() {
+        let base = PathBuf::from("/srv/mc");
+        let debounce = Duration::from_millis(100);
+        let (raw_tx, raw_rx) = channel();
+        let (ctl_tx, mut ctl_rx) = mpsc::channel(8);
+        let watched = base.clone();
+        std::thread::spawn(move || debounce_events(raw_rx, &watched, debounce, true, &ctl_tx));
+        let modified = |rel: &str| {
+            Ok(
+                notify::Event::new(EventKind::Modify(notify::event::ModifyKind::Any))
+                    .add_path(base.join(rel)),
+            )
+        };
+
+        raw_tx.send(modified("lua_plugins/motd/init.lua")).unwrap();
+        let start = Instant::now();
+        while start.elapsed() < debounce * 5 {
+            raw_tx.send(modified("lua_plugins/motd/data.json")).unwrap();
+            raw_tx.send(modified("server.properties")).unwrap();
+            if let Ok(msg) = ctl_rx.try_recv() {
+                assert_eq!(msg, ControlMsg::PluginChanged("motd".into()));
+                return;
+            }
+            std::thread::sleep(debounce / 4);
+        }
+        panic!("flush held back by irrelevant events");
+    }
+}
@@ -8,12 +8,12 @@
 -- type checking. It is NOT loaded or executed at runtime — the `---@meta`
 -- tag tells the language server to treat it as definitions only.
 --
--- This is the canonical, version-controlled copy. To use it in a plugin
--- workspace, point a `.luarc.json` at this directory (see the README in
--- this folder, or docs/plugin-development.md §2.5).
+-- To use it in a plugin workspace, point a `.luarc.json` at this directory
+-- (see the README in this folder, or docs/plugin-development.md §2.5).
 --
--- Keep this file in sync with `src/lua_ctx.rs` (the Rust side that
--- registers these methods).
+-- GENERATED by `mcrstw gen-types` from src/api_spec.rs; do not edit by hand.
+-- Change the declarations there and run
+--     cargo run -- gen-types tools/lua-types/mcrw.lua
 
 --------------------------------------------------------------------------------
 -- Callback signatures
@@ -30,6 +30,13 @@
 --- not participate is the empty string).
 ---@alias mcrw.TriggerCallback fun(line: string, ...: string): mcrw.Commands
 
+--- Callback of a trigger registered with a `mcrw.LogFilter`. The varargs are the
+--- capture groups of `message` in the record's message.
+---@alias mcrw.FilterCallback fun(record: mcrw.LogRecord, ...: string): mcrw.Commands
+
+--- `wrapper:register_on_exception` callback. `record.exception` is always set.
+---@alias mcrw.ExceptionCallback fun(record: mcrw.LogRecord): mcrw.Commands
+
 --- Cron callback. `fire_time` is the scheduled fire time as an RFC 3339 /
 --- ISO 8601 string in the local timezone, e.g. "2026-05-21T03:00:00+08:00".
 ---@alias mcrw.CronCallback fun(fire_time: string): mcrw.Commands
@@ -42,46 +49,67 @@
 --- process has already exited, so no commands can be delivered.
 ---@alias mcrw.LifecycleCallback fun()
 
+--- Join/leave callback. Receives the affected player handle; may return a list
+--- of commands to run.
+---@alias mcrw.PlayerCallback fun(player: mcrw.Player): mcrw.Commands
+
 --------------------------------------------------------------------------------
 -- Data shapes
 --------------------------------------------------------------------------------
 
+--- A `wrapper:load_config` schema entry: a type name, or a table of checks.
+---@alias mcrw.ConfigType "string"|"number"|"integer"|"boolean"|"table"|"array"|"any"
+
+---@alias mcrw.ConfigSpec mcrw.ConfigType|mcrw.ConfigSpecTable
+
+---@class mcrw.ConfigSpecTable
+---@field type mcrw.ConfigType
+---@field enum? any[] Allowed values.
+---@field min? number Lower bound (value, or length of a string/array).
+---@field max? number Upper bound (value, or length of a string/array).
+---@field fields? table<string, mcrw.ConfigSpec> Nested fields of a "table".
+---@field items? mcrw.ConfigSpec Spec of every element of an "array".
+---@field optional? boolean The key may be absent.
+
 --- Parsed contents of the plugin's `meta.toml`, as returned by `wrapper:meta()`.
 ---@class mcrw.Meta
----@field name string            Plugin display name.
----@field version string         Plugin version string.
----@field description string     Optional; "" when absent.
----@field authors string[]       Optional; {} when absent.
----@field dependencies string[]  Optional; {} when absent. Not yet enforced.
----@field mcrw_version string    Optional; "" when absent. Not yet enforced.
+---@field name string Plugin display name.
+---@field version string Plugin version string.
+---@field description string Optional; "" when absent.
+---@field authors string[] Optional; {} when absent.
+---@field dependencies string[] Optional; {} when absent. Loaded before this plugin.
+---@field mcrw_version string Optional; "" when absent. SemVer requirement on the wrapper version.
+---@field permissions string[] Optional; {} when absent. Capabilities needed under [permissions] enforce.
+---@field isolation "shared"|"isolated" Optional; "shared" when absent. "isolated" runs in its own Lua state.
+---@field libs table<string,string> Optional; {} when absent. lua_libs/ library name → SemVer requirement.
 
 --- Options for `wrapper:run_python`.
 ---@class mcrw.PythonOpts
----@field stdin? string             Data piped to the script's stdin.
----@field timeout_ms? integer       Per-call timeout; defaults to mcrw.toml's python.default_timeout_ms (30000).
+---@field stdin? string Data piped to the script's stdin.
+---@field timeout_ms? integer Per-call timeout; defaults to mcrw.toml's python.default_timeout_ms (30000).
 ---@field env? table<string,string> Extra environment variables for the child process.
 
 --- Result of `wrapper:run_python`.
 ---@class mcrw.PythonResult
----@field stdout any     The script's last non-empty stdout line, JSON-decoded. `nil` if stdout was empty.
----@field stderr string  The script's full stderr output.
----@field code integer   The process exit code. `-1` if the process was killed (e.g. timeout).
+---@field stdout any The script's last non-empty stdout line, JSON-decoded. `nil` if stdout was empty.
+---@field stderr string The script's full stderr output.
+---@field code integer The process exit code. `-1` if the process was killed (e.g. timeout).
 
 --- Options for `wrapper:http_request`.
 ---@class mcrw.HttpOpts
----@field url string                The request URL. Required.
----@field method? string            HTTP method; defaults to "GET". Case-insensitive.
+---@field url string The request URL. Required.
+---@field method? string HTTP method; defaults to "GET". Case-insensitive.
 ---@field headers? table<string,string> Request headers.
----@field body? string              Raw request body. Mutually exclusive with `json`.
----@field json? any                 A value to JSON-encode as the body; also sets Content-Type: application/json unless already set. Mutually exclusive with `body`.
----@field timeout_ms? integer       Per-request timeout; defaults to mcrw.toml's http.default_timeout_ms (30000).
+---@field body? string Raw request body. Mutually exclusive with `json`.
+---@field json? any A value to JSON-encode as the body; also sets Content-Type: application/json unless already set. Mutually exclusive with `body`.
+---@field timeout_ms? integer Per-request timeout; defaults to mcrw.toml's http.default_timeout_ms (30000).
 
 --- Result of `wrapper:http_request`.
 ---@class mcrw.HttpResponse
----@field status integer             The HTTP status code.
----@field ok boolean                 True if `status` is in the 200–299 range.
+---@field status integer The HTTP status code.
+---@field ok boolean True if `status` is in the 200–299 range.
 ---@field headers table<string,string> Response headers, with lowercased names.
----@field body string                The full response body as a string. Use `wrapper:json_decode` to parse JSON.
+---@field body string The full response body as a string. Use `wrapper:json_decode` to parse JSON.
 
 --- A live player position, as returned by `Player:pos()`.
 ---@class mcrw.Pos
@@ -89,17 +117,84 @@
 ---@field y number
 ---@field z number
 
+--- Returned by `wrapper:server_info()`.
+---@class mcrw.ServerInfo
+---@field version? string Minecraft version, e.g. "1.20.4".
+---@field flavor string "vanilla", "paper", "fabric", "forge", "velocity", or "auto" until detected.
+---@field level_name? string
+---@field port? integer The game port the server listens on.
+---@field rcon_port? integer Set while RCON is enabled in server.properties.
+---@field query_port? integer Set while the query protocol is enabled in server.properties.
+---@field max_players? integer
+---@field online_mode? boolean
+---@field startup_secs? number Startup time from the server's "Done (3.021s)!" line.
+
+--- A `wrapper:register` filter. Unset fields match any line.
+---@class mcrw.LogFilter
+---@field level? string Log level, e.g. "WARN" (case-insensitive).
+---@field thread? string Thread name, e.g. "Server thread". Paper lines have none.
+---@field logger? string Logger name (Fabric and Forge lines only).
+---@field message? string Rust regex matched against the message after the prefix.
+
+--- A server log line split into its prefix fields and message.
+---@class mcrw.LogRecord
+---@field time? string Timestamp as printed, e.g. "12:00:01".
+---@field thread? string
+---@field level? string
+---@field logger? string
+---@field message string The line after the prefix; the whole line if it has none.
+---@field raw string The full line.
+---@field continuation? string[] Lines folded into this record (stack frames etc.), unparsed.
+---@field exception? mcrw.ExceptionInfo Set when the record contains a Java exception.
+
+--- A Java exception found in a log record.
+---@class mcrw.ExceptionInfo
+---@field class string Fully qualified class, e.g. "java.lang.IllegalStateException".
+---@field message? string The text after the class; nil if there is none.
+---@field trace string The exception line and every line after it, newline-separated.
+
+--- Options for `wrapper:wait_for`.
+---@class mcrw.WaitForOpts
+---@field timeout_ms? integer Give up and return nil after this long. Default 5000.
+---@field after_command? string Command to send once the matcher is registered; needs "command".
+
+--- Options for `wrapper:query`.
+---@class mcrw.QueryOpts
+---@field response_pattern string Rust regex the reply must match, searched anywhere in the line or RCON reply.
+---@field timeout_ms? integer Give up and return nil after this long. Default 5000.
+
+--- Options for `wrapper:stats`.
+---@class mcrw.StatsOpts
+---@field plugin? string Plugin directory to report; `"*"` for all. Defaults to this plugin.
+---@field reset? boolean Clear the reported entries after reading them.
+
+--- One callback's metrics, as returned by `wrapper:stats`.
+---@class mcrw.Stat
+---@field plugin string Plugin directory.
+---@field kind "trigger"|"cron"|"timer"|"join"|"leave"|"lifecycle"|"stop"|"crash"|"unload"
+---@field key string Trigger pattern, cron expression, `after` / `every <ms>ms` for timers, lifecycle event or unload reason; empty otherwise.
+---@field calls integer
+---@field errors integer Calls that raised an error (or, for unload hooks, timed out).
+---@field commands integer Server commands the callback returned.
+---@field total_ms number Total wall time, including time spent yielded.
+---@field p50_ms number Median over the last 512 calls.
+---@field p99_ms number 99th percentile over the last 512 calls.
+
+--------------------------------------------------------------------------------
+-- Handles
+--------------------------------------------------------------------------------
+
 --- A player handle, returned by `wrapper:players()` / `wrapper:player()` and
 --- passed to join/leave callbacks. The fields are read from the cached registry
 --- state; `pos()`/`dimension()` fetch live data on demand.
 ---@class mcrw.Player
----@field name string             The player name.
----@field uuid string|nil         UUID, once seen in the auth log; nil otherwise.
----@field ip string|nil           Last login IP, if known.
----@field online boolean          Whether the player is currently online.
----@field first_join integer|nil  Unix seconds of the first-ever join (persisted across restarts).
----@field last_seen integer       Unix seconds of the last seen join/leave.
----@field join_time integer|nil   Unix seconds of the current session's join; nil when offline.
+---@field name string The player name.
+---@field uuid string|nil UUID, once seen in the auth log; nil otherwise.
+---@field ip string|nil Last login IP, if known.
+---@field online boolean Whether the player is currently online.
+---@field first_join integer|nil Unix seconds of the first-ever join (persisted across restarts).
+---@field last_seen integer Unix seconds of the last seen join/leave.
+---@field join_time integer|nil Unix seconds of the current session's join; nil when offline.
 local Player = {}
 
 --- Live coordinates for this player. Yields until the lookup resolves. Returns
@@ -114,10 +209,6 @@ function Player:pos() end
 ---@return string|nil
 function Player:dimension() end
 
---- Join/leave callback. Receives the affected player handle; may return a list
---- of commands to run.
----@alias mcrw.PlayerCallback fun(player: mcrw.Player): mcrw.Commands
-
 --- A persistent key-value store handle, bound to one namespace and returned by
 --- `wrapper:store([namespace])`. Each namespace is a flat `string -> value` map
 --- (a key like `"homes.bed"` is one opaque key, not a nested path). Values may be
@@ -136,7 +227,7 @@ function Store:get(key) end
 --- and `delete(k)` are equivalent). The write is auto-saved within ~5s; call
 --- `flush()` to persist immediately.
 ---@param key string
----@param value any  Any JSON-serialisable value, or nil to delete.
+---@param value any Any JSON-serialisable value, or nil to delete.
 function Store:set(key, value) end
 
 --- Delete `key` from this namespace (no-op if absent).
@@ -152,6 +243,19 @@ function Store:keys() end
 --- be lost on a crash. Writes are atomic (temp file + rename).
 function Store:flush() end
 
+--- A pending timer, returned by `wrapper:after` and `wrapper:every`.
+---@class mcrw.Timer
+local Timer = {}
+
+--- Stop the timer. Returns `false` if it already fired (`after`), was cancelled,
+--- or was dropped by a reload of its plugin.
+---@return boolean
+function Timer:cancel() end
+
+--- `true` while the timer is still scheduled.
+---@return boolean
+function Timer:active() end
+
 --------------------------------------------------------------------------------
 -- The `wrapper` handle (per-plugin), returned by `Server:get_context`.
 --------------------------------------------------------------------------------
@@ -164,10 +268,16 @@ local Wrapper = {}
 --- lookaround). On a match, `callback` is invoked with the line and its capture
 --- groups, and may return a list of commands to run.
 ---
+--- With a `mcrw.LogFilter` table instead, the line's log prefix must match the
+--- filter, the regex is matched against the message only, and the callback gets
+--- the parsed `mcrw.LogRecord` in place of the line. Filter triggers see whole
+--- records: continuation lines such as stack trace frames are folded into the
+--- record before them (see `[log]` in mcrw.toml) instead of firing on their own.
+---
 --- Note: Lua string escapes apply first, so a literal backslash in the regex
 --- must be written `\\` in the Lua string.
----@param pattern string             Rust regex. Raises if the pattern fails to compile.
----@param callback mcrw.TriggerCallback
+---@param pattern string|mcrw.LogFilter Rust regex, or a filter table. Raises if the regex fails to compile.
+---@param callback mcrw.TriggerCallback|mcrw.FilterCallback
 function Wrapper:register(pattern, callback) end
 
 --- Register a recurring cron job. `expr` is a 6-field cron expression
@@ -177,10 +287,40 @@ function Wrapper:register(pattern, callback) end
 ---
 --- Overlap is NOT prevented: if a previous run has not finished when the next
 --- tick fires, both run concurrently. Guard with a Lua flag if needed.
----@param expr string                6-field cron expression. Raises if invalid or has no future fire time.
+---@param expr string 6-field cron expression. Raises if invalid or has no future fire time.
 ---@param callback mcrw.CronCallback
 function Wrapper:register_cron(expr, callback) end
 
+--- Run `callback` once, `ms` milliseconds from now. Like a cron callback it may
+--- return commands. The timer belongs to this plugin and is dropped when it is
+--- reloaded or unloaded.
+---@param ms integer
+---@param callback fun(): mcrw.Commands?
+---@return mcrw.Timer
+function Wrapper:after(ms, callback) end
+
+--- Run `callback` every `ms` milliseconds (at least 1), first after one period,
+--- until cancelled or the plugin is reloaded. Periods missed while the wrapper
+--- was busy are skipped, not made up.
+---@param ms integer
+---@param callback fun(): mcrw.Commands?
+---@return mcrw.Timer
+function Wrapper:every(ms, callback) end
+
+--- Yield the running callback for `ms` milliseconds. Other callbacks keep running
+--- meanwhile; later callbacks for the same line wait for this one.
+---@param ms integer
+function Wrapper:sleep(ms) end
+
+--- Wait for the next server line matching `pattern`, optionally sending a command
+--- first; the matcher is in place before the command goes out. Returns the line and
+--- its captures, like a trigger callback receives them, or nil on timeout.
+---@param pattern string Rust regex.
+---@param opts? mcrw.WaitForOpts
+---@return string?
+---@return string ...
+function Wrapper:wait_for(pattern, opts) end
+
 --- Register a callback for when the server finishes starting up (the "Done"
 --- line, configurable via trigger_config.toml). May return commands.
 ---@param callback mcrw.StartCallback
@@ -194,6 +334,26 @@ function Wrapper:register_on_stop(callback) end
 ---@param callback mcrw.LifecycleCallback
 function Wrapper:register_on_crash(callback) end
 
+--- Register a callback for Java exceptions in the server log. Called with each
+--- assembled `mcrw.LogRecord` whose lines contain an exception and at least one
+--- `at ...` frame; `record.exception` holds the class, message and full trace.
+--- May return commands.
+---@param callback mcrw.ExceptionCallback
+function Wrapper:register_on_exception(callback) end
+
+--- Register a callback run before this plugin's registrations are cleared.
+--- `reason` is "reload", "unload" or "shutdown". Each call is limited to
+--- `[reload] unload_timeout_ms` (default 2000 ms).
+---@param callback fun(reason: "reload"|"unload"|"shutdown")
+function Wrapper:register_on_unload(callback) end
+
+--- Register a callback run once every plugin of the current load pass has
+--- initialized (startup, `!reload`, or this plugin's own `!reload`/`!load`).
+--- `reason` is "load", or "rollback" when a failed reload put this instance
+--- back after its unload callbacks ran.
+---@param callback fun(reason: "load"|"rollback")
+function Wrapper:register_on_load_complete(callback) end
+
 --- Register a callback fired when a player joins the game. The callback receives
 --- the player handle and may return commands.
 ---@param callback mcrw.PlayerCallback
@@ -222,6 +382,13 @@ function Wrapper:player(name) end
 ---@return boolean
 function Wrapper:is_rcon() end
 
+--- What the wrapper knows about the server: version, flavor, level name, ports,
+--- max players, online mode and startup time. Combines `server.properties` with
+--- the server's startup lines; fields not known yet (e.g. before the server is
+--- up) are nil. Returns a fresh snapshot on each call.
+---@return mcrw.ServerInfo
+function Wrapper:server_info() end
+
 --- Run an arbitrary command over RCON and return its output text. Unlike
 --- `wrapper:command` (fire-and-forget to stdin), this captures the response.
 --- Yields until the response arrives. RAISES a Lua error if RCON is not enabled,
@@ -233,10 +400,20 @@ function Wrapper:is_rcon() end
 ---   local players = wrapper:rcon_command("list")
 --- end
 --- ```
----@param cmd string  The server command to run (no leading slash).
----@return string     The command's output text.
+---@param cmd string The server command to run (no leading slash).
+---@return string The command's output text.
 function Wrapper:rcon_command(cmd) end
 
+--- Run a command and return its reply: over RCON when connected, otherwise on the
+--- console, taking the next server line that matches `response_pattern`. Returns the
+--- reply and the pattern's captures, or nil on timeout or when the reply does not
+--- match. Queries with the same pattern take turns on the console path.
+---@param cmd string The server command to run (no leading slash).
+---@param opts mcrw.QueryOpts
+---@return string?
+---@return string ...
+function Wrapper:query(cmd, opts) end
+
 --- Open a persistent key-value store handle. With no argument you get this
 --- plugin's PRIVATE namespace (isolated from every other plugin). Pass a name to
 --- open a SHARED namespace that any plugin can reach by the same name — use this
@@ -252,7 +429,7 @@ function Wrapper:rcon_command(cmd) end
 --- econ:set("Steve.balance", 100)
 --- econ:flush()                          -- persist now (don't risk the ~5s debounce)
 --- ```
----@param namespace? string  Shared namespace name; omit for this plugin's private store.
+---@param namespace? string Shared namespace name; omit for this plugin's private store.
 ---@return mcrw.Store
 function Wrapper:store(namespace) end
 
@@ -260,7 +437,7 @@ function Wrapper:store(namespace) end
 --- current callback to return. Use this to emit commands from outside a
 --- trigger return value (e.g. between awaited steps). Yields if the command
 --- queue is full (backpressure); resumes when there is room.
----@param cmd string  One command, no trailing newline.
+---@param cmd string One command, no trailing newline.
 function Wrapper:command(cmd) end
 
 --- Check whether `name` is listed in the server's `ops.json`
@@ -271,18 +448,57 @@ function Wrapper:command(cmd) end
 function Wrapper:is_op(name) end
 
 --- Load this plugin's `config.json`, creating it from `defaults` on first run.
---- If the file exists it is returned as-is (no merging of new default keys —
---- handle config migration yourself). The return value has the same shape as
---- `defaults`.
+--- An existing file is deep-merged over `defaults`; keys it lacks are written
+--- back, user values are never changed. The return value has the same shape as
+--- `defaults`. With `schema`, the merged config is validated and every
+--- violation is raised with its JSON path (e.g. `$.rewards.amount`).
 ---@generic T
----@param defaults T  The default config table, written verbatim if no file exists.
+---@param defaults T The default config table.
+---@param schema? table<string, mcrw.ConfigSpec>
 ---@return T
-function Wrapper:load_config(defaults) end
+function Wrapper:load_config(defaults, schema) end
+
+--- Register a callback for edits to this plugin's `config.json`. Called with
+--- the new and previous config after the edit passed `load_config`'s merge and
+--- schema; invalid edits are logged and ignored. Requires a prior `load_config`.
+---@param callback fun(new: table, old: table)
+function Wrapper:register_on_config_change(callback) end
 
 --- Return this plugin's parsed `meta.toml`.
 ---@return mcrw.Meta
 function Wrapper:meta() end
 
+--- `true` if this wrapper provides the `wrapper` method `name`
+--- (e.g. "rcon_command"). Use it to degrade gracefully on older wrappers.
+---@param name string
+---@return boolean
+function Wrapper:has_feature(name) end
+
+--- `true` if this plugin may use the capability `cap` ("http", "python",
+--- "rcon", "command", "fs", "metrics", "store:shared:<name>"). Always `true`
+--- unless the operator enables `[permissions] enforce` in mcrw.toml.
+---@param cap string
+---@return boolean
+function Wrapper:has_permission(cap) end
+
+--- Publish `api` for plugins that list this one in `dependencies`. Calling it
+--- again replaces the earlier export; exports are cleared on `!reload`.
+---@param api table
+function Wrapper:export(api) end
+
+--- Return the table exported by `name` (directory or meta.toml name). `name`
+--- MUST be listed in this plugin's `dependencies`; errors otherwise, or if the
+--- dependency never called `wrapper:export`.
+---@param name string
+---@return table
+function Wrapper:import(name) end
+
+--- Register a callback for when another plugin is quarantined because its
+--- callbacks keep failing (see `[quarantine]` in mcrw.toml). Called with the
+--- plugin's directory and a reason such as "10 consecutive failures".
+---@param callback fun(plugin: string, reason: string)
+function Wrapper:register_on_quarantine(callback) end
+
 --- Print a line to the wrapper console, prefixed with `[<plugin name>]`.
 ---@param msg string
 function Wrapper:log(msg) end
@@ -292,8 +508,8 @@ function Wrapper:log(msg) end
 --- checked (paths escaping via `..` or symlinks are rejected). The script's
 --- last non-empty stdout line MUST be valid JSON (or stdout empty); it is
 --- decoded into `result.stdout`. Yields until the process exits or times out.
----@param script string             Path to the .py file, relative to the plugin dir.
----@param args? string[]            Command-line arguments passed to the script.
+---@param script string Path to the .py file, relative to the plugin dir.
+---@param args? string[] Command-line arguments passed to the script.
 ---@param opts? mcrw.PythonOpts
 ---@return mcrw.PythonResult
 function Wrapper:run_python(script, args, opts) end
@@ -332,6 +548,14 @@ function Wrapper:json_encode(value) end
 ---@return any
 function Wrapper:json_decode(str) end
 
+--- Callback metrics since startup or the last reset, slowest total first; the
+--- same numbers as the `!profile` console command. Returns this plugin's
+--- entries unless `opts.plugin` names another (`"*"` for all plugins), which
+--- needs the "metrics" permission under `[permissions] enforce`.
+---@param opts? mcrw.StatsOpts
+---@return mcrw.Stat[]
+function Wrapper:stats(opts) end
+
 --------------------------------------------------------------------------------
 -- The global `Server` object.
 --------------------------------------------------------------------------------
@@ -348,6 +572,6 @@ Server = {}
 --- ```lua
 --- local wrapper = Server:get_context(...)
 --- ```
----@param module_path string  Pass `...`.
+---@param module_path string Pass `...`.
 ---@return mcrw.Wrapper
 function Server:get_context(module_path) end